@@ -1,6 +1,10 @@
 use std::{fmt::Display, str::FromStr};
 
-use crate::{Pool, domain::user::User};
+use crate::domain::token_scope::TokenScope;
+use crate::{
+    Pool,
+    domain::{api_token::ApiToken, user::User},
+};
 
 use crate::{AppState, AuthError};
 use axum_session_auth::Rights;
@@ -57,11 +61,62 @@ pub async fn get_user() -> Result<Option<User>, ServerFnError> {
     Ok(auth.current_user)
 }
 
+/// A request authenticated with a personal access token instead of a session.
+///
+/// The server inserts it into the request extensions, token requests never have a
+/// session user, so only server functions using [`get_user_with_scope`] accept them.
+#[derive(Clone, Debug)]
+pub struct TokenAuth {
+    pub token: ApiToken,
+    pub user: User,
+}
+
+impl TokenAuth {
+    /// Looks up the user of a bearer token, returns `None` for unknown or expired tokens.
+    pub async fn authenticate(state: &AppState, secret: &str) -> Result<Option<Self>, sqlx::Error> {
+        let Some(token) = state
+            .api_token_repository
+            .get_by_hash(&ApiToken::hash(secret))
+            .await?
+        else {
+            return Ok(None);
+        };
+        if token.is_expired() {
+            return Ok(None);
+        }
+        let user = state.user_repository.get_by_id(token.user_id).await?;
+        state
+            .api_token_repository
+            .touch(token.id, chrono::Utc::now())
+            .await?;
+        Ok(Some(Self { token, user }))
+    }
+}
+
+/// Get the personal access token of the current request
+pub fn token_auth() -> Option<TokenAuth> {
+    use_context::<axum::http::request::Parts>()
+        .and_then(|parts| parts.extensions.get::<TokenAuth>().cloned())
+}
+
+/// Get the current user, either from the session or from a personal access token with `scope`
+/// # Errors
+/// Will return an error if the context is not available or the token lacks the scope
+pub async fn get_user_with_scope(scope: TokenScope) -> Result<Option<User>, ServerFnError> {
+    match token_auth() {
+        Some(token_auth) if token_auth.token.has_scope(scope) => Ok(Some(token_auth.user)),
+        Some(_) => Err(ServerFnError::ServerError(format!(
+            "Token is missing the {scope} scope"
+        ))),
+        None => get_user().await,
+    }
+}
+
 #[server]
 pub async fn is_admin() -> Result<bool, ServerFnError> {
     use crate::auth::get_user;
     let Some(user) = get_user().await? else {
         return Err(ServerFnError::ServerError("Not Logged in".into()));
     };
-    Ok(user.permissions.contains("Admin") || user.permissions.contains("admin"))
+    Ok(user.is_admin())
 }
@@ -0,0 +1,659 @@
+//! Fan-out of live updates between server instances.
+//!
+//! Websockets are served by the instance a client happens to connect to, so
+//! everything that is pushed to clients is published on the [`Backplane`] and
+//! every instance, including the publishing one, delivers it to its own
+//! connections in [`spawn_relay`].
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    config::{BackplaneConfig, BackplaneKind},
+    presence::PresenceUpdate,
+    server_fn::chat::ChatChannelMessages,
+    user_events::UserEvent,
+};
+
+/// Events buffered for slow subscribers before they start missing some,
+/// and for a slow or unreachable Redis before new ones are dropped.
+const CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BackplaneEvent {
+    /// A message for the channel of a group.
+    Chat {
+        group_id: String,
+        message: ChatChannelMessages,
+    },
+    User {
+        user_id: Uuid,
+        event: UserEvent,
+    },
+    Presence(PresenceUpdate),
+    /// A user left a group, their connections must stop receiving its updates.
+    RevokeGroup {
+        user_id: Uuid,
+        group_id: Uuid,
+    },
+    CloseSessions {
+        session_ids: Vec<String>,
+    },
+}
+
+pub trait Backplane: Send + Sync {
+    /// Sends an event to all instances, including this one.
+    fn publish(&self, event: BackplaneEvent);
+    /// The events published by all instances.
+    fn subscribe(&self) -> broadcast::Receiver<BackplaneEvent>;
+}
+
+/// Builds the configured backplane.
+pub fn from_config(config: &BackplaneConfig) -> Arc<dyn Backplane> {
+    match config.kind {
+        BackplaneKind::Local => Arc::new(LocalBackplane::new()),
+        BackplaneKind::Redis => Arc::new(RedisBackplane::new(
+            config.url.clone().unwrap_or_default(),
+            config.channel.clone(),
+        )),
+    }
+}
+
+/// Delivers the events of the backplane to the connections of this instance.
+pub fn spawn_relay(state: AppState) {
+    let mut events = state.backplane.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    log::warn!("Backplane relay skipped {skipped} events");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            match event {
+                BackplaneEvent::Chat { group_id, message } => {
+                    use leptos::prelude::{Owner, provide_context};
+                    let owner = Owner::new();
+                    let channel = owner.with(|| {
+                        provide_context(state.server_signals.clone());
+                        leptos_ws::ChannelSignal::<ChatChannelMessages>::new(&group_id)
+                    });
+                    match channel {
+                        Ok(channel) => {
+                            let _ = channel.send_message(message);
+                        }
+                        Err(error) => {
+                            log::error!("Failed to create channel of {group_id}: {error:?}")
+                        }
+                    }
+                }
+                BackplaneEvent::User { user_id, event } => {
+                    state.user_events.deliver(user_id, event)
+                }
+                BackplaneEvent::Presence(update) => state.presence.apply(update),
+                BackplaneEvent::RevokeGroup { user_id, group_id } => {
+                    state.ws_connections.revoke_group(user_id, group_id)
+                }
+                BackplaneEvent::CloseSessions { session_ids } => {
+                    state.ws_connections.close_sessions(&session_ids)
+                }
+            }
+        }
+    });
+}
+
+/// Backplane of a single instance.
+pub struct LocalBackplane {
+    events: broadcast::Sender<BackplaneEvent>,
+}
+
+impl LocalBackplane {
+    pub fn new() -> Self {
+        Self {
+            events: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl Default for LocalBackplane {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backplane for LocalBackplane {
+    fn publish(&self, event: BackplaneEvent) {
+        let _ = self.events.send(event);
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<BackplaneEvent> {
+        self.events.subscribe()
+    }
+}
+
+/// Backplane on the pub/sub of a Redis compatible server.
+///
+/// Events are published as JSON on one channel, instances receive their own
+/// events back through their subscription. Connections are re-established in
+/// the background, with growing delays while the server stays unreachable.
+/// Events that can't be published meanwhile only reach this instance.
+pub struct RedisBackplane {
+    outgoing: tokio::sync::mpsc::Sender<BackplaneEvent>,
+    events: broadcast::Sender<BackplaneEvent>,
+}
+
+impl RedisBackplane {
+    /// Starts publishing and subscribing in the background.
+    pub fn new(url: String, channel: String) -> Self {
+        let (outgoing, pending) = tokio::sync::mpsc::channel(CAPACITY);
+        let events = broadcast::channel(CAPACITY).0;
+        let address = resp::Address::parse(&url);
+        tokio::spawn(resp::publish_loop(
+            address.clone(),
+            channel.clone(),
+            pending,
+            events.clone(),
+        ));
+        tokio::spawn(resp::subscribe_loop(address, channel, events.clone()));
+        Self { outgoing, events }
+    }
+}
+
+impl Backplane for RedisBackplane {
+    fn publish(&self, event: BackplaneEvent) {
+        if let Err(tokio::sync::mpsc::error::TrySendError::Full(event)) =
+            self.outgoing.try_send(event)
+        {
+            log::warn!("Backplane publish queue is full, delivering event locally only");
+            let _ = self.events.send(event);
+        }
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<BackplaneEvent> {
+        self.events.subscribe()
+    }
+}
+
+/// The parts of the Redis protocol (RESP2) needed for pub/sub.
+mod resp {
+    use std::time::Duration;
+
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpStream,
+        sync::{broadcast, mpsc},
+        time::Instant,
+    };
+
+    use super::BackplaneEvent;
+
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+    const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+    const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+    /// Delay before the next connection attempt, doubled after every failed one.
+    pub(super) struct Backoff(Duration);
+
+    impl Backoff {
+        pub(super) fn new() -> Self {
+            Self(MIN_RECONNECT_DELAY)
+        }
+
+        pub(super) fn next_delay(&mut self) -> Duration {
+            let delay = self.0;
+            self.0 = (delay * 2).min(MAX_RECONNECT_DELAY);
+            delay
+        }
+
+        fn reset(&mut self) {
+            self.0 = MIN_RECONNECT_DELAY;
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Address {
+        host: String,
+        password: Option<String>,
+    }
+
+    impl Address {
+        /// Parses `redis://[:password@]host[:port]`, the port defaults to 6379.
+        pub fn parse(url: &str) -> Self {
+            let rest = url.trim_start_matches("redis://").trim_end_matches('/');
+            let (password, host) = match rest.rsplit_once('@') {
+                Some((credentials, host)) => (
+                    Some(credentials.trim_start_matches(':').to_string())
+                        .filter(|password| !password.is_empty()),
+                    host,
+                ),
+                None => (None, rest),
+            };
+            let host = if host.contains(':') {
+                host.to_string()
+            } else {
+                format!("{host}:6379")
+            };
+            Self { host, password }
+        }
+    }
+
+    #[derive(Debug)]
+    enum Frame {
+        Simple,
+        Error(String),
+        Integer,
+        Bulk(Option<Vec<u8>>),
+        Array(Vec<Frame>),
+    }
+
+    type Connection = BufReader<TcpStream>;
+
+    async fn connect(address: &Address) -> std::io::Result<Connection> {
+        let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&address.host))
+            .await
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+        let mut connection = BufReader::new(stream);
+        if let Some(password) = &address.password {
+            command(&mut connection, &["AUTH", password]).await?;
+            if let Frame::Error(error) = read_frame(&mut connection).await? {
+                return Err(std::io::Error::other(error));
+            }
+        }
+        Ok(connection)
+    }
+
+    async fn command(connection: &mut Connection, args: &[&str]) -> std::io::Result<()> {
+        let mut buffer = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            buffer.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            buffer.extend_from_slice(arg.as_bytes());
+            buffer.extend_from_slice(b"\r\n");
+        }
+        connection.get_mut().write_all(&buffer).await
+    }
+
+    async fn read_line(connection: &mut Connection) -> std::io::Result<String> {
+        let mut line = String::new();
+        if connection.read_line(&mut line).await? == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(line.trim_end_matches("\r\n").to_string())
+    }
+
+    fn invalid(line: &str) -> std::io::Error {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("invalid reply `{line}`"),
+        )
+    }
+
+    async fn read_frame(connection: &mut Connection) -> std::io::Result<Frame> {
+        let line = read_line(connection).await?;
+        let (kind, value) = line.split_at_checked(1).ok_or_else(|| invalid(&line))?;
+        let length = || value.parse::<i64>().map_err(|_| invalid(&line));
+        Ok(match kind {
+            "+" => Frame::Simple,
+            "-" => Frame::Error(value.to_string()),
+            ":" => {
+                length()?;
+                Frame::Integer
+            }
+            "$" => match usize::try_from(length()?) {
+                Ok(length) => {
+                    let mut data = vec![0; length + 2];
+                    connection.read_exact(&mut data).await?;
+                    data.truncate(length);
+                    Frame::Bulk(Some(data))
+                }
+                Err(_) => Frame::Bulk(None),
+            },
+            "*" => {
+                let mut frames = Vec::new();
+                for _ in 0..length()?.max(0) {
+                    frames.push(Box::pin(read_frame(connection)).await?);
+                }
+                Frame::Array(frames)
+            }
+            _ => return Err(invalid(&line)),
+        })
+    }
+
+    /// Publishes the events, reconnecting when the connection breaks.
+    ///
+    /// Events that can't be published are sent to `events` directly, so the
+    /// connections of this instance keep receiving them during an outage.
+    pub async fn publish_loop(
+        address: Address,
+        channel: String,
+        mut pending: mpsc::Receiver<BackplaneEvent>,
+        events: broadcast::Sender<BackplaneEvent>,
+    ) {
+        let mut connection = None;
+        let mut backoff = Backoff::new();
+        let mut reconnect_at = Instant::now();
+        while let Some(event) = pending.recv().await {
+            let payload = match serde_json::to_string(&event) {
+                Ok(payload) => payload,
+                Err(error) => {
+                    log::error!("Failed to serialize backplane event: {error}");
+                    continue;
+                }
+            };
+            let mut published = false;
+            for _ in 0..2 {
+                let current = match connection.as_mut() {
+                    Some(current) => current,
+                    // Not waiting on an unreachable server for every event
+                    None if Instant::now() < reconnect_at => break,
+                    None => match connect(&address).await {
+                        Ok(new) => {
+                            backoff.reset();
+                            connection.insert(new)
+                        }
+                        Err(error) => {
+                            log::error!("Failed to connect to the backplane: {error}");
+                            reconnect_at = Instant::now() + backoff.next_delay();
+                            break;
+                        }
+                    },
+                };
+                let result = async {
+                    command(current, &["PUBLISH", &channel, &payload]).await?;
+                    match read_frame(current).await? {
+                        Frame::Error(error) => Err(std::io::Error::other(error)),
+                        _ => Ok(()),
+                    }
+                }
+                .await;
+                match result {
+                    Ok(()) => {
+                        published = true;
+                        break;
+                    }
+                    Err(error) => {
+                        log::warn!("Failed to publish to the backplane: {error}");
+                        connection = None;
+                    }
+                }
+            }
+            if !published {
+                let _ = events.send(event);
+            }
+        }
+    }
+
+    /// Forwards the events of the channel, resubscribing when the connection breaks.
+    pub async fn subscribe_loop(
+        address: Address,
+        channel: String,
+        events: broadcast::Sender<BackplaneEvent>,
+    ) {
+        let mut backoff = Backoff::new();
+        loop {
+            if let Err(error) = subscribe(&address, &channel, &events, &mut backoff).await {
+                log::error!("Backplane subscription failed: {error}");
+            }
+            tokio::time::sleep(backoff.next_delay()).await;
+        }
+    }
+
+    async fn subscribe(
+        address: &Address,
+        channel: &str,
+        events: &broadcast::Sender<BackplaneEvent>,
+        backoff: &mut Backoff,
+    ) -> std::io::Result<()> {
+        let mut connection = connect(address).await?;
+        command(&mut connection, &["SUBSCRIBE", channel]).await?;
+        log::info!("Subscribed to backplane channel {channel}");
+        backoff.reset();
+        loop {
+            let Frame::Array(frames) = read_frame(&mut connection).await? else {
+                continue;
+            };
+            let [Frame::Bulk(Some(kind)), _, Frame::Bulk(Some(payload))] = frames.as_slice() else {
+                continue;
+            };
+            if kind.as_slice() != b"message" {
+                continue;
+            }
+            match serde_json::from_slice(payload) {
+                Ok(event) => {
+                    let _ = events.send(event);
+                }
+                Err(error) => log::warn!("Invalid backplane event: {error}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+        sync::{broadcast, mpsc},
+    };
+
+    use super::*;
+
+    const CHANNEL: &str = "test";
+
+    /// Subscribed channel and the connection that pushes its messages.
+    type Subscriber = (String, mpsc::UnboundedSender<Vec<u8>>);
+
+    /// Just enough of a Redis server for the backplane: SUBSCRIBE and PUBLISH.
+    #[derive(Clone)]
+    struct FakeRedis {
+        url: String,
+        subscribers: Arc<Mutex<Vec<Subscriber>>>,
+        disconnect: broadcast::Sender<()>,
+    }
+
+    impl FakeRedis {
+        async fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let redis = FakeRedis {
+                url: format!("redis://{}", listener.local_addr().unwrap()),
+                subscribers: Arc::default(),
+                disconnect: broadcast::channel(1).0,
+            };
+            let server = redis.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(server.clone().serve(stream));
+                }
+            });
+            redis
+        }
+
+        fn subscriber_count(&self) -> usize {
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers.retain(|(_, subscriber)| !subscriber.is_closed());
+            subscribers.len()
+        }
+
+        /// Drops every connection, as a restarting server would.
+        fn disconnect_all(&self) {
+            let _ = self.disconnect.send(());
+        }
+
+        async fn serve(self, stream: TcpStream) {
+            let (reader, mut writer) = stream.into_split();
+            let mut reader = BufReader::new(reader);
+            let (pushed, mut pushes) = mpsc::unbounded_channel();
+            let mut disconnect = self.disconnect.subscribe();
+            loop {
+                let reply = tokio::select! {
+                    request = read_request(&mut reader) => match request {
+                        Some(args) => self.handle(args, &pushed),
+                        None => return,
+                    },
+                    Some(push) = pushes.recv() => push,
+                    _ = disconnect.recv() => return,
+                };
+                if writer.write_all(&reply).await.is_err() {
+                    return;
+                }
+            }
+        }
+
+        fn handle(&self, args: Vec<String>, pushed: &mpsc::UnboundedSender<Vec<u8>>) -> Vec<u8> {
+            match args
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .as_slice()
+            {
+                ["SUBSCRIBE", channel] => {
+                    self.subscribers
+                        .lock()
+                        .unwrap()
+                        .push((channel.to_string(), pushed.clone()));
+                    array(&["subscribe", channel], ":1\r\n")
+                }
+                ["PUBLISH", channel, payload] => {
+                    let message = array(&["message", channel, payload], "");
+                    let mut receivers = 0;
+                    for (subscribed, subscriber) in self.subscribers.lock().unwrap().iter() {
+                        if subscribed == channel && subscriber.send(message.clone()).is_ok() {
+                            receivers += 1;
+                        }
+                    }
+                    format!(":{receivers}\r\n").into_bytes()
+                }
+                _ => b"-ERR unknown command\r\n".to_vec(),
+            }
+        }
+    }
+
+    /// A RESP array of bulk strings, followed by the already encoded `tail`.
+    fn array(items: &[&str], tail: &str) -> Vec<u8> {
+        let length = items.len() + usize::from(!tail.is_empty());
+        let mut encoded = format!("*{length}\r\n");
+        for item in items {
+            encoded.push_str(&format!("${}\r\n{item}\r\n", item.len()));
+        }
+        encoded.push_str(tail);
+        encoded.into_bytes()
+    }
+
+    async fn read_request(
+        reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>,
+    ) -> Option<Vec<String>> {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.ok()?;
+        let count = line.trim_end().strip_prefix('*')?.parse::<usize>().ok()?;
+        let mut args = Vec::with_capacity(count);
+        for _ in 0..count {
+            line.clear();
+            reader.read_line(&mut line).await.ok()?;
+            let length = line.trim_end().strip_prefix('$')?.parse::<usize>().ok()?;
+            let mut data = vec![0; length + 2];
+            reader.read_exact(&mut data).await.ok()?;
+            data.truncate(length);
+            args.push(String::from_utf8(data).ok()?);
+        }
+        Some(args)
+    }
+
+    async fn eventually(mut condition: impl FnMut() -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Condition not met in time");
+    }
+
+    fn event(session_id: &str) -> BackplaneEvent {
+        BackplaneEvent::CloseSessions {
+            session_ids: vec![session_id.to_string()],
+        }
+    }
+
+    async fn next_session_id(events: &mut broadcast::Receiver<BackplaneEvent>) -> String {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("No event in time")
+            .unwrap();
+        let BackplaneEvent::CloseSessions { mut session_ids } = event else {
+            panic!("Unexpected event {event:?}");
+        };
+        session_ids.remove(0)
+    }
+
+    #[tokio::test]
+    async fn instances_receive_each_others_events() {
+        let redis = FakeRedis::start().await;
+        let first = RedisBackplane::new(redis.url.clone(), CHANNEL.to_string());
+        let second = RedisBackplane::new(redis.url.clone(), CHANNEL.to_string());
+        let mut first_events = first.subscribe();
+        let mut second_events = second.subscribe();
+        eventually(|| redis.subscriber_count() == 2).await;
+
+        first.publish(event("from-first"));
+        assert_eq!(next_session_id(&mut second_events).await, "from-first");
+        // The publishing instance gets its own events back too
+        assert_eq!(next_session_id(&mut first_events).await, "from-first");
+
+        second.publish(event("from-second"));
+        assert_eq!(next_session_id(&mut first_events).await, "from-second");
+        assert_eq!(next_session_id(&mut second_events).await, "from-second");
+    }
+
+    #[tokio::test]
+    async fn resubscribes_after_the_connection_drops() {
+        let redis = FakeRedis::start().await;
+        let first = RedisBackplane::new(redis.url.clone(), CHANNEL.to_string());
+        let second = RedisBackplane::new(redis.url.clone(), CHANNEL.to_string());
+        let mut second_events = second.subscribe();
+        eventually(|| redis.subscriber_count() == 2).await;
+        first.publish(event("before"));
+        assert_eq!(next_session_id(&mut second_events).await, "before");
+
+        redis.disconnect_all();
+        eventually(|| redis.subscriber_count() == 0).await;
+        eventually(|| redis.subscriber_count() == 2).await;
+
+        // The publishing connection broke as well and is re-established on the next event
+        first.publish(event("after"));
+        assert_eq!(next_session_id(&mut second_events).await, "after");
+    }
+
+    #[tokio::test]
+    async fn delivers_locally_while_redis_is_unreachable() {
+        // Nothing listens on the port once the listener is gone
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("redis://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let backplane = RedisBackplane::new(url, CHANNEL.to_string());
+        let mut events = backplane.subscribe();
+
+        backplane.publish(event("first"));
+        assert_eq!(next_session_id(&mut events).await, "first");
+        // Published while waiting to reconnect
+        backplane.publish(event("second"));
+        assert_eq!(next_session_id(&mut events).await, "second");
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        let mut backoff = resp::Backoff::new();
+        let delays = (0..8)
+            .map(|_| backoff.next_delay().as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+    }
+}
@@ -0,0 +1,421 @@
+//! Slash commands typed into the composer, e.g. `/me waves`.
+//!
+//! Commands implement [`SlashCommand`] and are looked up by name in the
+//! [`CommandRegistry`] of the [`AppState`]. Team specific commands are added
+//! to [`CommandRegistry::default`] next to the built-in ones.
+use std::{collections::BTreeMap, sync::Arc};
+
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    server_fn::commands::{CommandError, CommandInfo},
+    user_events::UserEvent,
+};
+
+/// Longest topic of a group.
+const MAX_TOPIC_LENGTH: usize = 256;
+
+/// The user running a command and the group it runs in, the user is a member.
+pub struct CommandContext<'a> {
+    pub state: &'a AppState,
+    pub user_id: Uuid,
+    pub username: String,
+    pub group_id: Uuid,
+}
+
+pub enum CommandOutcome {
+    /// Send the text to the group as a message of the user.
+    Post(String),
+    /// Show the text to the user only.
+    Reply(String),
+    Done,
+}
+
+#[async_trait::async_trait]
+pub trait SlashCommand: Send + Sync {
+    /// Typed after the slash, without whitespace.
+    fn name(&self) -> &'static str;
+    /// Hint of the arguments shown while typing the command.
+    fn args(&self) -> &'static str {
+        ""
+    }
+    fn description(&self) -> &'static str;
+    async fn run(
+        &self,
+        context: &CommandContext<'_>,
+        args: &str,
+    ) -> Result<CommandOutcome, CommandError>;
+
+    /// The command with its arguments, shown when they don't fit.
+    fn usage(&self) -> String {
+        format!("/{} {}", self.name(), self.args())
+            .trim_end()
+            .to_string()
+    }
+}
+
+pub struct CommandRegistry {
+    commands: BTreeMap<&'static str, Arc<dyn SlashCommand>>,
+}
+
+impl CommandRegistry {
+    /// A registry without any commands.
+    pub fn empty() -> Self {
+        Self {
+            commands: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the command, replacing one of the same name.
+    pub fn register(&mut self, command: impl SlashCommand + 'static) -> &mut Self {
+        self.commands.insert(command.name(), Arc::new(command));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn SlashCommand>> {
+        self.commands.get(name).cloned()
+    }
+
+    /// All commands, sorted by name.
+    pub fn list(&self) -> Vec<CommandInfo> {
+        self.commands
+            .values()
+            .map(|command| CommandInfo {
+                name: command.name().to_string(),
+                args: command.args().to_string(),
+                description: command.description().to_string(),
+            })
+            .collect()
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register(Me)
+            .register(Shrug)
+            .register(Invite)
+            .register(Leave)
+            .register(Topic);
+        registry
+    }
+}
+
+struct Me;
+
+#[async_trait::async_trait]
+impl SlashCommand for Me {
+    fn name(&self) -> &'static str {
+        "me"
+    }
+
+    fn args(&self) -> &'static str {
+        "<action>"
+    }
+
+    fn description(&self) -> &'static str {
+        "Describe what you are doing"
+    }
+
+    async fn run(
+        &self,
+        context: &CommandContext<'_>,
+        args: &str,
+    ) -> Result<CommandOutcome, CommandError> {
+        if args.is_empty() {
+            return Err(CommandError::Usage(self.usage()));
+        }
+        Ok(CommandOutcome::Post(format!(
+            "* {} {args}",
+            context.username
+        )))
+    }
+}
+
+struct Shrug;
+
+#[async_trait::async_trait]
+impl SlashCommand for Shrug {
+    fn name(&self) -> &'static str {
+        "shrug"
+    }
+
+    fn args(&self) -> &'static str {
+        "[message]"
+    }
+
+    fn description(&self) -> &'static str {
+        "Append ¯\\_(ツ)_/¯ to your message"
+    }
+
+    async fn run(
+        &self,
+        _context: &CommandContext<'_>,
+        args: &str,
+    ) -> Result<CommandOutcome, CommandError> {
+        Ok(CommandOutcome::Post(
+            format!("{args} ¯\\_(ツ)_/¯").trim_start().to_string(),
+        ))
+    }
+}
+
+struct Invite;
+
+#[async_trait::async_trait]
+impl SlashCommand for Invite {
+    fn name(&self) -> &'static str {
+        "invite"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show the join code of the group"
+    }
+
+    async fn run(
+        &self,
+        context: &CommandContext<'_>,
+        _args: &str,
+    ) -> Result<CommandOutcome, CommandError> {
+        let group = context
+            .state
+            .group_repository
+            .get_group_by_id(context.group_id)
+            .await?;
+        Ok(CommandOutcome::Reply(format!(
+            "Share the join code {} to invite others to {}",
+            group.join_code, group.name
+        )))
+    }
+}
+
+struct Leave;
+
+#[async_trait::async_trait]
+impl SlashCommand for Leave {
+    fn name(&self) -> &'static str {
+        "leave"
+    }
+
+    fn description(&self) -> &'static str {
+        "Leave the group"
+    }
+
+    async fn run(
+        &self,
+        context: &CommandContext<'_>,
+        _args: &str,
+    ) -> Result<CommandOutcome, CommandError> {
+        crate::server_fn::groups::leave(
+            context.state,
+            context.user_id,
+            &context.username,
+            context.group_id,
+        )
+        .await?;
+        Ok(CommandOutcome::Done)
+    }
+}
+
+struct Topic;
+
+#[async_trait::async_trait]
+impl SlashCommand for Topic {
+    fn name(&self) -> &'static str {
+        "topic"
+    }
+
+    fn args(&self) -> &'static str {
+        "[topic]"
+    }
+
+    fn description(&self) -> &'static str {
+        "Set the topic of the group, or clear it"
+    }
+
+    async fn run(
+        &self,
+        context: &CommandContext<'_>,
+        args: &str,
+    ) -> Result<CommandOutcome, CommandError> {
+        if args.chars().count() > MAX_TOPIC_LENGTH {
+            return Err(CommandError::Failed(format!(
+                "Topics can't be longer than {MAX_TOPIC_LENGTH} characters"
+            )));
+        }
+        let topic = Some(args.to_string()).filter(|topic| !topic.is_empty());
+        let state = context.state;
+        state
+            .group_repository
+            .set_topic(context.group_id, topic.as_deref())
+            .await?;
+        for member in state
+            .group_repository
+            .list_members(context.group_id)
+            .await?
+        {
+            state.user_events.send(
+                member.user_id,
+                UserEvent::TopicChanged {
+                    group_id: context.group_id.to_string(),
+                    topic: topic.clone(),
+                },
+            );
+        }
+        Ok(CommandOutcome::Post(match topic {
+            Some(topic) => format!("* {} set the topic to: {topic}", context.username),
+            None => format!("* {} cleared the topic", context.username),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{Config, RateLimitConfig},
+        domain::{group::Group, user::User},
+        server_fn::commands::run,
+        test_support,
+    };
+
+    struct Echo;
+
+    #[async_trait::async_trait]
+    impl SlashCommand for Echo {
+        fn name(&self) -> &'static str {
+            "me"
+        }
+
+        fn description(&self) -> &'static str {
+            "Repeat the arguments"
+        }
+
+        async fn run(
+            &self,
+            _context: &CommandContext<'_>,
+            args: &str,
+        ) -> Result<CommandOutcome, CommandError> {
+            Ok(CommandOutcome::Reply(args.to_string()))
+        }
+    }
+
+    async fn member(state: &AppState) -> (User, String) {
+        let id = test_support::user(state, "alice").await;
+        let group_id = state
+            .group_repository
+            .create_group(Group::new("lunch".to_string()))
+            .await
+            .unwrap();
+        state
+            .group_repository
+            .add_member(group_id, id)
+            .await
+            .unwrap();
+        let user = state.user_repository.get_by_id(id).await.unwrap();
+        (user, group_id.to_string())
+    }
+
+    async fn last_message(state: &AppState, group_id: &str) -> Option<String> {
+        state
+            .message_repository
+            .get_last_by_group(group_id.parse().unwrap())
+            .await
+            .unwrap()
+            .map(|message| message.content)
+    }
+
+    #[test]
+    fn lists_the_builtin_commands_by_name() {
+        let names = CommandRegistry::default()
+            .list()
+            .into_iter()
+            .map(|command| command.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["invite", "leave", "me", "shrug", "topic"]);
+    }
+
+    #[tokio::test]
+    async fn dispatches_by_name() {
+        let state = test_support::state(Config::default()).await;
+        let (alice, group_id) = member(&state).await;
+
+        assert_eq!(run(&state, &alice, &group_id, "/me waves").await, Ok(None));
+        assert_eq!(
+            last_message(&state, &group_id).await.as_deref(),
+            Some("* alice waves")
+        );
+        let reply = run(&state, &alice, &group_id, "/invite").await.unwrap();
+        assert!(reply.unwrap().starts_with("Share the join code"));
+        assert_eq!(
+            run(&state, &alice, &group_id, "/me").await,
+            Err(CommandError::Usage("/me <action>".to_string()))
+        );
+        assert_eq!(
+            run(&state, &alice, &group_id, "/dance").await,
+            Err(CommandError::UnknownCommand("dance".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn registered_commands_replace_builtins() {
+        let mut state = test_support::state(Config::default()).await;
+        let mut registry = CommandRegistry::default();
+        registry.register(Echo);
+        state.commands = Arc::new(registry);
+        let (alice, group_id) = member(&state).await;
+
+        assert_eq!(state.commands.list().len(), 5);
+        assert_eq!(
+            run(&state, &alice, &group_id, "/me waves").await,
+            Ok(Some("waves".to_string()))
+        );
+        assert_eq!(last_message(&state, &group_id).await, None);
+    }
+
+    #[tokio::test]
+    async fn refuses_non_members() {
+        let state = test_support::state(Config::default()).await;
+        let (_, group_id) = member(&state).await;
+        let eve = test_support::user(&state, "eve").await;
+        let eve = state.user_repository.get_by_id(eve).await.unwrap();
+
+        assert_eq!(
+            run(&state, &eve, &group_id, "/topic Mine now").await,
+            Err(CommandError::NotAMember)
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_limited_commands_change_nothing() {
+        let state = test_support::state(Config {
+            rate_limit: RateLimitConfig {
+                burst: 1,
+                messages_per_minute: 1,
+            },
+            ..Config::default()
+        })
+        .await;
+        let (alice, group_id) = member(&state).await;
+        run(&state, &alice, &group_id, "/me is hungry")
+            .await
+            .unwrap();
+
+        let result = run(&state, &alice, &group_id, "/topic Pizza").await;
+        let Err(CommandError::Failed(reason)) = result else {
+            panic!("Expected to be slowed down, got {result:?}");
+        };
+        assert!(reason.starts_with("You are sending messages too fast"));
+        let group = state
+            .group_repository
+            .get_group_by_id(group_id.parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(group.topic, None);
+        assert_eq!(
+            last_message(&state, &group_id).await.as_deref(),
+            Some("* alice is hungry")
+        );
+    }
+}
@@ -0,0 +1,472 @@
+//! Typed server configuration.
+//!
+//! Settings are read from an optional TOML file (`config.toml`, or the path in
+//! `APP_CONFIG`) and can be overridden with `APP__<SECTION>__<KEY>` environment
+//! variables, e.g. `APP__POW__DIFFICULTY=20`. `DATABASE_URL` is still honored.
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to load configuration: {0}")]
+    Load(#[from] config::ConfigError),
+    #[error("invalid configuration value `{key}`: {reason}")]
+    Invalid { key: &'static str, reason: String },
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Public base url of the site, used for redirects back from external services.
+    pub public_url: Option<String>,
+    /// Reverse proxies whose `X-Forwarded-For` header is believed, e.g. `["127.0.0.1"]`.
+    /// Without any, the address of the connection is taken as the client's.
+    pub trusted_proxies: Vec<std::net::IpAddr>,
+    pub database: DatabaseConfig,
+    pub session: SessionConfig,
+    pub pow: PowConfig,
+    pub uploads: UploadConfig,
+    pub rate_limit: RateLimitConfig,
+    pub features: FeatureConfig,
+    pub oidc: OidcConfig,
+    pub backplane: BackplaneConfig,
+    pub link_previews: LinkPreviewConfig,
+    pub outgoing_webhooks: OutgoingWebhookConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub create_if_missing: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub lifetime_days: i64,
+    pub auth_max_age_hours: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PowConfig {
+    /// Between 10 and 98, 20 is reasonable for release builds but blocks debug builds.
+    pub difficulty: u8,
+    pub valid_seconds: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UploadConfig {
+    /// Maximum size of any request body.
+    pub max_request_bytes: usize,
+    pub max_avatar_bytes: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Messages a user can send to a group at once.
+    pub burst: u32,
+    /// Messages a user can send to a group per minute after the burst.
+    pub messages_per_minute: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FeatureConfig {
+    pub signup: bool,
+    pub api_tokens: bool,
+    pub oidc: bool,
+    pub webhooks: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OidcConfig {
+    /// Providers keyed by their id, which is part of the callback url.
+    pub providers: BTreeMap<String, OidcProviderSettings>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcProviderSettings {
+    pub name: Option<String>,
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackplaneKind {
+    /// Live updates only reach clients of this instance.
+    #[default]
+    Local,
+    /// Live updates are fanned out to all instances through a Redis compatible server.
+    Redis,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BackplaneConfig {
+    pub kind: BackplaneKind,
+    /// `redis://[:password@]host[:port]`, required for the redis backplane.
+    pub url: Option<String>,
+    /// Pub/sub channel shared by all instances.
+    pub channel: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LinkPreviewConfig {
+    pub enabled: bool,
+    /// Time a page gets to answer, redirects included.
+    pub timeout_secs: u64,
+    /// Bytes of a page read at most, the metadata is expected in its head.
+    pub max_bytes: usize,
+    /// Hours a fetched preview is shown before the page is fetched again.
+    pub cache_hours: i64,
+    /// Hosts that may be fetched even though they resolve to private or local addresses.
+    pub allowed_hosts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OutgoingWebhookConfig {
+    /// Hosts webhooks may be sent to even though they resolve to private or local addresses.
+    pub allowed_hosts: Vec<String>,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            max_connections: 10,
+            create_if_missing: true,
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            lifetime_days: 365,
+            auth_max_age_hours: 6,
+        }
+    }
+}
+
+impl Default for PowConfig {
+    fn default() -> Self {
+        Self {
+            difficulty: 10,
+            valid_seconds: 10,
+        }
+    }
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            max_request_bytes: 2 * 1024 * 1024,
+            max_avatar_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            burst: 10,
+            messages_per_minute: 30,
+        }
+    }
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        Self {
+            signup: true,
+            api_tokens: true,
+            oidc: true,
+            webhooks: true,
+        }
+    }
+}
+
+impl Default for BackplaneConfig {
+    fn default() -> Self {
+        Self {
+            kind: BackplaneKind::Local,
+            url: None,
+            channel: "leptos-chat".to_string(),
+        }
+    }
+}
+
+impl Default for LinkPreviewConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_secs: 5,
+            max_bytes: 256 * 1024,
+            cache_hours: 24,
+            allowed_hosts: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads and validates the configuration.
+    /// # Errors
+    /// Will return an error if the file can't be parsed or a value is invalid
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = std::env::var("APP_CONFIG").unwrap_or_else(|_| "config.toml".to_string());
+        let mut builder = config::Config::builder()
+            .add_source(config::File::with_name(&path).required(false))
+            .add_source(
+                config::Environment::with_prefix("APP")
+                    .prefix_separator("__")
+                    .separator("__")
+                    .try_parsing(true),
+            );
+        if let Ok(url) = std::env::var("DATABASE_URL") {
+            builder = builder.set_override("database.url", url)?;
+        }
+        let config: Self = builder.build()?.try_deserialize()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        fn invalid(key: &'static str, reason: &str) -> Result<(), ConfigError> {
+            Err(ConfigError::Invalid {
+                key,
+                reason: reason.to_string(),
+            })
+        }
+        if self.database.url.is_empty() {
+            return invalid(
+                "database.url",
+                "missing, set DATABASE_URL or APP__DATABASE__URL",
+            );
+        }
+        if self.database.max_connections == 0 {
+            return invalid("database.max_connections", "must be at least 1");
+        }
+        if self.session.lifetime_days <= 0 {
+            return invalid("session.lifetime_days", "must be positive");
+        }
+        if self.session.auth_max_age_hours <= 0 {
+            return invalid("session.auth_max_age_hours", "must be positive");
+        }
+        if !(10..99).contains(&self.pow.difficulty) {
+            return invalid("pow.difficulty", "must be between 10 and 98");
+        }
+        if self.pow.valid_seconds == 0 {
+            return invalid("pow.valid_seconds", "must be at least 1");
+        }
+        if self.uploads.max_avatar_bytes > self.uploads.max_request_bytes {
+            return invalid(
+                "uploads.max_avatar_bytes",
+                "must not exceed uploads.max_request_bytes",
+            );
+        }
+        if self.rate_limit.burst == 0 {
+            return invalid("rate_limit.burst", "must be at least 1");
+        }
+        if self.rate_limit.messages_per_minute == 0 {
+            return invalid("rate_limit.messages_per_minute", "must be at least 1");
+        }
+        if let Some(public_url) = &self.public_url
+            && !(public_url.starts_with("http://") || public_url.starts_with("https://"))
+        {
+            return invalid("public_url", "must start with http:// or https://");
+        }
+        if self.backplane.kind == BackplaneKind::Redis
+            && !self
+                .backplane
+                .url
+                .as_ref()
+                .is_some_and(|url| url.starts_with("redis://"))
+        {
+            return invalid("backplane.url", "must be a redis:// url");
+        }
+        if self.backplane.channel.is_empty() {
+            return invalid("backplane.channel", "must not be empty");
+        }
+        if self.link_previews.timeout_secs == 0 {
+            return invalid("link_previews.timeout_secs", "must be at least 1");
+        }
+        if self.link_previews.max_bytes == 0 {
+            return invalid("link_previews.max_bytes", "must be at least 1");
+        }
+        if self.link_previews.cache_hours <= 0 {
+            return invalid("link_previews.cache_hours", "must be positive");
+        }
+        for (id, provider) in &self.oidc.providers {
+            if !id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return invalid(
+                    "oidc.providers",
+                    &format!("provider id `{id}` may only contain letters, digits, - and _"),
+                );
+            }
+            if provider.issuer_url.is_empty() || provider.client_id.is_empty() {
+                return invalid(
+                    "oidc.providers",
+                    &format!("provider `{id}` needs an issuer_url and a client_id"),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A setting and a change that makes it invalid.
+    type Case = (&'static str, fn(&mut Config));
+
+    fn parse(toml: &str) -> Result<Config, ConfigError> {
+        let config: Config = config::Config::builder()
+            .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+            .build()?
+            .try_deserialize()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn valid() -> Config {
+        Config {
+            database: DatabaseConfig {
+                url: "sqlite::memory:".to_string(),
+                ..DatabaseConfig::default()
+            },
+            ..Config::default()
+        }
+    }
+
+    fn provider(issuer_url: &str, client_id: &str) -> OidcProviderSettings {
+        OidcProviderSettings {
+            name: None,
+            issuer_url: issuer_url.to_string(),
+            client_id: client_id.to_string(),
+            client_secret: None,
+        }
+    }
+
+    #[test]
+    fn loads_the_example() {
+        let example = include_str!("../../config.example.toml");
+        let config = parse(example).unwrap();
+        assert_eq!(config.database.url, "sqlite://sqlite.db");
+        assert_eq!(config.backplane.kind, BackplaneKind::Local);
+        assert!(config.oidc.providers.is_empty());
+
+        // The commented out provider is valid as well
+        let with_provider = example.replace("# [oidc.", "[oidc.").replace(
+            "# name = \"Company SSO\"\n# issuer_url = \"https://sso.example.com/realms/main\"\n# client_id",
+            "name = \"Company SSO\"\nissuer_url = \"https://sso.example.com/realms/main\"\nclient_id",
+        );
+        let config = parse(&with_provider).unwrap();
+        assert_eq!(
+            config.oidc.providers["company"].name.as_deref(),
+            Some("Company SSO")
+        );
+    }
+
+    #[test]
+    fn accepts_the_defaults_with_a_database() {
+        valid().validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let cases: &[Case] = &[
+            ("database.url", |config| config.database.url.clear()),
+            ("database.max_connections", |config| {
+                config.database.max_connections = 0
+            }),
+            ("session.lifetime_days", |config| {
+                config.session.lifetime_days = 0
+            }),
+            ("session.auth_max_age_hours", |config| {
+                config.session.auth_max_age_hours = -1
+            }),
+            ("pow.difficulty", |config| config.pow.difficulty = 9),
+            ("pow.difficulty", |config| config.pow.difficulty = 99),
+            ("pow.valid_seconds", |config| config.pow.valid_seconds = 0),
+            ("uploads.max_avatar_bytes", |config| {
+                config.uploads.max_avatar_bytes = config.uploads.max_request_bytes + 1
+            }),
+            ("rate_limit.burst", |config| config.rate_limit.burst = 0),
+            ("rate_limit.messages_per_minute", |config| {
+                config.rate_limit.messages_per_minute = 0
+            }),
+            ("public_url", |config| {
+                config.public_url = Some("chat.example.com".to_string())
+            }),
+            ("backplane.url", |config| {
+                config.backplane.kind = BackplaneKind::Redis
+            }),
+            ("backplane.url", |config| {
+                config.backplane.kind = BackplaneKind::Redis;
+                config.backplane.url = Some("http://127.0.0.1:6379".to_string());
+            }),
+            ("backplane.channel", |config| {
+                config.backplane.channel.clear()
+            }),
+            ("link_previews.timeout_secs", |config| {
+                config.link_previews.timeout_secs = 0
+            }),
+            ("link_previews.max_bytes", |config| {
+                config.link_previews.max_bytes = 0
+            }),
+            ("link_previews.cache_hours", |config| {
+                config.link_previews.cache_hours = 0
+            }),
+            ("oidc.providers", |config| {
+                config.oidc.providers.insert(
+                    "my sso".to_string(),
+                    provider("https://sso.example.com", "chat"),
+                );
+            }),
+            ("oidc.providers", |config| {
+                config
+                    .oidc
+                    .providers
+                    .insert("sso".to_string(), provider("https://sso.example.com", ""));
+            }),
+            ("oidc.providers", |config| {
+                config
+                    .oidc
+                    .providers
+                    .insert("sso".to_string(), provider("", "chat"));
+            }),
+        ];
+        for (key, change) in cases {
+            let mut config = valid();
+            change(&mut config);
+            let result = config.validate();
+            let Err(ConfigError::Invalid { key: rejected, .. }) = result else {
+                panic!("Expected {key} to be rejected, got {result:?}");
+            };
+            assert_eq!(rejected, *key);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        let result = parse("[pow]\ndifficulty = \"hard\"\n");
+        assert!(matches!(result, Err(ConfigError::Load(_))), "{result:?}");
+    }
+}
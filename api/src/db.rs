@@ -1,6 +1,28 @@
+mod api_token_repository;
+mod block_repository;
 mod group_repository;
+mod identity_repository;
+mod import_repository;
+mod link_preview_repository;
 mod message_repository;
+mod outgoing_webhook_repository;
+mod poll_repository;
+mod profile_repository;
+mod scheduled_message_repository;
+mod session_repository;
 mod user_repository;
+mod webhook_repository;
+pub use api_token_repository::ApiTokenRepository;
+pub use block_repository::BlockRepository;
 pub use group_repository::GroupRepository;
+pub use identity_repository::IdentityRepository;
+pub use import_repository::ImportRepository;
+pub use link_preview_repository::LinkPreviewRepository;
 pub use message_repository::MessageRepository;
+pub use outgoing_webhook_repository::OutgoingWebhookRepository;
+pub use poll_repository::PollRepository;
+pub use profile_repository::ProfileRepository;
+pub use scheduled_message_repository::ScheduledMessageRepository;
+pub use session_repository::SessionRepository;
 pub use user_repository::UserRepository;
+pub use webhook_repository::WebhookRepository;
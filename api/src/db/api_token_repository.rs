@@ -0,0 +1,104 @@
+use crate::Pool;
+use crate::domain::api_token::ApiToken;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct ApiTokenRepository {
+    pub pool: Pool,
+}
+
+impl ApiTokenRepository {
+    pub fn new(pool: Pool) -> Self {
+        ApiTokenRepository { pool }
+    }
+
+    pub async fn create(&self, token: ApiToken) -> Result<Uuid, sqlx::Error> {
+        let scopes = ApiToken::scopes_to_string(&token.scopes);
+        sqlx::query!(
+            "INSERT INTO api_tokens (id, user_id, name, token_hash, prefix, scopes, created_at, expires_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            token.id,
+            token.user_id,
+            token.name,
+            token.token_hash,
+            token.prefix,
+            scopes,
+            token.created_at,
+            token.expires_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(token.id)
+    }
+
+    pub async fn get_by_hash(&self, token_hash: &str) -> Result<Option<ApiToken>, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", user_id as "user_id: uuid::Uuid", name, token_hash, prefix, scopes, created_at as "created_at: chrono::DateTime<chrono::Utc>", expires_at as "expires_at: chrono::DateTime<chrono::Utc>", last_used_at as "last_used_at: chrono::DateTime<chrono::Utc>" FROM api_tokens WHERE token_hash = ?1"#,
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|record| ApiToken {
+            id: record.id,
+            user_id: record.user_id,
+            name: record.name,
+            token_hash: record.token_hash,
+            prefix: record.prefix,
+            scopes: ApiToken::scopes_from_string(&record.scopes),
+            created_at: record.created_at,
+            expires_at: record.expires_at,
+            last_used_at: record.last_used_at,
+        }))
+    }
+
+    pub async fn list_by_user(&self, user_id: Uuid) -> Result<Vec<ApiToken>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", user_id as "user_id: uuid::Uuid", name, token_hash, prefix, scopes, created_at as "created_at: chrono::DateTime<chrono::Utc>", expires_at as "expires_at: chrono::DateTime<chrono::Utc>", last_used_at as "last_used_at: chrono::DateTime<chrono::Utc>"
+            FROM api_tokens
+            WHERE user_id = ?1
+            ORDER BY created_at DESC"#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| ApiToken {
+                id: record.id,
+                user_id: record.user_id,
+                name: record.name,
+                token_hash: record.token_hash,
+                prefix: record.prefix,
+                scopes: ApiToken::scopes_from_string(&record.scopes),
+                created_at: record.created_at,
+                expires_at: record.expires_at,
+                last_used_at: record.last_used_at,
+            })
+            .collect())
+    }
+
+    pub async fn touch(&self, id: Uuid, last_used_at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE api_tokens SET last_used_at = ?1 WHERE id = ?2",
+            last_used_at,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes the token, returns false if it did not belong to the user.
+    pub async fn delete(&self, id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM api_tokens WHERE id = ?1 AND user_id = ?2",
+            id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,73 @@
+use crate::Pool;
+use crate::domain::block::{BlockedUser, UserBlock};
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct BlockRepository {
+    pub pool: Pool,
+}
+
+impl BlockRepository {
+    pub fn new(pool: Pool) -> Self {
+        BlockRepository { pool }
+    }
+
+    /// Blocking an already blocked user keeps the original block.
+    pub async fn create(&self, block: UserBlock) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO user_blocks (blocker_id, blocked_id, created_at) VALUES (?1, ?2, ?3)
+            ON CONFLICT (blocker_id, blocked_id) DO NOTHING",
+            block.blocker_id,
+            block.blocked_id,
+            block.created_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, blocker_id: Uuid, blocked_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM user_blocks WHERE blocker_id = ?1 AND blocked_id = ?2",
+            blocker_id,
+            blocked_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn is_blocked(&self, blocker_id: Uuid, blocked_id: Uuid) -> Result<bool, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT COUNT(*) AS "count: i32" FROM user_blocks WHERE blocker_id = ?1 AND blocked_id = ?2"#,
+            blocker_id,
+            blocked_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(record.count > 0)
+    }
+
+    pub async fn list_blocked(&self, blocker_id: Uuid) -> Result<Vec<BlockedUser>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"SELECT u.id as "user_id: uuid::Uuid", u.username, u.display_name, b.created_at as "created_at: chrono::DateTime<chrono::Utc>"
+            FROM user_blocks b
+            JOIN users u ON u.id = b.blocked_id
+            WHERE b.blocker_id = ?1
+            ORDER BY b.created_at DESC"#,
+            blocker_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| BlockedUser {
+                user_id: record.user_id,
+                username: record.username,
+                display_name: record.display_name,
+                blocked_at: record.created_at,
+            })
+            .collect())
+    }
+}
@@ -36,7 +36,7 @@ impl GroupRepository {
 
     pub async fn get_group_by_id(&self, id: Uuid) -> Result<Group, sqlx::Error> {
         let record = sqlx::query!(
-            "SELECT id as 'id: uuid::Uuid', name, avatar_url, join_code, created_at as 'created_at: chrono::DateTime<chrono::Utc>' FROM groups WHERE id = ?1",
+            "SELECT id as 'id: uuid::Uuid', name, avatar_url, join_code, topic, message_ttl_secs, created_at as 'created_at: chrono::DateTime<chrono::Utc>' FROM groups WHERE id = ?1",
             id
         )
         .fetch_one(&self.pool)
@@ -48,12 +48,14 @@ impl GroupRepository {
             avatar: record.avatar_url,
             created_at: record.created_at,
             join_code: record.join_code.unwrap_or_default(),
+            topic: record.topic,
+            message_ttl_secs: record.message_ttl_secs,
         })
     }
 
     pub async fn get_group_by_name(&self, name: String) -> Result<Group, sqlx::Error> {
         let record = sqlx::query!(
-            "SELECT id as 'id: uuid::Uuid', avatar_url, join_code, name, created_at as 'created_at: chrono::DateTime<chrono::Utc>' FROM groups WHERE name = ?1",
+            "SELECT id as 'id: uuid::Uuid', avatar_url, join_code, topic, message_ttl_secs, name, created_at as 'created_at: chrono::DateTime<chrono::Utc>' FROM groups WHERE name = ?1",
             name
         )
         .fetch_one(&self.pool)
@@ -65,12 +67,14 @@ impl GroupRepository {
             avatar: record.avatar_url,
             created_at: record.created_at,
             join_code: record.join_code.unwrap_or_default(),
+            topic: record.topic,
+            message_ttl_secs: record.message_ttl_secs,
         })
     }
 
     pub async fn list_groups(&self) -> Result<Vec<Group>, sqlx::Error> {
         let records = sqlx::query!(
-            "SELECT id as 'id: uuid::Uuid', avatar_url, join_code, name, created_at as 'created_at: chrono::DateTime<chrono::Utc>' FROM groups"
+            "SELECT id as 'id: uuid::Uuid', avatar_url, join_code, topic, message_ttl_secs, name, created_at as 'created_at: chrono::DateTime<chrono::Utc>' FROM groups"
         )
         .fetch_all(&self.pool)
         .await?;
@@ -83,10 +87,54 @@ impl GroupRepository {
                 avatar: record.avatar_url,
                 created_at: record.created_at,
                 join_code: record.join_code.unwrap_or_default(),
+                topic: record.topic,
+                message_ttl_secs: record.message_ttl_secs,
             })
             .collect())
     }
 
+    pub async fn set_topic(&self, group_id: Uuid, topic: Option<&str>) -> Result<(), sqlx::Error> {
+        sqlx::query!("UPDATE groups SET topic = ?1 WHERE id = ?2", topic, group_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_message_ttl(
+        &self,
+        group_id: Uuid,
+        ttl_secs: Option<i64>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE groups SET message_ttl_secs = ?1 WHERE id = ?2",
+            ttl_secs,
+            group_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Groups whose messages disappear, with the seconds they are kept for.
+    pub async fn list_message_ttls(&self) -> Result<Vec<(Uuid, i64)>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"SELECT id AS "id: uuid::Uuid", message_ttl_secs AS "message_ttl_secs!: i64" FROM groups WHERE message_ttl_secs IS NOT NULL"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(records
+            .into_iter()
+            .map(|record| (record.id, record.message_ttl_secs))
+            .collect())
+    }
+
+    pub async fn rename(&self, group_id: Uuid, name: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!("UPDATE groups SET name = ?1 WHERE id = ?2", name, group_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     pub async fn add_member(&self, group_id: Uuid, user_id: Uuid) -> Result<(), sqlx::Error> {
         let joined_at = Utc::now();
         sqlx::query!(
@@ -131,7 +179,7 @@ impl GroupRepository {
 
     pub async fn list_user_groups(&self, user_id: Uuid) -> Result<Vec<Group>, sqlx::Error> {
         let records = sqlx::query!(
-            "SELECT g.id as 'id: uuid::Uuid', g.name, g.avatar_url, g.created_at as 'created_at: chrono::DateTime<chrono::Utc>', g.join_code \
+            "SELECT g.id as 'id: uuid::Uuid', g.name, g.avatar_url, g.created_at as 'created_at: chrono::DateTime<chrono::Utc>', g.join_code, g.topic, g.message_ttl_secs \
              FROM groups g \
              JOIN group_members gm ON g.id = gm.group_id \
              WHERE gm.user_id = ?1",
@@ -148,6 +196,8 @@ impl GroupRepository {
                 avatar: record.avatar_url,
                 created_at: record.created_at,
                 join_code: record.join_code.unwrap_or_default(),
+                topic: record.topic,
+                message_ttl_secs: record.message_ttl_secs,
             })
             .collect())
     }
@@ -163,11 +213,14 @@ impl GroupRepository {
                 g.name,
                 g.avatar_url,
                 g.join_code,
+                g.topic,
+                g.message_ttl_secs,
                 g.created_at AS "group_created_at: chrono::DateTime<chrono::Utc>",
                 m.id AS "message_id: uuid::Uuid",
                 m.group_id AS "message_group_id: uuid::Uuid",
                 m.user_id AS "message_user_id: uuid::Uuid",
                 m.content AS message_content,
+                m.sender_name AS message_sender_name,
                 m.created_at AS "message_created_at: chrono::DateTime<chrono::Utc>"
             FROM groups g
             JOIN group_members gm ON g.id = gm.group_id
@@ -196,12 +249,15 @@ impl GroupRepository {
                     avatar: record.avatar_url,
                     created_at: record.group_created_at,
                     join_code: record.join_code.unwrap_or_default(),
+                    topic: record.topic,
+                    message_ttl_secs: record.message_ttl_secs,
                 },
                 last_message: record.message_id.map(|id| Message {
                     id,
                     group_id: record.message_group_id.unwrap(),
                     user_id: record.message_user_id.unwrap(),
                     content: record.message_content.unwrap(),
+                    sender_name: record.message_sender_name,
                     created_at: record.message_created_at.unwrap(),
                 }),
             })
@@ -210,7 +266,7 @@ impl GroupRepository {
 
     pub async fn get_by_join_code(&self, join_code: &str) -> Result<Option<Group>, sqlx::Error> {
         let record = sqlx::query!(
-            r#"SELECT id AS "group_id: Uuid", name, avatar_url, created_at AS "group_created_at: DateTime<Utc>", join_code
+            r#"SELECT id AS "group_id: Uuid", name, avatar_url, created_at AS "group_created_at: DateTime<Utc>", join_code, topic, message_ttl_secs
             FROM groups
             WHERE join_code = ?1"#,
             join_code
@@ -224,6 +280,8 @@ impl GroupRepository {
             avatar: record.avatar_url,
             created_at: record.group_created_at,
             join_code: record.join_code.unwrap_or_default(),
+            topic: record.topic,
+            message_ttl_secs: record.message_ttl_secs,
         }))
     }
 
@@ -237,4 +295,15 @@ impl GroupRepository {
             .await?;
         Ok(record.count > 0)
     }
+
+    /// The longest-standing member of the group, which is its creator unless they have left.
+    pub async fn owner(&self, group_id: Uuid) -> Result<Option<Uuid>, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT user_id AS "user_id: uuid::Uuid" FROM group_members WHERE group_id = ?1 ORDER BY joined_at LIMIT 1"#,
+            group_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(record.map(|record| record.user_id))
+    }
 }
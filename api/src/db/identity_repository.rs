@@ -0,0 +1,90 @@
+use crate::Pool;
+use crate::domain::identity::UserIdentity;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct IdentityRepository {
+    pub pool: Pool,
+}
+
+impl IdentityRepository {
+    pub fn new(pool: Pool) -> Self {
+        IdentityRepository { pool }
+    }
+
+    pub async fn create(&self, identity: UserIdentity) -> Result<Uuid, sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO user_identities (id, user_id, provider, subject, email, created_account, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            identity.id,
+            identity.user_id,
+            identity.provider,
+            identity.subject,
+            identity.email,
+            identity.created_account,
+            identity.created_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(identity.id)
+    }
+
+    pub async fn get_by_subject(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<UserIdentity>, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", user_id as "user_id: uuid::Uuid", provider, subject, email, created_account, created_at as "created_at: chrono::DateTime<chrono::Utc>" FROM user_identities WHERE provider = ?1 AND subject = ?2"#,
+            provider,
+            subject
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|record| UserIdentity {
+            id: record.id,
+            user_id: record.user_id,
+            provider: record.provider,
+            subject: record.subject,
+            email: record.email,
+            created_account: record.created_account,
+            created_at: record.created_at,
+        }))
+    }
+
+    pub async fn list_by_user(&self, user_id: Uuid) -> Result<Vec<UserIdentity>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", user_id as "user_id: uuid::Uuid", provider, subject, email, created_account, created_at as "created_at: chrono::DateTime<chrono::Utc>"
+            FROM user_identities
+            WHERE user_id = ?1
+            ORDER BY created_at"#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| UserIdentity {
+                id: record.id,
+                user_id: record.user_id,
+                provider: record.provider,
+                subject: record.subject,
+                email: record.email,
+                created_account: record.created_account,
+                created_at: record.created_at,
+            })
+            .collect())
+    }
+
+    pub async fn delete(&self, id: Uuid, user_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM user_identities WHERE id = ?1 AND user_id = ?2",
+            id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
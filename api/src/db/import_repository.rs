@@ -0,0 +1,178 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use sha2::{Digest as _, Sha256};
+use sqlx::{Sqlite, Transaction};
+use uuid::Uuid;
+
+use crate::Pool;
+use crate::domain::{group::Group, user::User};
+use crate::import::{ImportReport, ImportSource, ImportedAuthor, ImportedChannel};
+
+/// Longest username the users table takes.
+const MAX_USERNAME_LENGTH: usize = 32;
+
+#[derive(Clone)]
+pub struct ImportRepository {
+    pub pool: Pool,
+}
+
+impl ImportRepository {
+    pub fn new(pool: Pool) -> Self {
+        ImportRepository { pool }
+    }
+}
+
+impl ImportRepository {
+    /// Writes the channels in one transaction, nothing is kept if any of it fails.
+    pub async fn import(
+        &self,
+        source: ImportSource,
+        channels: &[ImportedChannel],
+    ) -> Result<ImportReport, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut report = ImportReport::default();
+        // Authors and whether they are existing users, looked up once per import
+        let mut authors = HashMap::<&ImportedAuthor, (Uuid, bool)>::new();
+        for channel in channels {
+            let group_id = find_or_create_group(&mut tx, channel, &mut report).await?;
+            let mut members = HashSet::new();
+            for message in &channel.messages {
+                let (user_id, matched) = match authors.get(&message.author) {
+                    Some(author) => *author,
+                    None => {
+                        let author =
+                            find_or_create_user(&mut tx, source, &message.author, &mut report)
+                                .await?;
+                        authors.insert(&message.author, author);
+                        author
+                    }
+                };
+                // Only real users join, placeholders would just crowd the member list
+                if matched && members.insert(user_id) {
+                    let joined_at = Utc::now();
+                    sqlx::query!(
+                        "INSERT OR IGNORE INTO group_members (group_id, user_id, joined_at) VALUES (?1, ?2, ?3)",
+                        group_id,
+                        user_id,
+                        joined_at
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                let id = message_id(source, &channel.name, &message.source_id);
+                let inserted = sqlx::query!(
+                    "INSERT INTO messages (id, group_id, user_id, content, created_at) VALUES (?1, ?2, ?3, ?4, ?5) ON CONFLICT (id) DO NOTHING",
+                    id,
+                    group_id,
+                    user_id,
+                    message.text,
+                    message.sent_at
+                )
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+                if inserted == 0 {
+                    report.messages_skipped += 1;
+                } else {
+                    report.messages_imported += 1;
+                }
+            }
+        }
+        tx.commit().await?;
+        Ok(report)
+    }
+}
+
+async fn find_or_create_group(
+    tx: &mut Transaction<'_, Sqlite>,
+    channel: &ImportedChannel,
+    report: &mut ImportReport,
+) -> Result<Uuid, sqlx::Error> {
+    let existing = sqlx::query!(
+        "SELECT id as 'id: uuid::Uuid' FROM groups WHERE name = ?1",
+        channel.name
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    if let Some(group) = existing {
+        report.groups_reused += 1;
+        return Ok(group.id);
+    }
+    let group = Group {
+        topic: channel.topic.clone(),
+        ..Group::new(channel.name.clone())
+    };
+    sqlx::query!(
+        "INSERT INTO groups (id, name, avatar_url, created_at, join_code, topic) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        group.id,
+        group.name,
+        group.avatar,
+        group.created_at,
+        group.join_code,
+        group.topic
+    )
+    .execute(&mut **tx)
+    .await?;
+    report.groups_created += 1;
+    Ok(group.id)
+}
+
+/// The user with the author's username, or else the placeholder standing in
+/// for the author. Returns whether an existing user was matched.
+async fn find_or_create_user(
+    tx: &mut Transaction<'_, Sqlite>,
+    source: ImportSource,
+    author: &ImportedAuthor,
+    report: &mut ImportReport,
+) -> Result<(Uuid, bool), sqlx::Error> {
+    if let Some(user_id) = user_id_by_username(tx, &author.username).await? {
+        report.users_matched += 1;
+        return Ok((user_id, true));
+    }
+    let username = format!("{}-{}", source.name(), author.username)
+        .chars()
+        .take(MAX_USERNAME_LENGTH)
+        .collect::<String>();
+    // Placeholders of an earlier import of the same source are reused
+    if let Some(user_id) = user_id_by_username(tx, &username).await? {
+        return Ok((user_id, false));
+    }
+    // Nobody knows the password of a placeholder, so it can't sign in
+    let password = password_auth::generate_hash(nanoid::nanoid!(32));
+    let user = User::new(username, password);
+    let now = Utc::now();
+    sqlx::query!(
+        "INSERT INTO users (id, username, password, created_at) VALUES (?1, ?2, ?3, ?4)",
+        user.id,
+        user.username,
+        user.password,
+        now
+    )
+    .execute(&mut **tx)
+    .await?;
+    report.placeholders_created += 1;
+    Ok((user.id, false))
+}
+
+async fn user_id_by_username(
+    tx: &mut Transaction<'_, Sqlite>,
+    username: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let user = sqlx::query!(
+        r#"SELECT id as "id: uuid::Uuid" FROM users WHERE username = ?1"#,
+        username
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    Ok(user.map(|user| user.id))
+}
+
+/// Derived from the message's place in the export, so a repeated import finds
+/// the messages it already wrote.
+fn message_id(source: ImportSource, channel: &str, source_id: &str) -> Uuid {
+    let hash = Sha256::digest(format!("{}:{channel}:{source_id}", source.name()));
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    uuid::Builder::from_custom_bytes(bytes).into_uuid()
+}
@@ -0,0 +1,83 @@
+use crate::Pool;
+use crate::domain::link_preview::LinkPreview;
+use std::collections::HashMap;
+
+#[derive(Clone)]
+pub struct LinkPreviewRepository {
+    pub pool: Pool,
+}
+
+impl LinkPreviewRepository {
+    pub fn new(pool: Pool) -> Self {
+        LinkPreviewRepository { pool }
+    }
+
+    pub async fn get(&self, url: &str) -> Result<Option<LinkPreview>, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT url, title, description, image_url, site_name, fetched_at as "fetched_at: chrono::DateTime<chrono::Utc>" FROM link_previews WHERE url = ?1"#,
+            url
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(record.map(|record| LinkPreview {
+            url: record.url,
+            title: record.title,
+            description: record.description,
+            image_url: record.image_url,
+            site_name: record.site_name,
+            fetched_at: record.fetched_at,
+        }))
+    }
+
+    /// Replaces an earlier preview of the same url.
+    /// The cached previews of several urls in one round trip, keyed by url.
+    pub async fn get_many(
+        &self,
+        urls: &[String],
+    ) -> Result<HashMap<String, LinkPreview>, sqlx::Error> {
+        if urls.is_empty() {
+            return Ok(HashMap::new());
+        }
+        // SQLite can't bind a list, the urls are passed as a JSON array instead
+        let urls = serde_json::to_string(urls).expect("Serializing strings can't fail");
+        let records = sqlx::query!(
+            r#"SELECT url, title, description, image_url, site_name, fetched_at as "fetched_at: chrono::DateTime<chrono::Utc>"
+            FROM link_previews
+            WHERE url IN (SELECT value FROM json_each(?1))"#,
+            urls
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(records
+            .into_iter()
+            .map(|record| {
+                let preview = LinkPreview {
+                    url: record.url,
+                    title: record.title,
+                    description: record.description,
+                    image_url: record.image_url,
+                    site_name: record.site_name,
+                    fetched_at: record.fetched_at,
+                };
+                (preview.url.clone(), preview)
+            })
+            .collect())
+    }
+
+    pub async fn save(&self, preview: &LinkPreview) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO link_previews (url, title, description, image_url, site_name, fetched_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT (url) DO UPDATE SET title = excluded.title, description = excluded.description,
+            image_url = excluded.image_url, site_name = excluded.site_name, fetched_at = excluded.fetched_at",
+            preview.url,
+            preview.title,
+            preview.description,
+            preview.image_url,
+            preview.site_name,
+            preview.fetched_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
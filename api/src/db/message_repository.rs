@@ -3,6 +3,7 @@ use crate::domain::group::Group;
 use crate::domain::group_member::GroupMember;
 use crate::domain::message::Message;
 use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt, stream::BoxStream};
 use uuid::Uuid;
 
 #[derive(Clone)]
@@ -17,11 +18,12 @@ impl MessageRepository {
 
     pub async fn create(&self, message: Message) -> Result<Uuid, sqlx::Error> {
         sqlx::query!(
-            "INSERT INTO messages (id, group_id, user_id, content, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            "INSERT INTO messages (id, group_id, user_id, content, sender_name, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             message.id,
             message.group_id,
             message.user_id,
             message.content,
+            message.sender_name,
             message.created_at
         )
         .execute(&self.pool)
@@ -29,9 +31,25 @@ impl MessageRepository {
         Ok(message.id)
     }
 
+    /// Inserts the message unless one with its id exists, returns whether it was inserted.
+    pub async fn create_if_absent(&self, message: Message) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "INSERT INTO messages (id, group_id, user_id, content, sender_name, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6) ON CONFLICT (id) DO NOTHING",
+            message.id,
+            message.group_id,
+            message.user_id,
+            message.content,
+            message.sender_name,
+            message.created_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn get_by_id(&self, id: Uuid) -> Result<Message, sqlx::Error> {
         let record = sqlx::query!(
-            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", content, created_at as "created_at: chrono::DateTime<chrono::Utc>" FROM messages WHERE id = ?1"#,
+            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", content, sender_name, created_at as "created_at: chrono::DateTime<chrono::Utc>" FROM messages WHERE id = ?1"#,
             id
         )
         .fetch_one(&self.pool)
@@ -42,33 +60,57 @@ impl MessageRepository {
             group_id: record.group_id,
             user_id: record.user_id,
             content: record.content,
+            sender_name: record.sender_name,
             created_at: record.created_at,
         })
     }
 
-    pub async fn get_by_group(&self, group_id: Uuid) -> Result<Vec<Message>, sqlx::Error> {
-        let records = sqlx::query!(
-            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", content, created_at as "created_at: chrono::DateTime<chrono::Utc>" FROM messages WHERE group_id = ?1 ORDER BY created_at ASC"#,
+    pub async fn get_last_by_group(&self, group_id: Uuid) -> Result<Option<Message>, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", content, sender_name, created_at as "created_at: chrono::DateTime<chrono::Utc>" FROM messages WHERE group_id = ?1 ORDER BY created_at DESC LIMIT 1"#,
             group_id
         )
-        .fetch_all(&self.pool)
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(records
-            .into_iter()
-            .map(|record| Message {
+        Ok(record.map(|record| Message {
+            id: record.id,
+            group_id: record.group_id,
+            user_id: record.user_id,
+            content: record.content,
+            sender_name: record.sender_name,
+            created_at: record.created_at,
+        }))
+    }
+
+    /// All messages of a group with the username of their sender, oldest first,
+    /// read from the database as the stream is polled.
+    pub fn get_by_group<'a>(
+        &'a self,
+        group_id: &'a Uuid,
+    ) -> BoxStream<'a, Result<(Message, String), sqlx::Error>> {
+        sqlx::query!(
+            r#"SELECT messages.id as "id: uuid::Uuid", messages.group_id as "group_id: uuid::Uuid", messages.user_id as "user_id: uuid::Uuid", messages.content, messages.sender_name, messages.created_at as "created_at: chrono::DateTime<chrono::Utc>", users.username FROM messages JOIN users ON users.id = messages.user_id WHERE messages.group_id = ?1 ORDER BY messages.created_at ASC"#,
+            *group_id
+        )
+        .fetch(&self.pool)
+        .map_ok(|record| {
+            let message = Message {
                 id: record.id,
                 group_id: record.group_id,
                 user_id: record.user_id,
                 content: record.content,
+                sender_name: record.sender_name,
                 created_at: record.created_at,
-            })
-            .collect())
+            };
+            (message, record.username)
+        })
+        .boxed()
     }
 
     pub async fn get_by_user(&self, user_id: Uuid) -> Result<Vec<Message>, sqlx::Error> {
         let records = sqlx::query!(
-            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", content, created_at as "created_at: chrono::DateTime<chrono::Utc>" FROM messages WHERE user_id = ?1 ORDER BY created_at ASC"#,
+            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", content, sender_name, created_at as "created_at: chrono::DateTime<chrono::Utc>" FROM messages WHERE user_id = ?1 ORDER BY created_at ASC"#,
             user_id
         )
         .fetch_all(&self.pool)
@@ -81,6 +123,7 @@ impl MessageRepository {
                 group_id: record.group_id,
                 user_id: record.user_id,
                 content: record.content,
+                sender_name: record.sender_name,
                 created_at: record.created_at,
             })
             .collect())
@@ -93,19 +136,49 @@ impl MessageRepository {
         Ok(())
     }
 
+    /// Deletes up to `limit` messages of a group sent before `before`,
+    /// together with their polls, and returns their ids.
+    pub async fn delete_sent_before(
+        &self,
+        group_id: Uuid,
+        before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<Uuid>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"DELETE FROM messages
+            WHERE id IN (
+                SELECT id FROM messages
+                WHERE group_id = ?1 AND created_at < ?2
+                ORDER BY created_at ASC
+                LIMIT ?3
+            )
+            RETURNING id as "id: uuid::Uuid""#,
+            group_id,
+            before,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(records.into_iter().map(|record| record.id).collect())
+    }
+
+    /// Newest messages first, without the messages of users `viewer_id` has blocked.
     pub async fn get_by_group_paginated(
         &self,
         group_id: Uuid,
+        viewer_id: Uuid,
         offset: i64,
         limit: i64,
     ) -> Result<Vec<Message>, sqlx::Error> {
         let records = sqlx::query!(
-            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", content, created_at as "created_at: chrono::DateTime<chrono::Utc>"
+            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", content, sender_name, created_at as "created_at: chrono::DateTime<chrono::Utc>"
             FROM messages
             WHERE group_id = ?1
+            AND user_id NOT IN (SELECT blocked_id FROM user_blocks WHERE blocker_id = ?2)
             ORDER BY created_at DESC
-            LIMIT ?2 OFFSET ?3"#,
+            LIMIT ?3 OFFSET ?4"#,
             group_id,
+            viewer_id,
             limit,
             offset
         )
@@ -119,6 +192,47 @@ impl MessageRepository {
                 group_id: record.group_id,
                 user_id: record.user_id,
                 content: record.content,
+                sender_name: record.sender_name,
+                created_at: record.created_at,
+            })
+            .collect())
+    }
+
+    /// Messages sent at or after `after`, oldest first, without `after` itself
+    /// and the messages of users `viewer_id` has blocked.
+    pub async fn get_by_group_after(
+        &self,
+        group_id: Uuid,
+        viewer_id: Uuid,
+        after: &Message,
+        limit: i64,
+    ) -> Result<Vec<Message>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", content, sender_name, created_at as "created_at: chrono::DateTime<chrono::Utc>"
+            FROM messages
+            WHERE group_id = ?1
+            AND created_at >= ?2
+            AND id != ?3
+            AND user_id NOT IN (SELECT blocked_id FROM user_blocks WHERE blocker_id = ?4)
+            ORDER BY created_at ASC
+            LIMIT ?5"#,
+            group_id,
+            after.created_at,
+            after.id,
+            viewer_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| Message {
+                id: record.id,
+                group_id: record.group_id,
+                user_id: record.user_id,
+                content: record.content,
+                sender_name: record.sender_name,
                 created_at: record.created_at,
             })
             .collect())
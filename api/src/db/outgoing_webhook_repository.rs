@@ -0,0 +1,182 @@
+use crate::Pool;
+use crate::domain::outgoing_webhook::{OutgoingWebhook, WebhookDelivery};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct OutgoingWebhookRepository {
+    pub pool: Pool,
+}
+
+impl OutgoingWebhookRepository {
+    pub fn new(pool: Pool) -> Self {
+        OutgoingWebhookRepository { pool }
+    }
+
+    pub async fn create(&self, webhook: OutgoingWebhook) -> Result<Uuid, sqlx::Error> {
+        let events = OutgoingWebhook::events_to_string(&webhook.events);
+        sqlx::query!(
+            "INSERT INTO outgoing_webhooks (id, group_id, url, secret, events, created_by, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            webhook.id,
+            webhook.group_id,
+            webhook.url,
+            webhook.secret,
+            events,
+            webhook.created_by,
+            webhook.created_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(webhook.id)
+    }
+
+    pub async fn get_by_id(&self, id: Uuid) -> Result<Option<OutgoingWebhook>, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", url, secret, events, created_by as "created_by: uuid::Uuid", created_at as "created_at: chrono::DateTime<chrono::Utc>" FROM outgoing_webhooks WHERE id = ?1"#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|record| OutgoingWebhook {
+            id: record.id,
+            group_id: record.group_id,
+            url: record.url,
+            secret: record.secret,
+            events: OutgoingWebhook::events_from_string(&record.events),
+            created_by: record.created_by,
+            created_at: record.created_at,
+        }))
+    }
+
+    pub async fn list_by_group(&self, group_id: Uuid) -> Result<Vec<OutgoingWebhook>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", url, secret, events, created_by as "created_by: uuid::Uuid", created_at as "created_at: chrono::DateTime<chrono::Utc>"
+            FROM outgoing_webhooks
+            WHERE group_id = ?1
+            ORDER BY created_at DESC"#,
+            group_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| OutgoingWebhook {
+                id: record.id,
+                group_id: record.group_id,
+                url: record.url,
+                secret: record.secret,
+                events: OutgoingWebhook::events_from_string(&record.events),
+                created_by: record.created_by,
+                created_at: record.created_at,
+            })
+            .collect())
+    }
+
+    /// Deletes the webhook and its queued deliveries, returns false if it did
+    /// not belong to the group.
+    pub async fn delete(&self, id: Uuid, group_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM outgoing_webhooks WHERE id = ?1 AND group_id = ?2",
+            id,
+            group_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn enqueue(&self, delivery: WebhookDelivery) -> Result<(), sqlx::Error> {
+        let event = delivery.event.to_string();
+        sqlx::query!(
+            "INSERT INTO webhook_deliveries (id, webhook_id, event, payload, attempts, next_attempt_at, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            delivery.id,
+            delivery.webhook_id,
+            event,
+            delivery.payload,
+            delivery.attempts,
+            delivery.next_attempt_at,
+            delivery.created_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Deliveries whose next attempt is due, oldest first.
+    pub async fn due(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", webhook_id as "webhook_id: uuid::Uuid", event, payload, attempts, next_attempt_at as "next_attempt_at: chrono::DateTime<chrono::Utc>", last_error, created_at as "created_at: chrono::DateTime<chrono::Utc>", delivered_at as "delivered_at: chrono::DateTime<chrono::Utc>"
+            FROM webhook_deliveries
+            WHERE next_attempt_at IS NOT NULL AND next_attempt_at <= ?1
+            ORDER BY next_attempt_at ASC
+            LIMIT ?2"#,
+            now,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .filter_map(|record| {
+                Some(WebhookDelivery {
+                    id: record.id,
+                    webhook_id: record.webhook_id,
+                    event: record.event.parse().ok()?,
+                    payload: record.payload,
+                    attempts: record.attempts,
+                    next_attempt_at: record.next_attempt_at,
+                    last_error: record.last_error,
+                    created_at: record.created_at,
+                    delivered_at: record.delivered_at,
+                })
+            })
+            .collect())
+    }
+
+    /// Postpones the next attempt to `until` unless another worker did so first,
+    /// returns whether this worker may attempt the delivery.
+    pub async fn claim(
+        &self,
+        delivery: &WebhookDelivery,
+        until: DateTime<Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE webhook_deliveries SET next_attempt_at = ?1 WHERE id = ?2 AND next_attempt_at = ?3",
+            until,
+            delivery.id,
+            delivery.next_attempt_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Records an attempt, a delivery without `next_attempt_at` is not retried.
+    pub async fn record_attempt(
+        &self,
+        id: Uuid,
+        attempts: i64,
+        next_attempt_at: Option<DateTime<Utc>>,
+        last_error: Option<String>,
+        delivered_at: Option<DateTime<Utc>>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE webhook_deliveries SET attempts = ?1, next_attempt_at = ?2, last_error = ?3, delivered_at = ?4 WHERE id = ?5",
+            attempts,
+            next_attempt_at,
+            last_error,
+            delivered_at,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
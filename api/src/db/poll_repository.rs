@@ -0,0 +1,303 @@
+use crate::Pool;
+use crate::domain::message::Message;
+use crate::domain::poll::{Poll, PollOption, PollVote};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Ids as a JSON array of hex strings, matched with `unhex(value)` from `json_each`
+/// since SQLite can't bind a list.
+fn json_ids(ids: &[Uuid]) -> String {
+    serde_json::to_string(&ids.iter().map(|id| id.simple().to_string()).collect::<Vec<_>>())
+        .expect("Serializing strings can't fail")
+}
+
+#[derive(Clone)]
+pub struct PollRepository {
+    pub pool: Pool,
+}
+
+impl PollRepository {
+    pub fn new(pool: Pool) -> Self {
+        PollRepository { pool }
+    }
+
+    /// Inserts the message holding the question together with its poll.
+    pub async fn create(&self, message: &Message, poll: &Poll) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "INSERT INTO messages (id, group_id, user_id, content, sender_name, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            message.id,
+            message.group_id,
+            message.user_id,
+            message.content,
+            message.sender_name,
+            message.created_at
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO polls (message_id, multiple_choice, anonymous, closes_at) VALUES (?1, ?2, ?3, ?4)",
+            poll.message_id,
+            poll.multiple_choice,
+            poll.anonymous,
+            poll.closes_at
+        )
+        .execute(&mut *tx)
+        .await?;
+        for option in &poll.options {
+            sqlx::query!(
+                "INSERT INTO poll_options (id, poll_id, position, text) VALUES (?1, ?2, ?3, ?4)",
+                option.id,
+                poll.message_id,
+                option.position,
+                option.text
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// The poll of a message, `None` for messages without one.
+    pub async fn get_by_message_id(&self, message_id: Uuid) -> Result<Option<Poll>, sqlx::Error> {
+        let Some(record) = sqlx::query!(
+            r#"SELECT message_id as "message_id: uuid::Uuid", multiple_choice, anonymous, closes_at as "closes_at: chrono::DateTime<chrono::Utc>" FROM polls WHERE message_id = ?1"#,
+            message_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let options = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", position, text FROM poll_options WHERE poll_id = ?1 ORDER BY position ASC"#,
+            message_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(Some(Poll {
+            message_id: record.message_id,
+            multiple_choice: record.multiple_choice,
+            anonymous: record.anonymous,
+            closes_at: record.closes_at,
+            options: options
+                .into_iter()
+                .map(|option| PollOption {
+                    id: option.id,
+                    position: option.position,
+                    text: option.text,
+                })
+                .collect(),
+        }))
+    }
+
+    /// The polls of the given messages in one round trip, keyed by message id.
+    pub async fn get_by_message_ids(
+        &self,
+        message_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, Poll>, sqlx::Error> {
+        if message_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let ids = json_ids(message_ids);
+        let records = sqlx::query!(
+            r#"SELECT message_id as "message_id: uuid::Uuid", multiple_choice, anonymous, closes_at as "closes_at: chrono::DateTime<chrono::Utc>"
+            FROM polls
+            WHERE message_id IN (SELECT unhex(value) FROM json_each(?1))"#,
+            ids
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        if records.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let options = sqlx::query!(
+            r#"SELECT poll_id as "poll_id: uuid::Uuid", id as "id: uuid::Uuid", position, text
+            FROM poll_options
+            WHERE poll_id IN (SELECT unhex(value) FROM json_each(?1))
+            ORDER BY position ASC"#,
+            ids
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut polls: HashMap<Uuid, Poll> = records
+            .into_iter()
+            .map(|record| {
+                let poll = Poll {
+                    message_id: record.message_id,
+                    multiple_choice: record.multiple_choice,
+                    anonymous: record.anonymous,
+                    closes_at: record.closes_at,
+                    options: Vec::new(),
+                };
+                (record.message_id, poll)
+            })
+            .collect();
+        for option in options {
+            if let Some(poll) = polls.get_mut(&option.poll_id) {
+                poll.options.push(PollOption {
+                    id: option.id,
+                    position: option.position,
+                    text: option.text,
+                });
+            }
+        }
+        Ok(polls)
+    }
+
+    /// All votes of a poll, in the order they were cast.
+    pub async fn list_votes(&self, poll_id: Uuid) -> Result<Vec<PollVote>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"SELECT v.option_id as "option_id: uuid::Uuid", v.user_id as "user_id: uuid::Uuid", u.username
+            FROM poll_votes v
+            JOIN users u ON u.id = v.user_id
+            WHERE v.poll_id = ?1
+            ORDER BY v.created_at ASC"#,
+            poll_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| PollVote {
+                option_id: record.option_id,
+                user_id: record.user_id,
+                username: record.username,
+            })
+            .collect())
+    }
+
+    /// The votes of several polls in one round trip, keyed by poll id and in the order they were cast.
+    pub async fn list_votes_by_poll_ids(
+        &self,
+        poll_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, Vec<PollVote>>, sqlx::Error> {
+        if poll_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let ids = json_ids(poll_ids);
+        let records = sqlx::query!(
+            r#"SELECT v.poll_id as "poll_id: uuid::Uuid", v.option_id as "option_id: uuid::Uuid", v.user_id as "user_id: uuid::Uuid", u.username
+            FROM poll_votes v
+            JOIN users u ON u.id = v.user_id
+            WHERE v.poll_id IN (SELECT unhex(value) FROM json_each(?1))
+            ORDER BY v.created_at ASC"#,
+            ids
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut votes: HashMap<Uuid, Vec<PollVote>> = HashMap::new();
+        for record in records {
+            votes.entry(record.poll_id).or_default().push(PollVote {
+                option_id: record.option_id,
+                user_id: record.user_id,
+                username: record.username,
+            });
+        }
+        Ok(votes)
+    }
+
+    /// Replaces the votes of a user, no options withdraws them.
+    pub async fn set_votes(
+        &self,
+        poll_id: Uuid,
+        user_id: Uuid,
+        option_ids: &[Uuid],
+        voted_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "DELETE FROM poll_votes WHERE poll_id = ?1 AND user_id = ?2",
+            poll_id,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        for option_id in option_ids {
+            sqlx::query!(
+                "INSERT INTO poll_votes (poll_id, option_id, user_id, created_at) VALUES (?1, ?2, ?3, ?4)",
+                poll_id,
+                option_id,
+                user_id,
+                voted_at
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn close(&self, poll_id: Uuid, closes_at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE polls SET closes_at = ?1 WHERE message_id = ?2",
+            closes_at,
+            poll_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, domain::group::Group, test_support};
+
+    #[tokio::test]
+    async fn loads_the_polls_of_a_page_at_once() {
+        let state = test_support::state(Config::default()).await;
+        let repository = &state.poll_repository;
+        let alice = test_support::user(&state, "alice").await;
+        let bob = test_support::user(&state, "bob").await;
+        let group_id = state
+            .group_repository
+            .create_group(Group::new("lunch".to_string()))
+            .await
+            .unwrap();
+        let mut polls = Vec::new();
+        for question in ["Pizza?", "Sushi?"] {
+            let message = Message::new(group_id, alice, question.to_string());
+            let options = vec!["Yes".to_string(), "No".to_string(), "Maybe".to_string()];
+            let poll = Poll::new(message.id, options, false, false, None);
+            repository.create(&message, &poll).await.unwrap();
+            polls.push(poll);
+        }
+        let plain = Message::new(group_id, bob, "Hungry".to_string());
+        state
+            .message_repository
+            .create(plain.clone())
+            .await
+            .unwrap();
+        let now = Utc::now();
+        for (user, option) in [(alice, 0), (bob, 2)] {
+            let option_ids = [polls[0].options[option].id];
+            repository
+                .set_votes(polls[0].message_id, user, &option_ids, now)
+                .await
+                .unwrap();
+        }
+
+        let ids = [polls[0].message_id, plain.id, polls[1].message_id];
+        let loaded = repository.get_by_message_ids(&ids).await.unwrap();
+        assert_eq!(loaded.len(), 2);
+        for poll in &polls {
+            assert_eq!(loaded.get(&poll.message_id), Some(poll));
+        }
+        let votes = repository.list_votes_by_poll_ids(&ids).await.unwrap();
+        assert_eq!(
+            votes.get(&polls[0].message_id),
+            Some(&repository.list_votes(polls[0].message_id).await.unwrap())
+        );
+        assert_eq!(votes.get(&polls[0].message_id).map(Vec::len), Some(2));
+        assert!(!votes.contains_key(&polls[1].message_id));
+        assert!(repository.get_by_message_ids(&[]).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::Pool;
+use crate::domain::profile::{Avatar, Profile};
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct ProfileRepository {
+    pub pool: Pool,
+}
+
+impl ProfileRepository {
+    pub fn new(pool: Pool) -> Self {
+        ProfileRepository { pool }
+    }
+
+    pub async fn get_by_user_id(&self, user_id: Uuid) -> Result<Profile, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", username, display_name, bio, avatar_updated_at as "avatar_updated_at: chrono::DateTime<chrono::Utc>" FROM users WHERE id = ?1"#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Profile {
+            user_id: record.id,
+            username: record.username,
+            display_name: record.display_name,
+            bio: record.bio,
+            avatar_updated_at: record.avatar_updated_at,
+        })
+    }
+
+    pub async fn get_by_username(&self, username: &str) -> Result<Option<Profile>, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", username, display_name, bio, avatar_updated_at as "avatar_updated_at: chrono::DateTime<chrono::Utc>" FROM users WHERE username = ?1"#,
+            username
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|record| Profile {
+            user_id: record.id,
+            username: record.username,
+            display_name: record.display_name,
+            bio: record.bio,
+            avatar_updated_at: record.avatar_updated_at,
+        }))
+    }
+
+    pub async fn update(
+        &self,
+        user_id: Uuid,
+        display_name: Option<String>,
+        bio: Option<String>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE users SET display_name = ?1, bio = ?2 WHERE id = ?3",
+            display_name,
+            bio,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_avatar(&self, user_id: Uuid) -> Result<Option<Avatar>, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT user_id as "user_id: uuid::Uuid", content_type, data, updated_at as "updated_at: chrono::DateTime<chrono::Utc>" FROM user_avatars WHERE user_id = ?1"#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|record| Avatar {
+            user_id: record.user_id,
+            content_type: record.content_type,
+            data: record.data,
+            updated_at: record.updated_at,
+        }))
+    }
+
+    pub async fn set_avatar(&self, avatar: Avatar) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "INSERT INTO user_avatars (user_id, content_type, data, updated_at) VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (user_id) DO UPDATE SET content_type = excluded.content_type, data = excluded.data, updated_at = excluded.updated_at",
+            avatar.user_id,
+            avatar.content_type,
+            avatar.data,
+            avatar.updated_at
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE users SET avatar_updated_at = ?1 WHERE id = ?2",
+            avatar.updated_at,
+            avatar.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn remove_avatar(&self, user_id: Uuid) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("DELETE FROM user_avatars WHERE user_id = ?1", user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            "UPDATE users SET avatar_updated_at = NULL WHERE id = ?1",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
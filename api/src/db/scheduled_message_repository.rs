@@ -0,0 +1,187 @@
+use crate::Pool;
+use crate::domain::scheduled_message::ScheduledMessage;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct ScheduledMessageRepository {
+    pub pool: Pool,
+}
+
+impl ScheduledMessageRepository {
+    pub fn new(pool: Pool) -> Self {
+        ScheduledMessageRepository { pool }
+    }
+
+    pub async fn create(&self, message: ScheduledMessage) -> Result<Uuid, sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO scheduled_messages (id, group_id, user_id, content, send_at, claimed_until, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            message.id,
+            message.group_id,
+            message.user_id,
+            message.content,
+            message.send_at,
+            message.claimed_until,
+            message.created_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(message.id)
+    }
+
+    pub async fn get_by_id(&self, id: Uuid) -> Result<Option<ScheduledMessage>, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", content, send_at as "send_at: chrono::DateTime<chrono::Utc>", claimed_until as "claimed_until: chrono::DateTime<chrono::Utc>", created_at as "created_at: chrono::DateTime<chrono::Utc>" FROM scheduled_messages WHERE id = ?1"#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|record| ScheduledMessage {
+            id: record.id,
+            group_id: record.group_id,
+            user_id: record.user_id,
+            content: record.content,
+            send_at: record.send_at,
+            claimed_until: record.claimed_until,
+            created_at: record.created_at,
+        }))
+    }
+
+    /// The messages a user scheduled in a group, the next one first.
+    pub async fn list_by_user_and_group(
+        &self,
+        user_id: Uuid,
+        group_id: Uuid,
+    ) -> Result<Vec<ScheduledMessage>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", content, send_at as "send_at: chrono::DateTime<chrono::Utc>", claimed_until as "claimed_until: chrono::DateTime<chrono::Utc>", created_at as "created_at: chrono::DateTime<chrono::Utc>"
+            FROM scheduled_messages
+            WHERE user_id = ?1 AND group_id = ?2
+            ORDER BY send_at ASC"#,
+            user_id,
+            group_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| ScheduledMessage {
+                id: record.id,
+                group_id: record.group_id,
+                user_id: record.user_id,
+                content: record.content,
+                send_at: record.send_at,
+                claimed_until: record.claimed_until,
+                created_at: record.created_at,
+            })
+            .collect())
+    }
+
+    /// Changes a message of the user that is not being sent right now,
+    /// returns false if there is no such message.
+    pub async fn update(
+        &self,
+        id: Uuid,
+        user_id: Uuid,
+        content: String,
+        send_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE scheduled_messages SET content = ?1, send_at = ?2
+            WHERE id = ?3 AND user_id = ?4 AND (claimed_until IS NULL OR claimed_until <= ?5)",
+            content,
+            send_at,
+            id,
+            user_id,
+            now
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Cancels a message of the user, returns false if there is no such message.
+    pub async fn delete(&self, id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM scheduled_messages WHERE id = ?1 AND user_id = ?2",
+            id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Messages that are due and not being sent by a scheduler, oldest first.
+    pub async fn due(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ScheduledMessage>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", content, send_at as "send_at: chrono::DateTime<chrono::Utc>", claimed_until as "claimed_until: chrono::DateTime<chrono::Utc>", created_at as "created_at: chrono::DateTime<chrono::Utc>"
+            FROM scheduled_messages
+            WHERE send_at <= ?1 AND (claimed_until IS NULL OR claimed_until <= ?1)
+            ORDER BY send_at ASC
+            LIMIT ?2"#,
+            now,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| ScheduledMessage {
+                id: record.id,
+                group_id: record.group_id,
+                user_id: record.user_id,
+                content: record.content,
+                send_at: record.send_at,
+                claimed_until: record.claimed_until,
+                created_at: record.created_at,
+            })
+            .collect())
+    }
+
+    /// Claims the message until `until` unless another scheduler did so first,
+    /// returns whether this scheduler may send it.
+    pub async fn claim(
+        &self,
+        message: &ScheduledMessage,
+        until: DateTime<Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE scheduled_messages SET claimed_until = ?1 WHERE id = ?2 AND claimed_until IS ?3",
+            until,
+            message.id,
+            message.claimed_until
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Gives up the claim and tries again at `send_at`.
+    pub async fn reschedule(&self, id: Uuid, send_at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE scheduled_messages SET send_at = ?1, claimed_until = NULL WHERE id = ?2",
+            send_at,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Removes a message that was sent or can't be sent anymore.
+    pub async fn finish(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM scheduled_messages WHERE id = ?1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
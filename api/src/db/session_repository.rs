@@ -0,0 +1,157 @@
+use crate::Pool;
+use crate::domain::session::UserSession;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct SessionRepository {
+    pub pool: Pool,
+}
+
+impl SessionRepository {
+    pub fn new(pool: Pool) -> Self {
+        SessionRepository { pool }
+    }
+
+    pub async fn create(&self, session: UserSession) -> Result<Uuid, sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO user_sessions (id, session_id, user_id, user_agent, ip_address, created_at, last_seen_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            session.id,
+            session.session_id,
+            session.user_id,
+            session.user_agent,
+            session.ip_address,
+            session.created_at,
+            session.last_seen_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(session.id)
+    }
+
+    pub async fn get_by_session_id(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<UserSession>, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", session_id, user_id as "user_id: uuid::Uuid", user_agent, ip_address, created_at as "created_at: chrono::DateTime<chrono::Utc>", last_seen_at as "last_seen_at: chrono::DateTime<chrono::Utc>", revoked_at as "revoked_at: chrono::DateTime<chrono::Utc>" FROM user_sessions WHERE session_id = ?1"#,
+            session_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|record| UserSession {
+            id: record.id,
+            session_id: record.session_id,
+            user_id: record.user_id,
+            user_agent: record.user_agent,
+            ip_address: record.ip_address,
+            created_at: record.created_at,
+            last_seen_at: record.last_seen_at,
+            revoked_at: record.revoked_at,
+        }))
+    }
+
+    pub async fn list_active_by_user(&self, user_id: Uuid) -> Result<Vec<UserSession>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", session_id, user_id as "user_id: uuid::Uuid", user_agent, ip_address, created_at as "created_at: chrono::DateTime<chrono::Utc>", last_seen_at as "last_seen_at: chrono::DateTime<chrono::Utc>", revoked_at as "revoked_at: chrono::DateTime<chrono::Utc>"
+            FROM user_sessions
+            WHERE user_id = ?1 AND revoked_at IS NULL
+            ORDER BY last_seen_at DESC"#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| UserSession {
+                id: record.id,
+                session_id: record.session_id,
+                user_id: record.user_id,
+                user_agent: record.user_agent,
+                ip_address: record.ip_address,
+                created_at: record.created_at,
+                last_seen_at: record.last_seen_at,
+                revoked_at: record.revoked_at,
+            })
+            .collect())
+    }
+
+    pub async fn touch(
+        &self,
+        id: Uuid,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+        last_seen_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE user_sessions SET user_agent = ?1, ip_address = ?2, last_seen_at = ?3 WHERE id = ?4",
+            user_agent,
+            ip_address,
+            last_seen_at,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Marks the session as revoked and removes it from the session store.
+    /// Returns the internal session id, if the session belonged to the user.
+    pub async fn revoke(&self, id: Uuid, user_id: Uuid) -> Result<Option<String>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let now = Utc::now();
+        let record = sqlx::query!(
+            "UPDATE user_sessions SET revoked_at = ?1 WHERE id = ?2 AND user_id = ?3 AND revoked_at IS NULL RETURNING session_id",
+            now,
+            id,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(record) = &record {
+            sqlx::query!("DELETE FROM sessions_table WHERE id = ?1", record.session_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(record.map(|record| record.session_id))
+    }
+
+    /// Revokes every session of the user except `keep_session_id`.
+    /// Returns the internal session ids that were revoked.
+    pub async fn revoke_all_except(
+        &self,
+        user_id: Uuid,
+        keep_session_id: &str,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let now = Utc::now();
+        let records = sqlx::query!(
+            "UPDATE user_sessions SET revoked_at = ?1 WHERE user_id = ?2 AND session_id != ?3 AND revoked_at IS NULL RETURNING session_id",
+            now,
+            user_id,
+            keep_session_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for record in &records {
+            sqlx::query!("DELETE FROM sessions_table WHERE id = ?1", record.session_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(records
+            .into_iter()
+            .map(|record| record.session_id)
+            .collect())
+    }
+
+    pub async fn delete_by_session_id(&self, session_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM user_sessions WHERE session_id = ?1", session_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
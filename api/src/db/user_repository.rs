@@ -82,6 +82,17 @@ impl UserRepository {
 }
 
 impl UserRepository {
+    /// Whether a user has the username, ignoring case.
+    pub async fn username_exists(&self, username: &str) -> Result<bool, sqlx::Error> {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM users WHERE username = ?1 COLLATE NOCASE) AS "exists: bool""#,
+            username
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(exists)
+    }
+
     pub async fn get_by_username(&self, username: String) -> Result<User, sqlx::Error> {
         let user = sqlx::query!(
             r#"SELECT id as "id: uuid::Uuid", username, password FROM users WHERE username = ?1"#,
@@ -0,0 +1,104 @@
+use crate::Pool;
+use crate::domain::webhook::Webhook;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct WebhookRepository {
+    pub pool: Pool,
+}
+
+impl WebhookRepository {
+    pub fn new(pool: Pool) -> Self {
+        WebhookRepository { pool }
+    }
+
+    pub async fn create(&self, webhook: Webhook) -> Result<Uuid, sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO group_webhooks (id, group_id, user_id, name, token_hash, prefix, created_by, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            webhook.id,
+            webhook.group_id,
+            webhook.user_id,
+            webhook.name,
+            webhook.token_hash,
+            webhook.prefix,
+            webhook.created_by,
+            webhook.created_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(webhook.id)
+    }
+
+    pub async fn get_by_hash(&self, token_hash: &str) -> Result<Option<Webhook>, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", name, token_hash, prefix, created_by as "created_by: uuid::Uuid", created_at as "created_at: chrono::DateTime<chrono::Utc>", last_used_at as "last_used_at: chrono::DateTime<chrono::Utc>" FROM group_webhooks WHERE token_hash = ?1"#,
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|record| Webhook {
+            id: record.id,
+            group_id: record.group_id,
+            user_id: record.user_id,
+            name: record.name,
+            token_hash: record.token_hash,
+            prefix: record.prefix,
+            created_by: record.created_by,
+            created_at: record.created_at,
+            last_used_at: record.last_used_at,
+        }))
+    }
+
+    pub async fn list_by_group(&self, group_id: Uuid) -> Result<Vec<Webhook>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", group_id as "group_id: uuid::Uuid", user_id as "user_id: uuid::Uuid", name, token_hash, prefix, created_by as "created_by: uuid::Uuid", created_at as "created_at: chrono::DateTime<chrono::Utc>", last_used_at as "last_used_at: chrono::DateTime<chrono::Utc>"
+            FROM group_webhooks
+            WHERE group_id = ?1
+            ORDER BY created_at DESC"#,
+            group_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| Webhook {
+                id: record.id,
+                group_id: record.group_id,
+                user_id: record.user_id,
+                name: record.name,
+                token_hash: record.token_hash,
+                prefix: record.prefix,
+                created_by: record.created_by,
+                created_at: record.created_at,
+                last_used_at: record.last_used_at,
+            })
+            .collect())
+    }
+
+    pub async fn touch(&self, id: Uuid, last_used_at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE group_webhooks SET last_used_at = ?1 WHERE id = ?2",
+            last_used_at,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes the webhook, returns false if it did not belong to the group.
+    /// Its user stays, so the messages it posted keep their sender.
+    pub async fn delete(&self, id: Uuid, group_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM group_webhooks WHERE id = ?1 AND group_id = ?2",
+            id,
+            group_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -1,4 +1,30 @@
+#[cfg(feature = "ssr")]
+pub mod api_token;
+#[cfg(feature = "ssr")]
+pub mod block;
+#[cfg(feature = "ssr")]
 pub mod group;
+#[cfg(feature = "ssr")]
 pub mod group_member;
+#[cfg(feature = "ssr")]
+pub mod identity;
+#[cfg(feature = "ssr")]
+pub mod link_preview;
+#[cfg(feature = "ssr")]
 pub mod message;
+pub mod message_content;
+#[cfg(feature = "ssr")]
+pub mod outgoing_webhook;
+#[cfg(feature = "ssr")]
+pub mod poll;
+#[cfg(feature = "ssr")]
+pub mod profile;
+#[cfg(feature = "ssr")]
+pub mod scheduled_message;
+#[cfg(feature = "ssr")]
+pub mod session;
+pub mod token_scope;
+#[cfg(feature = "ssr")]
 pub mod user;
+#[cfg(feature = "ssr")]
+pub mod webhook;
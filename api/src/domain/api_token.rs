@@ -0,0 +1,75 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::domain::token_scope::TokenScope;
+
+/// Prefix of every personal access token, makes leaked tokens easy to grep for.
+pub const TOKEN_PREFIX: &str = "lcp_";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_hash: String,
+    pub prefix: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl ApiToken {
+    /// Creates a new token and returns it together with its secret,
+    /// the secret is not stored and can only be shown once.
+    pub fn generate(
+        user_id: Uuid,
+        name: String,
+        scopes: Vec<TokenScope>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> (Self, String) {
+        let secret = format!("{TOKEN_PREFIX}{}", nanoid::nanoid!(40));
+        let token = Self {
+            id: Uuid::new_v4(),
+            user_id,
+            name,
+            token_hash: Self::hash(&secret),
+            prefix: secret[..TOKEN_PREFIX.len() + 6].to_string(),
+            scopes,
+            created_at: Utc::now(),
+            expires_at,
+            last_used_at: None,
+        };
+        (token, secret)
+    }
+
+    pub fn hash(secret: &str) -> String {
+        format!("{:x}", Sha256::digest(secret.as_bytes()))
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+
+    pub fn has_scope(&self, scope: TokenScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    pub(crate) fn scopes_to_string(scopes: &[TokenScope]) -> String {
+        scopes
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    pub(crate) fn scopes_from_string(scopes: &str) -> Vec<TokenScope> {
+        scopes
+            .split(',')
+            .filter_map(|scope| scope.parse().ok())
+            .collect()
+    }
+}
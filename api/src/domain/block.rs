@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// `blocker_id` no longer sees messages of `blocked_id`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserBlock {
+    pub blocker_id: Uuid,
+    pub blocked_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl UserBlock {
+    pub fn new(blocker_id: Uuid, blocked_id: Uuid) -> Self {
+        Self {
+            blocker_id,
+            blocked_id,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// A blocked user as listed to the blocker.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockedUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub blocked_at: DateTime<Utc>,
+}
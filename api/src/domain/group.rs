@@ -9,6 +9,9 @@ pub struct Group {
     pub avatar: Option<String>,
     pub created_at: DateTime<Utc>,
     pub join_code: String,
+    pub topic: Option<String>,
+    /// Seconds after which messages are deleted, `None` keeps them forever.
+    pub message_ttl_secs: Option<i64>,
 }
 
 impl Group {
@@ -19,6 +22,8 @@ impl Group {
             avatar: None,
             created_at: Utc::now(),
             join_code: nanoid::nanoid!(8),
+            topic: None,
+            message_ttl_secs: None,
         }
     }
     pub fn new_with_avatar(name: String, avatar: String) -> Self {
@@ -28,6 +33,8 @@ impl Group {
             avatar: Some(avatar),
             created_at: Utc::now(),
             join_code: nanoid::nanoid!(8),
+            topic: None,
+            message_ttl_secs: None,
         }
     }
 }
@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// An account at an external OpenID Connect provider linked to a user.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserIdentity {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_account: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl UserIdentity {
+    pub fn new(
+        user_id: Uuid,
+        provider: String,
+        subject: String,
+        email: Option<String>,
+        created_account: bool,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            provider,
+            subject,
+            email,
+            created_account,
+            created_at: Utc::now(),
+        }
+    }
+}
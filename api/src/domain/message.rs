@@ -7,6 +7,8 @@ pub struct Message {
     pub group_id: Uuid,
     pub user_id: Uuid,
     pub content: String,
+    /// Shown instead of the username of the sender, set for webhook messages.
+    pub sender_name: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -17,6 +19,7 @@ impl Message {
             group_id,
             user_id,
             content,
+            sender_name: None,
             created_at: Utc::now(),
         }
     }
@@ -0,0 +1,160 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of characters in a message.
+pub const MAX_MESSAGE_LENGTH: usize = 4000;
+
+/// The text of a message, trimmed and without control or invisible formatting
+/// characters, see [`MessageContent::normalize`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageContent(String);
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageContentError {
+    #[error("Message is empty")]
+    Empty,
+    #[error("Message is too long")]
+    TooLong,
+}
+
+impl MessageContent {
+    /// Number of characters the text counts as after cleaning it up.
+    pub fn length(text: &str) -> usize {
+        Self::normalize(text).chars().count()
+    }
+
+    /// Removes control characters other than line breaks and tabs, and format
+    /// characters like bidi overrides and zero width spaces, then trims the text.
+    /// Zero width joiners are kept between letters or emoji, which need them.
+    fn normalize(text: &str) -> String {
+        let chars = text.chars().collect::<Vec<_>>();
+        let joins = |i: usize| {
+            let visible = |c: &char| !c.is_ascii() && !c.is_whitespace() && Self::is_allowed(*c);
+            i.checked_sub(1)
+                .and_then(|before| chars.get(before))
+                .is_some_and(visible)
+                && chars.get(i + 1).is_some_and(visible)
+        };
+        chars
+            .iter()
+            .enumerate()
+            .filter(|(i, c)| Self::is_allowed(**c) || (JOINERS.contains(c) && joins(*i)))
+            .map(|(_, c)| *c)
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+
+    fn is_allowed(c: char) -> bool {
+        use unicode_properties::{GeneralCategory, UnicodeGeneralCategory as _};
+        c == '\n'
+            || c == '\t'
+            || !(c.is_control() || c.general_category() == GeneralCategory::Format)
+    }
+}
+
+/// Zero width non-joiner and joiner, format characters that change how
+/// their neighbours are shown.
+const JOINERS: [char; 2] = ['\u{200C}', '\u{200D}'];
+
+impl FromStr for MessageContent {
+    type Err = MessageContentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = Self::normalize(s);
+        if text.is_empty() {
+            Err(MessageContentError::Empty)
+        } else if text.chars().count() > MAX_MESSAGE_LENGTH {
+            Err(MessageContentError::TooLong)
+        } else {
+            Ok(MessageContent(text))
+        }
+    }
+}
+
+impl TryFrom<String> for MessageContent {
+    type Error = MessageContentError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl AsRef<str> for MessageContent {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<MessageContent> for String {
+    fn from(value: MessageContent) -> Self {
+        value.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(text: &str) -> Result<String, MessageContentError> {
+        text.parse::<MessageContent>().map(String::from)
+    }
+
+    #[test]
+    fn length_counts_the_stored_text() {
+        for text in [
+            "  hello \u{7} world \n",
+            "\u{202E}\u{7} padded by invisible characters \u{200B}\u{FEFF}",
+            " \u{0}\t tabs inside \t stay\t",
+        ] {
+            assert_eq!(
+                MessageContent::length(text),
+                content(text).unwrap().chars().count(),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn trims_after_removing_invisible_characters() {
+        assert_eq!(content("\u{200B} hi \u{7}").unwrap(), "hi");
+        assert_eq!(
+            content("\u{200B}\u{2060} \u{FEFF}"),
+            Err(MessageContentError::Empty)
+        );
+    }
+
+    #[test]
+    fn removes_bidi_overrides_and_zero_width_characters() {
+        assert_eq!(content("evil\u{202E}txt.exe").unwrap(), "eviltxt.exe");
+        assert_eq!(content("\u{2066}a\u{2069}\u{200E}b\u{200F}").unwrap(), "ab");
+        assert_eq!(content("ad\u{200B}min\u{00AD}").unwrap(), "admin");
+        assert_eq!(content("ad\u{200D}min").unwrap(), "admin");
+    }
+
+    #[test]
+    fn keeps_joiners_inside_emoji_and_words() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        assert_eq!(content(family).unwrap(), family);
+        let persian = "\u{0645}\u{06CC}\u{200C}\u{062E}\u{0648}\u{0627}\u{0647}\u{0645}";
+        assert_eq!(content(persian).unwrap(), persian);
+        assert_eq!(content("\u{200D}\u{1F468}\u{200D}").unwrap(), "\u{1F468}");
+    }
+
+    #[test]
+    fn limits_the_length() {
+        assert!(content(&"a".repeat(MAX_MESSAGE_LENGTH)).is_ok());
+        assert_eq!(
+            content(&"a".repeat(MAX_MESSAGE_LENGTH + 1)),
+            Err(MessageContentError::TooLong)
+        );
+        // Removed characters don't count
+        let padded = format!(
+            "{}{}",
+            "a".repeat(MAX_MESSAGE_LENGTH),
+            "\u{200B}".repeat(10)
+        );
+        assert!(content(&padded).is_ok());
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::server_fn::webhooks::WebhookEvent;
+
+/// Prefix of every signing secret of outgoing webhooks.
+pub const SECRET_PREFIX: &str = "whsec_";
+
+/// A url the events of a group are sent to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutgoingWebhook {
+    pub id: Uuid,
+    pub group_id: Uuid,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OutgoingWebhook {
+    /// Creates a new webhook with a random signing secret.
+    pub fn generate(
+        group_id: Uuid,
+        url: String,
+        events: Vec<WebhookEvent>,
+        created_by: Uuid,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            group_id,
+            url,
+            secret: format!("{SECRET_PREFIX}{}", nanoid::nanoid!(32)),
+            events,
+            created_by,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn subscribes_to(&self, event: WebhookEvent) -> bool {
+        self.events.contains(&event)
+    }
+
+    pub(crate) fn events_to_string(events: &[WebhookEvent]) -> String {
+        events
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    pub(crate) fn events_from_string(events: &str) -> Vec<WebhookEvent> {
+        events
+            .split(',')
+            .filter_map(|event| event.parse().ok())
+            .collect()
+    }
+}
+
+/// A payload queued for an outgoing webhook, `next_attempt_at` is unset once
+/// it was delivered or all attempts failed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: WebhookEvent,
+    pub payload: String,
+    pub attempts: i64,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl WebhookDelivery {
+    pub fn new(id: Uuid, webhook_id: Uuid, event: WebhookEvent, payload: String) -> Self {
+        let now = Utc::now();
+        Self {
+            id,
+            webhook_id,
+            event,
+            payload,
+            attempts: 0,
+            next_attempt_at: Some(now),
+            last_error: None,
+            created_at: now,
+            delivered_at: None,
+        }
+    }
+}
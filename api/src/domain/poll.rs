@@ -0,0 +1,59 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A poll attached to the message holding its question.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Poll {
+    pub message_id: Uuid,
+    pub multiple_choice: bool,
+    pub anonymous: bool,
+    pub closes_at: Option<DateTime<Utc>>,
+    /// In the order they are shown.
+    pub options: Vec<PollOption>,
+}
+
+impl Poll {
+    pub fn new(
+        message_id: Uuid,
+        options: Vec<String>,
+        multiple_choice: bool,
+        anonymous: bool,
+        closes_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            message_id,
+            multiple_choice,
+            anonymous,
+            closes_at,
+            options: options
+                .into_iter()
+                .enumerate()
+                .map(|(position, text)| PollOption {
+                    id: Uuid::new_v4(),
+                    position: position as i64,
+                    text,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_closed(&self, now: DateTime<Utc>) -> bool {
+        self.closes_at.is_some_and(|closes_at| closes_at <= now)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PollOption {
+    pub id: Uuid,
+    pub position: i64,
+    pub text: String,
+}
+
+/// A vote for one option, polls with multiple choice have several per user.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PollVote {
+    pub option_id: Uuid,
+    pub user_id: Uuid,
+    pub username: String,
+}
@@ -0,0 +1,65 @@
+use std::io::Cursor;
+
+use chrono::{DateTime, Utc};
+use image::{ImageFormat, ImageReader, Limits, imageops::FilterType};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Width and height of stored avatars.
+pub const AVATAR_SIZE: u32 = 256;
+/// Larger uploads are rejected before decoding them.
+const MAX_AVATAR_DIMENSION: u32 = 4096;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    pub user_id: Uuid,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_updated_at: Option<DateTime<Utc>>,
+}
+
+impl Profile {
+    /// Uploaded avatars are served by the server, everyone else gets a generated one.
+    pub fn avatar_url(&self) -> String {
+        match self.avatar_updated_at {
+            Some(updated_at) => format!(
+                "/avatars/{}?v={}",
+                self.user_id,
+                updated_at.timestamp()
+            ),
+            None => format!("https://robohash.org/{}", self.username),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Avatar {
+    pub user_id: Uuid,
+    pub content_type: String,
+    pub data: Vec<u8>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Avatar {
+    /// Decodes an uploaded image, crops it to a square and scales it to [`AVATAR_SIZE`].
+    pub fn from_upload(user_id: Uuid, upload: &[u8]) -> Result<Self, image::ImageError> {
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_AVATAR_DIMENSION);
+        limits.max_image_height = Some(MAX_AVATAR_DIMENSION);
+        let mut reader = ImageReader::new(Cursor::new(upload)).with_guessed_format()?;
+        reader.limits(limits);
+        let image = reader
+            .decode()?
+            .resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3);
+
+        let mut data = Vec::new();
+        image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?;
+        Ok(Self {
+            user_id,
+            content_type: "image/png".to_string(),
+            data,
+            updated_at: Utc::now(),
+        })
+    }
+}
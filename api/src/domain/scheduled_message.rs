@@ -0,0 +1,31 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A message waiting to be sent to a group at `send_at`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledMessage {
+    /// Also the id of the message once it is sent.
+    pub id: Uuid,
+    pub group_id: Uuid,
+    pub user_id: Uuid,
+    pub content: String,
+    pub send_at: DateTime<Utc>,
+    /// Set while a scheduler is sending the message.
+    pub claimed_until: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ScheduledMessage {
+    pub fn new(group_id: Uuid, user_id: Uuid, content: String, send_at: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            group_id,
+            user_id,
+            content,
+            send_at,
+            claimed_until: None,
+            created_at: Utc::now(),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserSession {
+    pub id: Uuid,
+    pub session_id: String,
+    pub user_id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl UserSession {
+    pub fn new(
+        session_id: String,
+        user_id: Uuid,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            session_id,
+            user_id,
+            user_agent,
+            ip_address,
+            created_at: now,
+            last_seen_at: now,
+            revoked_at: None,
+        }
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
+    /// A short human readable description like "Firefox on Linux".
+    pub fn device(&self) -> String {
+        let Some(user_agent) = self.user_agent.as_deref() else {
+            return "Unknown device".to_string();
+        };
+        // Order matters: Edge and Chrome both claim to be Safari, Edge also claims to be Chrome.
+        let browser = [
+            ("Edg/", "Edge"),
+            ("OPR/", "Opera"),
+            ("Firefox/", "Firefox"),
+            ("Chrome/", "Chrome"),
+            ("Safari/", "Safari"),
+            ("curl/", "curl"),
+        ]
+        .into_iter()
+        .find(|(needle, _)| user_agent.contains(needle))
+        .map(|(_, name)| name)
+        .unwrap_or("Unknown browser");
+        let os = [
+            ("Android", "Android"),
+            ("iPhone", "iOS"),
+            ("iPad", "iPadOS"),
+            ("Windows", "Windows"),
+            ("Mac OS X", "macOS"),
+            ("Linux", "Linux"),
+        ]
+        .into_iter()
+        .find(|(needle, _)| user_agent.contains(needle))
+        .map(|(_, name)| name);
+        match os {
+            Some(os) => format!("{browser} on {os}"),
+            None => browser.to_string(),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// What a personal access token is allowed to do.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumString,
+)]
+pub enum TokenScope {
+    #[serde(rename = "messages:read")]
+    #[strum(serialize = "messages:read")]
+    MessagesRead,
+    #[serde(rename = "messages:write")]
+    #[strum(serialize = "messages:write")]
+    MessagesWrite,
+    #[serde(rename = "groups:read")]
+    #[strum(serialize = "groups:read")]
+    GroupsRead,
+}
+
+impl TokenScope {
+    pub const ALL: [TokenScope; 3] = [
+        TokenScope::MessagesRead,
+        TokenScope::MessagesWrite,
+        TokenScope::GroupsRead,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            TokenScope::MessagesRead => "Read messages of your groups",
+            TokenScope::MessagesWrite => "Post messages to your groups",
+            TokenScope::GroupsRead => "List your groups",
+        }
+    }
+}
@@ -37,6 +37,10 @@ impl User {
             permissions,
         }
     }
+
+    pub fn is_admin(&self) -> bool {
+        self.permissions.contains("Admin") || self.permissions.contains("admin")
+    }
 }
 
 #[async_trait::async_trait]
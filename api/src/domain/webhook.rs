@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::api_token::ApiToken;
+
+/// Prefix of every webhook token, like [`crate::domain::api_token::TOKEN_PREFIX`].
+pub const WEBHOOK_TOKEN_PREFIX: &str = "lcw_";
+
+/// An incoming webhook posting into a group as its own user.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub group_id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_hash: String,
+    pub prefix: String,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl Webhook {
+    /// Creates a new webhook and returns it together with its token,
+    /// the token is not stored and can only be shown once.
+    pub fn generate(group_id: Uuid, user_id: Uuid, name: String, created_by: Uuid) -> (Self, String) {
+        let secret = format!("{WEBHOOK_TOKEN_PREFIX}{}", nanoid::nanoid!(40));
+        let webhook = Self {
+            id: Uuid::new_v4(),
+            group_id,
+            user_id,
+            name,
+            token_hash: ApiToken::hash(&secret),
+            prefix: secret[..WEBHOOK_TOKEN_PREFIX.len() + 6].to_string(),
+            created_by,
+            created_at: Utc::now(),
+            last_used_at: None,
+        };
+        (webhook, secret)
+    }
+}
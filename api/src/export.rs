@@ -0,0 +1,338 @@
+//! Archives of the full history of a group.
+//!
+//! Messages are rendered while they are read from the database and handed to
+//! the response through a small channel, so exporting a large group never holds
+//! more than a few chunks in memory.
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    domain::{group::Group, message::Message},
+};
+
+/// Version of the JSON export, raised on incompatible changes to its shape.
+pub const EXPORT_VERSION: u32 = 1;
+/// Rendered text is sent on once a chunk reaches this size.
+const CHUNK_SIZE: usize = 16 * 1024;
+/// Chunks rendered ahead of a slow client.
+const CHUNKS_AHEAD: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Html,
+    Markdown,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Html => "text/html; charset=utf-8",
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Html => "html",
+            ExportFormat::Markdown => "md",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("You need to be logged in")]
+    Unauthorized,
+    #[error("You are not a member of this group")]
+    NotAMember,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// A group as it appears at the top of the JSON export.
+#[derive(Serialize)]
+struct ExportedGroup<'a> {
+    id: Uuid,
+    name: &'a str,
+    topic: Option<&'a str>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct ExportedAuthor<'a> {
+    id: Uuid,
+    name: &'a str,
+}
+
+/// A message of the JSON export.
+#[derive(Serialize)]
+struct ExportedMessage<'a> {
+    id: Uuid,
+    author: ExportedAuthor<'a>,
+    sent_at: DateTime<Utc>,
+    text: &'a str,
+}
+
+/// The export of a group the user is a member of, and the name to save it under.
+pub async fn export(
+    state: &AppState,
+    user_id: Uuid,
+    group_id: Uuid,
+    format: ExportFormat,
+) -> Result<
+    (
+        String,
+        impl Stream<Item = Result<String, sqlx::Error>> + Send + 'static,
+    ),
+    ExportError,
+> {
+    if !state.group_repository.is_member(group_id, user_id).await? {
+        return Err(ExportError::NotAMember);
+    }
+    let group = state.group_repository.get_group_by_id(group_id).await?;
+    let file_name = format!("{}.{}", file_stem(&group.name), format.extension());
+
+    let (sender, receiver) = mpsc::channel(CHUNKS_AHEAD);
+    let state = state.clone();
+    tokio::spawn(async move {
+        if let Err(error) = render(&state, &group, format, &sender).await {
+            log::error!("Failed to export group {}: {error}", group.id);
+            // Cuts the response short, so the client doesn't keep a truncated file
+            let _ = sender.send(Err(error)).await;
+        }
+    });
+    let chunks = futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+    Ok((file_name, chunks))
+}
+
+/// Writes the export into `sender`, stops early once the client went away.
+async fn render(
+    state: &AppState,
+    group: &Group,
+    format: ExportFormat,
+    sender: &mpsc::Sender<Result<String, sqlx::Error>>,
+) -> Result<(), sqlx::Error> {
+    let exported_at = Utc::now();
+    let mut chunk = header(format, group, exported_at);
+    // Authors come with the messages, the stream holds its connection until it ends
+    let mut messages = state.message_repository.get_by_group(&group.id);
+    let mut first = true;
+    while let Some(message) = messages.next().await {
+        let (message, username) = message?;
+        let author = message.sender_name.as_deref().unwrap_or(&username);
+        render_message(format, &mut chunk, &message, author, first);
+        first = false;
+        if chunk.len() >= CHUNK_SIZE && sender.send(Ok(std::mem::take(&mut chunk))).await.is_err() {
+            return Ok(());
+        }
+    }
+    chunk.push_str(footer(format));
+    let _ = sender.send(Ok(chunk)).await;
+    Ok(())
+}
+
+fn header(format: ExportFormat, group: &Group, exported_at: DateTime<Utc>) -> String {
+    match format {
+        ExportFormat::Json => {
+            let group = ExportedGroup {
+                id: group.id,
+                name: &group.name,
+                topic: group.topic.as_deref(),
+                created_at: group.created_at,
+            };
+            format!(
+                r#"{{"version":{EXPORT_VERSION},"exported_at":{},"group":{},"messages":["#,
+                serde_json::json!(exported_at),
+                serde_json::to_string(&group).expect("group serializes"),
+            )
+        }
+        ExportFormat::Html => {
+            let name = escape_html(&group.name);
+            let topic = group
+                .topic
+                .as_deref()
+                .map(|topic| format!("<p class=\"topic\">{}</p>", escape_html(topic)))
+                .unwrap_or_default();
+            format!(
+                "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+                 <title>{name}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n\
+                 <h1>{name}</h1>\n{topic}\n<p class=\"exported\">Exported {}</p>\n<ol>\n",
+                format_time(exported_at)
+            )
+        }
+        ExportFormat::Markdown => {
+            let topic = group
+                .topic
+                .as_deref()
+                .map(|topic| format!("> {topic}\n\n"))
+                .unwrap_or_default();
+            format!(
+                "# {}\n\n{topic}_Exported {}_\n\n",
+                group.name,
+                format_time(exported_at)
+            )
+        }
+    }
+}
+
+fn render_message(
+    format: ExportFormat,
+    chunk: &mut String,
+    message: &Message,
+    author: &str,
+    first: bool,
+) {
+    match format {
+        ExportFormat::Json => {
+            if !first {
+                chunk.push(',');
+            }
+            let message = ExportedMessage {
+                id: message.id,
+                author: ExportedAuthor {
+                    id: message.user_id,
+                    name: author,
+                },
+                sent_at: message.created_at,
+                text: &message.content,
+            };
+            chunk.push_str(&serde_json::to_string(&message).expect("message serializes"));
+        }
+        ExportFormat::Html => {
+            chunk.push_str(&format!(
+                "<li><p class=\"meta\"><strong>{}</strong> <time datetime=\"{}\">{}</time></p><p class=\"text\">{}</p></li>\n",
+                escape_html(author),
+                message.created_at.to_rfc3339(),
+                format_time(message.created_at),
+                escape_html(&message.content),
+            ));
+        }
+        ExportFormat::Markdown => {
+            // Trailing double spaces keep the line breaks of the message
+            chunk.push_str(&format!(
+                "**{author}** · {}  \n{}\n\n",
+                format_time(message.created_at),
+                message.content.replace('\n', "  \n"),
+            ));
+        }
+    }
+}
+
+fn footer(format: ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Json => "]}",
+        ExportFormat::Html => "</ol>\n</body>\n</html>\n",
+        ExportFormat::Markdown => "",
+    }
+}
+
+const HTML_STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:48rem;margin:2rem auto;padding:0 1rem;color:#222}\
+.topic,.exported,time{color:#666}ol{list-style:none;padding:0}li{padding:0.5rem 0;border-bottom:1px solid #eee}\
+.meta,.text{margin:0}.text{white-space:pre-wrap;overflow-wrap:anywhere}time{font-size:0.85rem;margin-left:0.5rem}";
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The group name without characters that are awkward in file names.
+fn file_stem(name: &str) -> String {
+    let stem = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if stem.is_empty() {
+        "group".to_string()
+    } else {
+        stem
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, test_support};
+
+    async fn group_with_message(state: &AppState, member: Uuid) -> Uuid {
+        let group_id = state
+            .group_repository
+            .create_group(Group::new("Lunch & Learn".to_string()))
+            .await
+            .unwrap();
+        state
+            .group_repository
+            .add_member(group_id, member)
+            .await
+            .unwrap();
+        state
+            .message_repository
+            .create(Message::new(
+                group_id,
+                member,
+                "Pizza \"today\"?".to_string(),
+            ))
+            .await
+            .unwrap();
+        group_id
+    }
+
+    #[tokio::test]
+    async fn refuses_non_members() {
+        let state = test_support::state(Config::default()).await;
+        let alice = test_support::user(&state, "alice").await;
+        let eve = test_support::user(&state, "eve").await;
+        let group_id = group_with_message(&state, alice).await;
+
+        let result = export(&state, eve, group_id, ExportFormat::Json).await;
+        assert!(matches!(result, Err(ExportError::NotAMember)));
+    }
+
+    #[tokio::test]
+    async fn exports_json() {
+        let state = test_support::state(Config::default()).await;
+        let alice = test_support::user(&state, "alice").await;
+        let group_id = group_with_message(&state, alice).await;
+
+        let (file_name, chunks) = export(&state, alice, group_id, ExportFormat::Json)
+            .await
+            .unwrap();
+        assert_eq!(file_name, "Lunch___Learn.json");
+        let json = chunks.map(|chunk| chunk.unwrap()).collect::<String>().await;
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["version"], EXPORT_VERSION);
+        assert_eq!(json["group"]["name"], "Lunch & Learn");
+        assert_eq!(json["messages"][0]["author"]["name"], "alice");
+        assert_eq!(json["messages"][0]["text"], "Pizza \"today\"?");
+    }
+}
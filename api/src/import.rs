@@ -0,0 +1,354 @@
+//! Import of chat history exported from other chat services.
+//!
+//! Every source is parsed into [`ImportedChannel`]s first, which are then
+//! written in a single transaction by [`import`]. Channels become groups of the
+//! same name, and authors are matched to users with the same username or
+//! represented by placeholder users that can't log in. Message ids are derived
+//! from the ids in the export, so importing the same export twice adds nothing.
+use chrono::{DateTime, Utc};
+
+use crate::{
+    Pool,
+    db::ImportRepository,
+    domain::message_content::{MAX_MESSAGE_LENGTH, MessageContent, MessageContentError},
+};
+
+pub mod discord;
+pub mod slack;
+
+/// The service an export comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportSource {
+    Slack,
+    Discord,
+}
+
+impl ImportSource {
+    /// Prefix of placeholder usernames and of the keys message ids are derived from.
+    pub fn name(self) -> &'static str {
+        match self {
+            ImportSource::Slack => "slack",
+            ImportSource::Discord => "discord",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportedChannel {
+    pub name: String,
+    pub topic: Option<String>,
+    pub messages: Vec<ImportedMessage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportedMessage {
+    /// Id of the message in the export, unique within its channel.
+    pub source_id: String,
+    pub author: ImportedAuthor,
+    pub text: String,
+    pub sent_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ImportedAuthor {
+    /// Id of the author in the export.
+    pub source_id: String,
+    /// Matched against the usernames of existing users.
+    pub username: String,
+}
+
+/// What an import changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub groups_created: usize,
+    pub groups_reused: usize,
+    pub users_matched: usize,
+    pub placeholders_created: usize,
+    pub messages_imported: usize,
+    /// Messages without text, of channels without a name, or imported before.
+    pub messages_skipped: usize,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("Failed to read the export: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid zip archive: {0}")]
+    InvalidArchive(#[from] zip::result::ZipError),
+    #[error("{0} is too large")]
+    FileTooLarge(String),
+    #[error("Missing {0} in the export")]
+    MissingFile(String),
+    #[error("Invalid JSON in {file}: {source}")]
+    InvalidJson {
+        file: String,
+        source: serde_json::Error,
+    },
+    #[error("Invalid timestamp {0}")]
+    InvalidTimestamp(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Longest group name and topic, as for groups created in the app.
+const MAX_GROUP_NAME_LENGTH: usize = 64;
+const MAX_TOPIC_LENGTH: usize = 256;
+
+/// Writes the channels to the database, all or nothing.
+pub async fn import(
+    pool: &Pool,
+    source: ImportSource,
+    channels: Vec<ImportedChannel>,
+) -> Result<ImportReport, ImportError> {
+    let mut empty = 0;
+    let channels = channels
+        .into_iter()
+        .filter_map(|channel| {
+            let name = truncate(channel.name.trim(), MAX_GROUP_NAME_LENGTH);
+            if name.is_empty() {
+                empty += channel.messages.len();
+                return None;
+            }
+            let messages = channel
+                .messages
+                .into_iter()
+                .filter_map(|message| match message_text(&message.text) {
+                    Some(text) => Some(ImportedMessage { text, ..message }),
+                    None => {
+                        empty += 1;
+                        None
+                    }
+                })
+                .collect();
+            Some(ImportedChannel {
+                name,
+                topic: channel
+                    .topic
+                    .map(|topic| truncate(topic.trim(), MAX_TOPIC_LENGTH))
+                    .filter(|topic| !topic.is_empty()),
+                messages,
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut report = ImportRepository::new(pool.clone())
+        .import(source, &channels)
+        .await?;
+    report.messages_skipped += empty;
+    Ok(report)
+}
+
+fn truncate(text: &str, length: usize) -> String {
+    text.chars().take(length).collect()
+}
+
+/// The text as it would be accepted from a user, cut to the maximum length,
+/// or `None` if nothing is left of it.
+fn message_text(text: &str) -> Option<String> {
+    match text.parse::<MessageContent>() {
+        Ok(content) => Some(content.into()),
+        Err(MessageContentError::Empty) => None,
+        Err(MessageContentError::TooLong) => truncate(text.trim(), MAX_MESSAGE_LENGTH)
+            .parse::<MessageContent>()
+            .ok()
+            .map(Into::into),
+    }
+}
+
+fn parse_json<T: serde::de::DeserializeOwned>(file: &str, data: &[u8]) -> Result<T, ImportError> {
+    serde_json::from_slice(data).map_err(|source| ImportError::InvalidJson {
+        file: file.to_string(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write as _};
+
+    use chrono::TimeZone as _;
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    use super::*;
+    use crate::{AppState, config::Config, test_support};
+
+    const SLACK_FILES: &[(&str, &str)] = &[
+        ("users.json", include_str!("../testdata/slack/users.json")),
+        (
+            "channels.json",
+            include_str!("../testdata/slack/channels.json"),
+        ),
+        (
+            "general/2024-03-01.json",
+            include_str!("../testdata/slack/general/2024-03-01.json"),
+        ),
+    ];
+    const DISCORD_EXPORT: &str = include_str!("../testdata/discord.json");
+
+    fn slack_export() -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_directory("general/", SimpleFileOptions::default())
+            .unwrap();
+        for (name, content) in SLACK_FILES {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    async fn messages_of(state: &AppState, username: &str) -> Vec<(String, DateTime<Utc>)> {
+        let user = state
+            .user_repository
+            .get_by_username(username.to_string())
+            .await
+            .unwrap();
+        state
+            .message_repository
+            .get_by_user(user.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|message| (message.content, message.created_at))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn imports_a_slack_export() {
+        let state = test_support::state(Config::default()).await;
+        let alice = test_support::user(&state, "alice").await;
+
+        let channels = slack::parse(slack_export()).unwrap();
+        let report = import(&state.pool, ImportSource::Slack, channels)
+            .await
+            .unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                groups_created: 1,
+                users_matched: 1,
+                placeholders_created: 1,
+                messages_imported: 2,
+                ..ImportReport::default()
+            }
+        );
+        let group = state
+            .group_repository
+            .get_group_by_name("general".to_string())
+            .await
+            .unwrap();
+        assert_eq!(group.topic.as_deref(), Some("Company wide announcements"));
+
+        // Messages of an author with an account go to it, the others to a placeholder
+        assert_eq!(
+            messages_of(&state, "alice").await,
+            [(
+                "Hi @ghost, see the docs (https://example.com)".to_string(),
+                Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap()
+                    + chrono::Duration::microseconds(200)
+            )]
+        );
+        assert_eq!(
+            messages_of(&state, "slack-ghost").await,
+            [(
+                "Thanks & bye".to_string(),
+                Utc.with_ymd_and_hms(2024, 3, 1, 11, 0, 0).unwrap()
+                    + chrono::Duration::microseconds(123_456)
+            )]
+        );
+        assert!(
+            state
+                .group_repository
+                .is_member(group.id, alice)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn imports_a_discord_export() {
+        let state = test_support::state(Config::default()).await;
+
+        let channel = discord::parse("raids.json", DISCORD_EXPORT.as_bytes()).unwrap();
+        let report = import(&state.pool, ImportSource::Discord, vec![channel])
+            .await
+            .unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                groups_created: 1,
+                placeholders_created: 2,
+                messages_imported: 2,
+                ..ImportReport::default()
+            }
+        );
+        assert_eq!(
+            messages_of(&state, "discord-bob").await,
+            [(
+                "Who's in?".to_string(),
+                Utc.with_ymd_and_hms(2024, 5, 1, 16, 30, 0).unwrap()
+                    + chrono::Duration::milliseconds(500)
+            )]
+        );
+        assert_eq!(
+            messages_of(&state, "discord-carol").await,
+            [(
+                "https://cdn.discordapp.com/attachments/10/20/map.png".to_string(),
+                Utc.with_ymd_and_hms(2024, 5, 1, 18, 31, 0).unwrap()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn importing_twice_adds_nothing() {
+        let state = test_support::state(Config::default()).await;
+        test_support::user(&state, "alice").await;
+
+        let mut reports = Vec::new();
+        for _ in 0..2 {
+            let channels = slack::parse(slack_export()).unwrap();
+            reports.push(
+                import(&state.pool, ImportSource::Slack, channels)
+                    .await
+                    .unwrap(),
+            );
+        }
+        assert_eq!(
+            reports[1],
+            ImportReport {
+                groups_reused: 1,
+                users_matched: 1,
+                messages_skipped: 2,
+                ..ImportReport::default()
+            }
+        );
+        let channel = discord::parse("raids.json", DISCORD_EXPORT.as_bytes()).unwrap();
+        import(&state.pool, ImportSource::Discord, vec![channel.clone()])
+            .await
+            .unwrap();
+        let report = import(&state.pool, ImportSource::Discord, vec![channel])
+            .await
+            .unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                groups_reused: 1,
+                messages_skipped: 2,
+                ..ImportReport::default()
+            }
+        );
+        assert_eq!(messages_of(&state, "alice").await.len(), 1);
+        assert_eq!(messages_of(&state, "slack-ghost").await.len(), 1);
+        assert_eq!(messages_of(&state, "discord-bob").await.len(), 1);
+    }
+
+    #[test]
+    fn refuses_what_is_not_a_zip_archive() {
+        let result = slack::parse(DISCORD_EXPORT.as_bytes().to_vec());
+        assert!(
+            matches!(result, Err(ImportError::InvalidArchive(_))),
+            "{result:?}"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Discord channels exported as JSON by DiscordChatExporter, one file per channel.
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::{ImportError, ImportedAuthor, ImportedChannel, ImportedMessage, parse_json};
+
+/// Message types written by people, the others announce joins, pins and the like.
+const KEPT_TYPES: &[&str] = &["Default", "Reply"];
+
+#[derive(Deserialize)]
+struct Export {
+    channel: Channel,
+    messages: Vec<Message>,
+}
+
+#[derive(Deserialize)]
+struct Channel {
+    name: String,
+    #[serde(default)]
+    topic: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    timestamp: DateTime<Utc>,
+    #[serde(default)]
+    content: String,
+    author: Author,
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+}
+
+#[derive(Deserialize)]
+struct Author {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Attachment {
+    url: String,
+}
+
+/// Reads the channel of a Discord export, `file` names it in errors.
+pub fn parse(file: &str, data: &[u8]) -> Result<ImportedChannel, ImportError> {
+    let export: Export = parse_json(file, data)?;
+    let messages = export
+        .messages
+        .into_iter()
+        .filter(|message| KEPT_TYPES.contains(&message.kind.as_str()))
+        .map(|message| {
+            let mut text = message.content;
+            // Attachments stay on Discord's servers, link to them
+            for attachment in message.attachments {
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(&attachment.url);
+            }
+            ImportedMessage {
+                source_id: message.id,
+                author: ImportedAuthor {
+                    source_id: message.author.id,
+                    username: message.author.name,
+                },
+                text,
+                sent_at: message.timestamp,
+            }
+        })
+        .collect();
+    Ok(ImportedChannel {
+        name: export.channel.name,
+        topic: export.channel.topic.filter(|topic| !topic.is_empty()),
+        messages,
+    })
+}
@@ -0,0 +1,248 @@
+//! Slack workspace exports: a zip archive with `users.json`, `channels.json`
+//! and one file of messages per channel and day, `<channel>/<yyyy-mm-dd>.json`.
+//!
+//! Private channels are listed in `groups.json` when the export includes them.
+//! Direct messages aren't imported, they have no group to go to.
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read as _},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use zip::{ZipArchive, result::ZipError};
+
+use super::{ImportError, ImportedAuthor, ImportedChannel, ImportedMessage, parse_json};
+
+/// Files larger than this are refused, a chat export has no business holding them.
+const MAX_FILE_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Message subtypes that record changes to the channel rather than something someone said.
+const SKIPPED_SUBTYPES: &[&str] = &[
+    "channel_join",
+    "channel_leave",
+    "channel_topic",
+    "channel_purpose",
+    "channel_name",
+    "channel_archive",
+    "channel_unarchive",
+    "group_join",
+    "group_leave",
+    "group_topic",
+    "group_purpose",
+    "group_name",
+    "pinned_item",
+    "unpinned_item",
+];
+
+#[derive(Deserialize)]
+struct User {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Channel {
+    name: String,
+    #[serde(default)]
+    topic: Option<Topic>,
+}
+
+#[derive(Deserialize)]
+struct Topic {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    #[serde(default)]
+    subtype: Option<String>,
+    #[serde(default)]
+    user: Option<String>,
+    /// Name of a bot or integration that posted without a user.
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    bot_id: Option<String>,
+    #[serde(default)]
+    text: String,
+    ts: String,
+    #[serde(default)]
+    files: Vec<File>,
+}
+
+#[derive(Deserialize)]
+struct File {
+    #[serde(default)]
+    url_private: Option<String>,
+}
+
+/// Reads the channels of a Slack export.
+pub fn parse(data: Vec<u8>) -> Result<Vec<ImportedChannel>, ImportError> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let users: Vec<User> = match read_file(&mut archive, "users.json")? {
+        Some(data) => parse_json("users.json", &data)?,
+        None => Vec::new(),
+    };
+    let usernames = users
+        .into_iter()
+        .map(|user| (user.id, user.name))
+        .collect::<HashMap<_, _>>();
+
+    let mut channels: Vec<Channel> = parse_json(
+        "channels.json",
+        &read_file(&mut archive, "channels.json")?
+            .ok_or_else(|| ImportError::MissingFile("channels.json".to_string()))?,
+    )?;
+    if let Some(data) = read_file(&mut archive, "groups.json")? {
+        channels.extend(parse_json::<Vec<Channel>>("groups.json", &data)?);
+    }
+    let mut imported = Vec::with_capacity(channels.len());
+    for channel in channels {
+        let prefix = format!("{}/", channel.name);
+        let mut days = archive
+            .file_names()
+            .filter(|name| name.starts_with(&prefix) && name.ends_with(".json"))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        days.sort();
+
+        let mut messages = Vec::new();
+        for day in days {
+            let data = read_file(&mut archive, &day)?.unwrap_or_default();
+            for message in parse_json::<Vec<Message>>(&day, &data)? {
+                if message
+                    .subtype
+                    .as_deref()
+                    .is_some_and(|subtype| SKIPPED_SUBTYPES.contains(&subtype))
+                {
+                    continue;
+                }
+                messages.push(convert_message(message, &usernames)?);
+            }
+        }
+        imported.push(ImportedChannel {
+            name: channel.name,
+            topic: channel
+                .topic
+                .map(|topic| topic.value)
+                .filter(|topic| !topic.is_empty()),
+            messages,
+        });
+    }
+    Ok(imported)
+}
+
+/// The content of the file, `None` if there is no such file in the archive.
+fn read_file(
+    archive: &mut ZipArchive<Cursor<Vec<u8>>>,
+    name: &str,
+) -> Result<Option<Vec<u8>>, ImportError> {
+    let file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    // The sizes in the archive are whatever its author wrote there, trust only what's read
+    let mut content = Vec::new();
+    file.take(MAX_FILE_SIZE + 1).read_to_end(&mut content)?;
+    if content.len() as u64 > MAX_FILE_SIZE {
+        return Err(ImportError::FileTooLarge(name.to_string()));
+    }
+    Ok(Some(content))
+}
+
+fn convert_message(
+    message: Message,
+    usernames: &HashMap<String, String>,
+) -> Result<ImportedMessage, ImportError> {
+    let author_id = message
+        .user
+        .or(message.bot_id)
+        .unwrap_or_else(|| "unknown".to_string());
+    let author_name = usernames
+        .get(&author_id)
+        .cloned()
+        .or(message.username)
+        .unwrap_or_else(|| author_id.clone());
+    let mut text = convert_text(&message.text, usernames);
+    for url in message.files.into_iter().filter_map(|file| file.url_private) {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&url);
+    }
+    Ok(ImportedMessage {
+        sent_at: parse_ts(&message.ts)?,
+        source_id: message.ts,
+        author: ImportedAuthor {
+            source_id: author_id,
+            username: author_name,
+        },
+        text,
+    })
+}
+
+/// Slack timestamps are seconds since the epoch with microseconds as fraction.
+fn parse_ts(ts: &str) -> Result<DateTime<Utc>, ImportError> {
+    let invalid = || ImportError::InvalidTimestamp(ts.to_string());
+    let (secs, micros) = ts.split_once('.').unwrap_or((ts, "0"));
+    let secs = secs.parse::<i64>().map_err(|_| invalid())?;
+    if !micros.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let micros = format!("{micros:0<6}")[..6]
+        .parse::<u32>()
+        .map_err(|_| invalid())?;
+    DateTime::from_timestamp(secs, micros * 1000).ok_or_else(invalid)
+}
+
+/// Turns Slack's markup of mentions and links into plain text.
+fn convert_text(text: &str, usernames: &HashMap<String, String>) -> String {
+    let mut converted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        converted.push_str(&rest[..start]);
+        let inner = &rest[start + 1..start + end];
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(label)),
+            None => (inner, None),
+        };
+        if let Some(user_id) = target.strip_prefix('@') {
+            converted.push('@');
+            converted.push_str(
+                label
+                    .or(usernames.get(user_id).map(String::as_str))
+                    .unwrap_or(user_id),
+            );
+        } else if let Some(channel_id) = target.strip_prefix('#') {
+            converted.push('#');
+            converted.push_str(label.unwrap_or(channel_id));
+        } else if let Some(special) = target.strip_prefix('!') {
+            match label {
+                Some(label) => converted.push_str(label),
+                None => {
+                    converted.push('@');
+                    converted.push_str(special);
+                }
+            }
+        } else {
+            let url = target.strip_prefix("mailto:").unwrap_or(target);
+            match label {
+                Some(label) if label != url => {
+                    converted.push_str(&format!("{label} ({url})"));
+                }
+                _ => converted.push_str(url),
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    converted.push_str(rest);
+    converted
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,88 @@
+//! Deletion of expired messages in groups with disappearing messages.
+//!
+//! Open chats remove deleted messages live through
+//! [`ChatChannelMessages::Deleted`], and the group lists of the members get the
+//! new last message so expired text doesn't linger in the previews.
+use std::time::Duration;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{
+    AppState, backplane::BackplaneEvent, server_fn::chat::ChatChannelMessages,
+    user_events::UserEvent,
+};
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+/// Messages deleted per statement, so a large backlog doesn't lock the database for long.
+const BATCH_SIZE: i64 = 100;
+
+/// Deletes expired messages in the background for as long as the server runs.
+pub fn spawn(state: AppState) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(SWEEP_INTERVAL).await;
+            let groups = match state.group_repository.list_message_ttls().await {
+                Ok(groups) => groups,
+                Err(error) => {
+                    log::error!("Failed to load groups with disappearing messages: {error}");
+                    continue;
+                }
+            };
+            for (group_id, ttl_secs) in groups {
+                if let Err(error) = sweep(&state, group_id, ttl_secs).await {
+                    log::error!("Failed to delete expired messages of {group_id}: {error}");
+                }
+            }
+        }
+    });
+}
+
+async fn sweep(state: &AppState, group_id: Uuid, ttl_secs: i64) -> Result<(), sqlx::Error> {
+    let before = Utc::now() - chrono::Duration::seconds(ttl_secs);
+    let mut deleted_any = false;
+    loop {
+        let deleted = state
+            .message_repository
+            .delete_sent_before(group_id, before, BATCH_SIZE)
+            .await?;
+        for message_id in &deleted {
+            state.backplane.publish(BackplaneEvent::Chat {
+                group_id: group_id.to_string(),
+                message: ChatChannelMessages::Deleted {
+                    message_id: message_id.to_string(),
+                },
+            });
+        }
+        deleted_any |= !deleted.is_empty();
+        if deleted.len() < BATCH_SIZE as usize {
+            break;
+        }
+    }
+    if deleted_any {
+        update_previews(state, group_id).await?;
+    }
+    Ok(())
+}
+
+/// Shows the last message that is left in the group lists of the members.
+async fn update_previews(state: &AppState, group_id: Uuid) -> Result<(), sqlx::Error> {
+    let (text, time) = match state.message_repository.get_last_by_group(group_id).await? {
+        Some(message) => (message.content, message.created_at),
+        None => {
+            let group = state.group_repository.get_group_by_id(group_id).await?;
+            ("No messages yet".to_string(), group.created_at)
+        }
+    };
+    for member in state.group_repository.list_members(group_id).await? {
+        state.user_events.send(
+            member.user_id,
+            UserEvent::MessagePreview {
+                group_id: group_id.to_string(),
+                text: text.clone(),
+                time,
+            },
+        );
+    }
+    Ok(())
+}
@@ -1,11 +1,44 @@
 #[cfg(feature = "ssr")]
 mod auth;
 #[cfg(feature = "ssr")]
-mod db;
+pub mod backplane;
+#[cfg(feature = "ssr")]
+pub mod commands;
 #[cfg(feature = "ssr")]
+pub mod config;
+#[cfg(feature = "ssr")]
+mod db;
 mod domain;
+#[cfg(feature = "ssr")]
+pub mod export;
+#[cfg(feature = "ssr")]
+pub mod import;
+#[cfg(feature = "ssr")]
+pub mod janitor;
+#[cfg(feature = "ssr")]
+pub mod link_previews;
+#[cfg(feature = "ssr")]
+pub mod oidc;
+#[cfg(feature = "ssr")]
+pub mod outgoing_webhooks;
+#[cfg(feature = "ssr")]
+pub mod rate_limit;
+#[cfg(feature = "ssr")]
+pub mod scheduler;
+#[cfg(all(test, feature = "ssr"))]
+mod test_support;
 
+pub mod presence;
 pub mod server_fn;
+pub mod user_events;
+#[cfg(feature = "ssr")]
+pub mod webhooks;
+pub mod ws;
+
+#[cfg(feature = "ssr")]
+pub use auth::{AuthSession, TokenAuth};
+#[cfg(feature = "ssr")]
+pub use domain::session::UserSession;
 
 #[cfg(feature = "ssr")]
 type Pool = sqlx::Pool<sqlx::Sqlite>;
@@ -31,6 +64,7 @@ use thiserror::Error;
 #[cfg(feature = "ssr")]
 #[derive(Clone, FromRef)]
 pub struct AppState {
+    pub config: std::sync::Arc<config::Config>,
     pub pool: Pool,
     pub routes: Option<Vec<AxumRouteListing>>,
     pub options: LeptosOptions,
@@ -38,54 +72,126 @@ pub struct AppState {
     pub user_repository: db::UserRepository,
     pub group_repository: db::GroupRepository,
     pub message_repository: db::MessageRepository,
+    pub session_repository: db::SessionRepository,
+    pub api_token_repository: db::ApiTokenRepository,
+    pub webhook_repository: db::WebhookRepository,
+    pub outgoing_webhook_repository: db::OutgoingWebhookRepository,
+    pub poll_repository: db::PollRepository,
+    pub identity_repository: db::IdentityRepository,
+    pub profile_repository: db::ProfileRepository,
+    pub scheduled_message_repository: db::ScheduledMessageRepository,
+    pub block_repository: db::BlockRepository,
+    pub link_preview_repository: db::LinkPreviewRepository,
+    pub oidc: oidc::Oidc,
+    pub ws_connections: ws::WsConnections,
+    pub presence: presence::PresenceService,
+    pub user_events: user_events::UserEventService,
+    pub backplane: std::sync::Arc<dyn backplane::Backplane>,
+    pub message_limiter: rate_limit::RateLimiter,
+    pub commands: std::sync::Arc<commands::CommandRegistry>,
 }
 #[cfg(feature = "ssr")]
 impl AppState {
     pub async fn new(
-        database_url: &str,
+        config: std::sync::Arc<config::Config>,
         options: LeptosOptions,
         routes: Option<Vec<AxumRouteListing>>,
-    ) -> Self {
-        use sqlx::{Sqlite, migrate::MigrateDatabase as _};
+    ) -> Result<Self, StartupError> {
+        let pool = connect(&config.database).await?;
+        let oidc = oidc::Oidc::from_config(&config, format!("http://{}", options.site_addr));
+        let server_signals = WsSignals::new();
+        let backplane = backplane::from_config(&config.backplane);
+        let message_limiter = rate_limit::RateLimiter::new(&config.rate_limit);
 
-        if !Sqlite::database_exists(&database_url)
-            .await
-            .unwrap_or(false)
-        {
-            use sqlx::migrate::MigrateDatabase as _;
-
-            log::info!("Creating database {}", database_url);
-            match Sqlite::create_database(&database_url).await {
-                Ok(_) => log::info!("Create db success"),
-                Err(error) => panic!("error: {}", error),
-            }
-        } else {
-            log::info!("Database already exists");
-        }
-        let pool = Pool::connect(database_url).await.unwrap();
-
-        Self {
+        let state = Self {
+            config,
             pool: pool.clone(),
             routes,
             options,
-            server_signals: WsSignals::new(),
+            presence: presence::PresenceService::new(server_signals.clone(), backplane.clone()),
+            user_events: user_events::UserEventService::new(
+                server_signals.clone(),
+                backplane.clone(),
+            ),
+            backplane,
+            message_limiter,
+            commands: std::sync::Arc::default(),
+            server_signals,
             user_repository: db::UserRepository::new(pool.clone()),
             group_repository: db::GroupRepository::new(pool.clone()),
             message_repository: db::MessageRepository::new(pool.clone()),
+            session_repository: db::SessionRepository::new(pool.clone()),
+            api_token_repository: db::ApiTokenRepository::new(pool.clone()),
+            webhook_repository: db::WebhookRepository::new(pool.clone()),
+            outgoing_webhook_repository: db::OutgoingWebhookRepository::new(pool.clone()),
+            poll_repository: db::PollRepository::new(pool.clone()),
+            identity_repository: db::IdentityRepository::new(pool.clone()),
+            profile_repository: db::ProfileRepository::new(pool.clone()),
+            scheduled_message_repository: db::ScheduledMessageRepository::new(pool.clone()),
+            block_repository: db::BlockRepository::new(pool.clone()),
+            link_preview_repository: db::LinkPreviewRepository::new(pool.clone()),
+            oidc,
+            ws_connections: ws::WsConnections::new(),
+        };
+        Ok(state)
+    }
+}
+
+/// Why the server could not be started.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Error)]
+pub enum StartupError {
+    #[error("database {0} does not exist and `database.create_if_missing` is off")]
+    MissingDatabase(String),
+    #[error("failed to create database {url}: {source}")]
+    CreateDatabase { url: String, source: sqlx::Error },
+    #[error("failed to connect to database {url}: {source}")]
+    Connect { url: String, source: sqlx::Error },
+    #[error("failed to set up the session store: {0}")]
+    SessionStore(#[from] axum_session::SessionError),
+}
+
+/// Opens the database, creating it first if it is missing and the config allows it.
+#[cfg(feature = "ssr")]
+pub async fn connect(config: &config::DatabaseConfig) -> Result<sqlx::SqlitePool, StartupError> {
+    use sqlx::{Sqlite, migrate::MigrateDatabase as _, sqlite::SqlitePoolOptions};
+
+    let database_url = config.url.as_str();
+    if !Sqlite::database_exists(database_url)
+        .await
+        .unwrap_or(false)
+    {
+        if !config.create_if_missing {
+            return Err(StartupError::MissingDatabase(database_url.to_string()));
         }
+        log::info!("Creating database {}", database_url);
+        Sqlite::create_database(database_url)
+            .await
+            .map_err(|source| StartupError::CreateDatabase {
+                url: database_url.to_string(),
+                source,
+            })?;
+    } else {
+        log::info!("Database already exists");
     }
+    SqlitePoolOptions::new()
+        .max_connections(config.max_connections)
+        .connect(database_url)
+        .await
+        .map_err(|source| StartupError::Connect {
+            url: database_url.to_string(),
+            source,
+        })
 }
 
 #[server]
 pub async fn get_pow() -> Result<String, ServerFnError> {
+    use leptos::prelude::use_context;
     use leptos_captcha::spow::pow::Pow;
-    const DEV_MODE: bool = true;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let pow = &state.config.pow;
 
-    if DEV_MODE {
-        Ok(Pow::with_difficulty(10, 10)?.to_string())
-    } else {
-        Ok(Pow::new(20)?.to_string())
-    }
+    Ok(Pow::with_difficulty(pow.difficulty, pow.valid_seconds)?.to_string())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Error)]
@@ -131,28 +237,32 @@ impl FromServerFnError for AuthError {
 #[cfg(feature = "ssr")]
 pub async fn get_session_store(
     pool: Pool,
-) -> axum_session::SessionStore<axum_session_sqlx::SessionSqlitePool> {
+    config: &config::SessionConfig,
+) -> Result<axum_session::SessionStore<axum_session_sqlx::SessionSqlitePool>, StartupError> {
     use chrono::Duration;
 
+    let lifetime = Duration::days(config.lifetime_days);
     let session_config = axum_session::SessionConfig::default()
-        .with_lifetime(Duration::days(365)) // short session
-        .with_max_lifetime(Duration::days(365)) // max in DB
-        .with_max_age(Some(Duration::days(365)))
+        .with_lifetime(lifetime) // short session
+        .with_max_lifetime(lifetime) // max in DB
+        .with_max_age(Some(lifetime))
         .with_always_save(false)
         .with_table_name("sessions_table");
 
     // create SessionStore and initiate the database tables
 
-    axum_session::SessionStore::<axum_session_sqlx::SessionSqlitePool>::new(
-        Some(pool.clone().into()),
-        session_config,
+    Ok(
+        axum_session::SessionStore::<axum_session_sqlx::SessionSqlitePool>::new(
+            Some(pool.clone().into()),
+            session_config,
+        )
+        .await?,
     )
-    .await
-    .unwrap()
 }
 #[cfg(feature = "ssr")]
 pub async fn get_auth_session(
     pool: Pool,
+    config: &config::SessionConfig,
 ) -> axum_session_auth::AuthSessionLayer<
     crate::domain::user::User,
     uuid::Uuid,
@@ -162,7 +272,8 @@ pub async fn get_auth_session(
     use chrono::Duration;
 
     let auth_config =
-        axum_session_auth::AuthConfig::<uuid::Uuid>::default().with_max_age(Duration::hours(6));
+        axum_session_auth::AuthConfig::<uuid::Uuid>::default()
+            .with_max_age(Duration::hours(config.auth_max_age_hours));
     axum_session_auth::AuthSessionLayer::<
         crate::domain::user::User,
         uuid::Uuid,
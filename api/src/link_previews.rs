@@ -0,0 +1,712 @@
+//! Previews of pages linked in messages.
+//!
+//! The first link of a message is fetched in the background once the message
+//! was sent, and the OpenGraph or Twitter card metadata of the page is pushed to
+//! the group as [`ChatChannelMessages::LinkPreview`]. Previews are cached by url,
+//! so a popular link is fetched at most once per `cache_hours`.
+//!
+//! Links make the server send requests on behalf of any member, so only http(s)
+//! is spoken, every address a host resolves to must be public unless the host
+//! is allow-listed, and the request goes to exactly the addresses checked.
+//! Redirects are followed by hand and checked the same way.
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use chrono::Utc;
+use reqwest::{Response, Url, header, redirect};
+
+use crate::{
+    AppState,
+    backplane::BackplaneEvent,
+    config::LinkPreviewConfig,
+    domain::{link_preview::LinkPreview, message::Message},
+    server_fn::chat::{self, ChatChannelMessages},
+};
+
+const MAX_REDIRECTS: usize = 3;
+const MAX_URL_LENGTH: usize = 2048;
+const MAX_TITLE_LENGTH: usize = 200;
+const MAX_DESCRIPTION_LENGTH: usize = 300;
+const MAX_SITE_NAME_LENGTH: usize = 100;
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; leptos-chat link preview)";
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum FetchError {
+    #[error("only http and https links are fetched")]
+    UnsupportedUrl,
+    #[error("{0} is not a public address")]
+    PrivateAddress(IpAddr),
+    #[error("failed to resolve the host: {0}")]
+    Resolve(std::io::Error),
+    #[error("too many redirects")]
+    TooManyRedirects,
+    #[error("timed out")]
+    Timeout,
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+}
+
+/// What a page says about itself.
+#[derive(Debug, Default)]
+struct Metadata {
+    title: Option<String>,
+    description: Option<String>,
+    image_url: Option<String>,
+    site_name: Option<String>,
+}
+
+/// Fetches the preview of the first link in the message and pushes it to the group.
+pub fn spawn_unfurl(state: &AppState, message: &Message) {
+    if !state.config.link_previews.enabled {
+        return;
+    }
+    let Some(url) = find_url(&message.content) else {
+        return;
+    };
+    let state = state.clone();
+    let group_id = message.group_id.to_string();
+    let message_id = message.id.to_string();
+    tokio::spawn(async move {
+        match preview(&state, url).await {
+            Ok(Some(preview)) => state.backplane.publish(BackplaneEvent::Chat {
+                group_id,
+                message: ChatChannelMessages::LinkPreview {
+                    message_id,
+                    preview,
+                },
+            }),
+            Ok(None) => {}
+            Err(error) => log::error!("Failed to store a link preview: {error}"),
+        }
+    });
+}
+
+/// The cached previews of the first links in `texts`, in the same order, for messages
+/// loaded later.
+pub(crate) async fn cached(
+    state: &AppState,
+    texts: &[&str],
+) -> Result<Vec<Option<chat::LinkPreview>>, sqlx::Error> {
+    if !state.config.link_previews.enabled {
+        return Ok(vec![None; texts.len()]);
+    }
+    let urls: Vec<Option<String>> = texts
+        .iter()
+        .map(|text| find_url(text).map(String::from))
+        .collect();
+    let wanted: Vec<String> = urls.iter().flatten().cloned().collect();
+    let previews = state.link_preview_repository.get_many(&wanted).await?;
+    Ok(urls
+        .into_iter()
+        .map(|url| {
+            // Cloned, the same link may be in several messages
+            let preview = previews.get(&url?).cloned()?;
+            to_chat_preview(preview)
+        })
+        .collect())
+}
+
+/// The preview of the url, from the cache or fetched now.
+async fn preview(state: &AppState, url: Url) -> Result<Option<chat::LinkPreview>, sqlx::Error> {
+    let config = &state.config.link_previews;
+    let cached = state.link_preview_repository.get(url.as_str()).await?;
+    if let Some(cached) = cached
+        && Utc::now() - cached.fetched_at < chrono::Duration::hours(config.cache_hours)
+    {
+        return Ok(to_chat_preview(cached));
+    }
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let fetched = tokio::time::timeout(timeout, fetch(config, url.clone()))
+        .await
+        .unwrap_or(Err(FetchError::Timeout));
+    // Failures are cached as well, so a broken link isn't fetched for every message
+    let metadata = fetched.unwrap_or_else(|error| {
+        log::debug!("No preview of {url}: {error}");
+        Metadata::default()
+    });
+    let preview = LinkPreview {
+        url: url.to_string(),
+        title: metadata.title,
+        description: metadata.description,
+        image_url: metadata.image_url,
+        site_name: metadata.site_name,
+        fetched_at: Utc::now(),
+    };
+    state.link_preview_repository.save(&preview).await?;
+    Ok(to_chat_preview(preview))
+}
+
+/// Pages without a title or description aren't worth a preview.
+fn to_chat_preview(preview: LinkPreview) -> Option<chat::LinkPreview> {
+    if preview.title.is_none() && preview.description.is_none() {
+        return None;
+    }
+    Some(chat::LinkPreview {
+        url: preview.url,
+        title: preview.title,
+        description: preview.description,
+        image_url: preview.image_url,
+        site_name: preview.site_name,
+    })
+}
+
+/// The first http(s) link in the text, without trailing punctuation.
+fn find_url(text: &str) -> Option<Url> {
+    text.split_whitespace().find_map(|word| {
+        let start = word.find("https://").or_else(|| word.find("http://"))?;
+        let candidate = word[start..].trim_end_matches(|c: char| {
+            matches!(c, '.' | ',' | ';' | ':' | '!' | '?' | ')' | ']' | '}' | '>' | '"' | '\'')
+        });
+        if candidate.len() > MAX_URL_LENGTH {
+            return None;
+        }
+        let mut url = Url::parse(candidate).ok()?;
+        url.host_str()?;
+        // Fragments never reach the server, they'd only split the cache
+        url.set_fragment(None);
+        Some(url)
+    })
+}
+
+async fn fetch(config: &LinkPreviewConfig, mut url: Url) -> Result<Metadata, FetchError> {
+    for _ in 0..=MAX_REDIRECTS {
+        let client = client_for(config, &url).await?;
+        let response = client
+            .get(url.clone())
+            .header(header::ACCEPT, "text/html,application/xhtml+xml")
+            .send()
+            .await?;
+        if response.status().is_redirection() {
+            let Some(location) = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+            else {
+                return Ok(Metadata::default());
+            };
+            url = url.join(location).map_err(|_| FetchError::UnsupportedUrl)?;
+            continue;
+        }
+        if !response.status().is_success() || !is_html(&response) {
+            return Ok(Metadata::default());
+        }
+        let body = read_limited(response, config.max_bytes).await?;
+        return Ok(parse_metadata(&String::from_utf8_lossy(&body), &url));
+    }
+    Err(FetchError::TooManyRedirects)
+}
+
+/// A client that connects to the checked addresses of the url's host only.
+async fn client_for(config: &LinkPreviewConfig, url: &Url) -> Result<reqwest::Client, FetchError> {
+    Ok(checked_client(&config.allowed_hosts, url)
+        .await?
+        .user_agent(USER_AGENT)
+        .build()?)
+}
+
+/// A client builder pinned to the addresses the url's host resolves to, after checking
+/// they are public. Hosts in `allowed_hosts` may resolve to private or local addresses.
+/// Redirects aren't followed, they'd need to be checked the same way.
+pub(crate) async fn checked_client(
+    allowed_hosts: &[String],
+    url: &Url,
+) -> Result<reqwest::ClientBuilder, FetchError> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(FetchError::UnsupportedUrl);
+    }
+    let host = url.host_str().ok_or(FetchError::UnsupportedUrl)?;
+    let port = url.port_or_known_default().ok_or(FetchError::UnsupportedUrl)?;
+    let allowed = allowed_hosts
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(host));
+    let check = |ip: IpAddr| {
+        if allowed || is_public(ip) {
+            Ok(())
+        } else {
+            Err(FetchError::PrivateAddress(ip))
+        }
+    };
+    // Proxies would resolve the host again, out of reach of the checks
+    let builder = reqwest::Client::builder()
+        .redirect(redirect::Policy::none())
+        .no_proxy();
+    // IPv6 hosts are written in brackets
+    match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => {
+            check(ip)?;
+            Ok(builder)
+        }
+        Err(_) => {
+            let addresses = tokio::net::lookup_host((host, port))
+                .await
+                .map_err(FetchError::Resolve)?
+                .collect::<Vec<SocketAddr>>();
+            if addresses.is_empty() {
+                return Err(FetchError::Resolve(std::io::ErrorKind::NotFound.into()));
+            }
+            for address in &addresses {
+                check(address.ip())?;
+            }
+            Ok(builder.resolve_to_addrs(host, &addresses))
+        }
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => is_public_v6(ip),
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // "This network", shared address space, protocol assignments, benchmarking, reserved
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b == 18 || b == 19))
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return is_public_v4(ip);
+    }
+    let segments = ip.segments();
+    let embedded_v4 = |high: u16, low: u16| {
+        let [a, b] = high.to_be_bytes();
+        let [c, d] = low.to_be_bytes();
+        Ipv4Addr::new(a, b, c, d)
+    };
+    match segments {
+        // NAT64 and 6to4 reach IPv4 addresses
+        [0x64, 0xff9b, 0, 0, 0, 0, high, low] => is_public_v4(embedded_v4(high, low)),
+        [0x2002, high, low, ..] => is_public_v4(embedded_v4(high, low)),
+        [first, second, ..] => {
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local, link local, documentation
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || (first == 0x2001 && second == 0x0db8))
+        }
+    }
+}
+
+fn is_html(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| {
+            let content_type = content_type.trim_start().to_ascii_lowercase();
+            content_type.starts_with("text/html")
+                || content_type.starts_with("application/xhtml+xml")
+        })
+}
+
+/// The start of the body, the metadata is expected in the head of the page.
+async fn read_limited(mut response: Response, max_bytes: usize) -> Result<Vec<u8>, FetchError> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        let room = max_bytes - body.len();
+        body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        if body.len() >= max_bytes {
+            break;
+        }
+    }
+    Ok(body)
+}
+
+fn parse_metadata(html: &str, page: &Url) -> Metadata {
+    // ASCII lowercasing keeps byte offsets, so positions in `lower` are valid in `html`
+    let lower = html.to_ascii_lowercase();
+    let head_end = lower.find("</head").unwrap_or(lower.len());
+    let mut properties = HashMap::<String, String>::new();
+    let mut position = 0;
+    while position < head_end {
+        let Some(start) = lower[position..head_end].find("<meta") else {
+            break;
+        };
+        let start = position + start;
+        let end = lower[start..].find('>').map_or(lower.len(), |end| start + end);
+        let attributes = parse_attributes(&html[start + "<meta".len()..end]);
+        let key = attributes
+            .get("property")
+            .or_else(|| attributes.get("name"));
+        if let (Some(key), Some(content)) = (key, attributes.get("content")) {
+            properties
+                .entry(key.to_ascii_lowercase())
+                .or_insert_with(|| content.clone());
+        }
+        position = end;
+    }
+    let title_tag = lower[..head_end].find("<title").and_then(|start| {
+        let start = start + lower[start..].find('>')? + 1;
+        let end = start + lower[start..].find("</title")?;
+        Some(decode_entities(&html[start..end]))
+    });
+    let property = |keys: &[&str]| keys.iter().find_map(|key| properties.get(*key).cloned());
+
+    Metadata {
+        title: property(&["og:title", "twitter:title"])
+            .or(title_tag)
+            .and_then(|title| clean(&title, MAX_TITLE_LENGTH)),
+        description: property(&["og:description", "twitter:description", "description"])
+            .and_then(|description| clean(&description, MAX_DESCRIPTION_LENGTH)),
+        image_url: property(&[
+            "og:image:secure_url",
+            "og:image",
+            "og:image:url",
+            "twitter:image",
+            "twitter:image:src",
+        ])
+        .and_then(|image| page.join(image.trim()).ok())
+        .filter(|image| {
+            matches!(image.scheme(), "http" | "https") && image.as_str().len() <= MAX_URL_LENGTH
+        })
+        .map(String::from),
+        site_name: property(&["og:site_name"])
+            .and_then(|site_name| clean(&site_name, MAX_SITE_NAME_LENGTH)),
+    }
+}
+
+/// Attributes of a tag by lowercase name, values with entities decoded.
+fn parse_attributes(tag: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = tag;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        if name_end == 0 {
+            break;
+        }
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let value = match rest.strip_prefix('=').map(str::trim_start) {
+            Some(after) => match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let inner = &after[1..];
+                    let end = inner.find(quote).unwrap_or(inner.len());
+                    rest = inner.get(end + 1..).unwrap_or("");
+                    &inner[..end]
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    rest = &after[end..];
+                    &after[..end]
+                }
+            },
+            None => "",
+        };
+        attributes.insert(name, decode_entities(value));
+    }
+    attributes
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .map(|end| &rest[1..=end]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#')?.parse().ok(),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// The text on one line and cut to `max_length` characters, `None` if empty.
+fn clean(text: &str, max_length: usize) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() <= max_length {
+        return Some(text);
+    }
+    let mut cut = text.chars().take(max_length - 1).collect::<String>();
+    cut.push('…');
+    Some(cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, test_support};
+    use axum::{
+        Router,
+        extract::Path,
+        response::{IntoResponse, Redirect},
+        routing::get,
+    };
+
+    const PAGE: &str = r#"<html><head><title>Fallback</title><meta property="og:title" content="Hello &amp; welcome"></head></html>"#;
+
+    /// A site on a local port, reachable only through `allowed_hosts`.
+    async fn site() -> Url {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = Router::new()
+            .route("/", get(|| async { html(PAGE.to_string()) }))
+            .route(
+                "/hop/{remaining}",
+                get(|Path(remaining): Path<usize>| async move {
+                    match remaining {
+                        0 => Redirect::to("/"),
+                        remaining => Redirect::to(&format!("/hop/{}", remaining - 1)),
+                    }
+                }),
+            )
+            .route("/loop", get(|| async { Redirect::to("/loop") }))
+            .route("/private", get(|| async { Redirect::to("http://10.0.0.1/") }))
+            .route(
+                "/json",
+                get(|| async { ([(header::CONTENT_TYPE, "application/json")], PAGE) }),
+            )
+            .route(
+                "/long",
+                get(|| async {
+                    html(format!(
+                        r#"<html><head><meta property="og:title" content="Early">{}<meta name="description" content="Late"></head></html>"#,
+                        " ".repeat(1024)
+                    ))
+                }),
+            )
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                    html(PAGE.to_string())
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{address}/").parse().unwrap()
+    }
+
+    fn html(body: String) -> impl IntoResponse {
+        ([(header::CONTENT_TYPE, "text/html; charset=utf-8")], body)
+    }
+
+    fn allowing(site: &Url) -> LinkPreviewConfig {
+        LinkPreviewConfig {
+            allowed_hosts: vec![site.host_str().unwrap().to_string()],
+            ..LinkPreviewConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_local_and_private_addresses() {
+        let site = site().await;
+        let config = LinkPreviewConfig::default();
+        for url in [
+            site.as_str(),
+            "http://localhost/",
+            "http://10.0.0.1/",
+            "http://192.168.1.1/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://[64:ff9b::a00:1]/",
+            "http://[2002:7f00:1::]/",
+        ] {
+            let result = fetch(&config, url.parse().unwrap()).await;
+            assert!(
+                matches!(result, Err(FetchError::PrivateAddress(_))),
+                "{url}: {result:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_other_schemes() {
+        let config = LinkPreviewConfig::default();
+        let result = fetch(&config, "ftp://example.com/".parse().unwrap()).await;
+        assert!(
+            matches!(result, Err(FetchError::UnsupportedUrl)),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn public_addresses_are_public() {
+        for ip in [
+            "93.184.216.34",
+            "2606:2800:220:1:248:1893:25c8:1946",
+            "64:ff9b::5db8:d822",
+        ] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "100.64.0.1",
+            "198.18.0.1",
+            "0.1.2.3",
+            "240.0.0.1",
+            "fe80::1",
+            "2001:db8::1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_allowed_hosts() {
+        let site = site().await;
+        let metadata = fetch(&allowing(&site), site.clone()).await.unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Hello & welcome"));
+    }
+
+    #[tokio::test]
+    async fn follows_a_limited_number_of_redirects() {
+        let site = site().await;
+        let config = allowing(&site);
+        let within_limit = site.join(&format!("/hop/{}", MAX_REDIRECTS - 1)).unwrap();
+        let metadata = fetch(&config, within_limit).await.unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Hello & welcome"));
+
+        let beyond_limit = site.join(&format!("/hop/{MAX_REDIRECTS}")).unwrap();
+        let result = fetch(&config, beyond_limit).await;
+        assert!(
+            matches!(result, Err(FetchError::TooManyRedirects)),
+            "{result:?}"
+        );
+        let result = fetch(&config, site.join("/loop").unwrap()).await;
+        assert!(
+            matches!(result, Err(FetchError::TooManyRedirects)),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn checks_redirect_targets() {
+        let site = site().await;
+        let result = fetch(&allowing(&site), site.join("/private").unwrap()).await;
+        assert!(
+            matches!(result, Err(FetchError::PrivateAddress(_))),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_at_most_max_bytes() {
+        let site = site().await;
+        let config = LinkPreviewConfig {
+            max_bytes: 512,
+            ..allowing(&site)
+        };
+        let metadata = fetch(&config, site.join("/long").unwrap()).await.unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Early"));
+        assert_eq!(metadata.description, None);
+    }
+
+    #[tokio::test]
+    async fn ignores_other_content_types() {
+        let site = site().await;
+        let metadata = fetch(&allowing(&site), site.join("/json").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(metadata.title, None);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_slow_pages_and_caches_the_failure() {
+        let site = site().await;
+        let config = Config {
+            link_previews: LinkPreviewConfig {
+                timeout_secs: 1,
+                ..allowing(&site)
+            },
+            ..Config::default()
+        };
+        let state = test_support::state(config).await;
+        let url = site.join("/slow").unwrap();
+
+        let started = std::time::Instant::now();
+        assert_eq!(preview(&state, url.clone()).await.unwrap(), None);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let cached = state
+            .link_preview_repository
+            .get(url.as_str())
+            .await
+            .unwrap();
+        assert_eq!(cached.map(|cached| cached.title), Some(None));
+    }
+
+    #[tokio::test]
+    async fn looks_up_cached_previews_in_order() {
+        let state = test_support::state(Config::default()).await;
+        for (url, title) in [
+            ("https://a.example/", Some("A")),
+            ("https://b.example/", None),
+        ] {
+            let preview = LinkPreview {
+                url: url.to_string(),
+                title: title.map(str::to_string),
+                description: None,
+                image_url: None,
+                site_name: None,
+                fetched_at: Utc::now(),
+            };
+            state.link_preview_repository.save(&preview).await.unwrap();
+        }
+
+        let texts = [
+            "see https://a.example/",
+            "no link",
+            "failed https://b.example/",
+            "not fetched yet https://c.example/",
+            "again https://a.example/#top.",
+        ];
+        let titles: Vec<Option<String>> = cached(&state, &texts)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|preview| preview.and_then(|preview| preview.title))
+            .collect();
+        let a = Some("A".to_string());
+        assert_eq!(titles, [a.clone(), None, None, None, a]);
+    }
+}
@@ -0,0 +1,658 @@
+//! OpenID Connect single sign-on with the authorization code flow and PKCE.
+//!
+//! Providers are configured in the `oidc.providers` section of the [`Config`](crate::config::Config).
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use openidconnect::{
+    AuthorizationCode, ClientId, ClientSecret, CsrfToken, EndpointMaybeSet, EndpointNotSet,
+    EndpointSet, IssuerUrl, Nonce, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope,
+    TokenResponse,
+    core::{CoreAuthenticationFlow, CoreClient, CoreProviderMetadata},
+    reqwest,
+    url::form_urlencoded,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    config::Config,
+    domain::{identity::UserIdentity, user::User},
+};
+
+/// Session key of the flow that is currently in progress.
+pub const OIDC_FLOW_KEY: &str = "oidc_flow";
+
+type OidcClient = CoreClient<
+    EndpointSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointMaybeSet,
+    EndpointMaybeSet,
+>;
+
+#[derive(Clone, Debug)]
+pub struct OidcProviderConfig {
+    pub id: String,
+    pub name: String,
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum OidcError {
+    #[error("Unknown identity provider {0}")]
+    UnknownProvider(String),
+    #[error("Identity provider discovery failed: {0}")]
+    Discovery(String),
+    #[error("Sign in was not started or has expired")]
+    MissingFlow,
+    #[error("Sign in state mismatch")]
+    StateMismatch,
+    #[error("Code exchange failed: {0}")]
+    Exchange(String),
+    #[error("Identity provider did not return an id token")]
+    MissingIdToken,
+    #[error("Invalid id token: {0}")]
+    InvalidIdToken(String),
+    #[error("This identity is already linked to another account")]
+    AlreadyLinked,
+    #[error("Sign up is disabled, link this identity to an existing account instead")]
+    SignupDisabled,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// State of a sign in that is kept in the session between redirect and callback.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OidcFlow {
+    pub provider: String,
+    pub csrf_state: String,
+    pub nonce: String,
+    pub pkce_verifier: String,
+    pub next: String,
+    /// Set when a signed in user links an identity to their account.
+    pub link_user_id: Option<Uuid>,
+}
+
+/// The verified claims of a sign in.
+#[derive(Clone, Debug)]
+pub struct OidcIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub preferred_username: Option<String>,
+    pub email: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct Oidc {
+    providers: Arc<Vec<OidcProviderConfig>>,
+    public_url: String,
+    metadata: Arc<Mutex<HashMap<String, CoreProviderMetadata>>>,
+    http: reqwest::Client,
+}
+
+impl Oidc {
+    pub fn new(providers: Vec<OidcProviderConfig>, public_url: String) -> Self {
+        let http = reqwest::ClientBuilder::new()
+            // Following redirects opens the client up to SSRF vulnerabilities
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to build http client");
+        Self {
+            providers: Arc::new(providers),
+            public_url: public_url.trim_end_matches('/').to_string(),
+            metadata: Arc::default(),
+            http,
+        }
+    }
+
+    /// Builds the providers of the configuration, none if single sign-on is disabled.
+    pub fn from_config(config: &Config, default_public_url: String) -> Self {
+        let providers = if config.features.oidc {
+            config
+                .oidc
+                .providers
+                .iter()
+                .map(|(id, provider)| OidcProviderConfig {
+                    id: id.clone(),
+                    name: provider.name.clone().unwrap_or_else(|| id.clone()),
+                    issuer_url: provider.issuer_url.clone(),
+                    client_id: provider.client_id.clone(),
+                    client_secret: provider.client_secret.clone(),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let public_url = config.public_url.clone().unwrap_or(default_public_url);
+        Self::new(providers, public_url)
+    }
+
+    pub fn providers(&self) -> &[OidcProviderConfig] {
+        &self.providers
+    }
+
+    fn provider(&self, id: &str) -> Result<&OidcProviderConfig, OidcError> {
+        self.providers
+            .iter()
+            .find(|provider| provider.id == id)
+            .ok_or_else(|| OidcError::UnknownProvider(id.to_string()))
+    }
+
+    async fn client(&self, provider: &OidcProviderConfig) -> Result<OidcClient, OidcError> {
+        let cached = self
+            .metadata
+            .lock()
+            .expect("oidc metadata poisoned")
+            .get(&provider.id)
+            .cloned();
+        let metadata = match cached {
+            Some(metadata) => metadata,
+            None => {
+                let issuer_url = IssuerUrl::new(provider.issuer_url.clone())
+                    .map_err(|error| OidcError::Discovery(error.to_string()))?;
+                let metadata = CoreProviderMetadata::discover_async(issuer_url, &self.http)
+                    .await
+                    .map_err(|error| OidcError::Discovery(error.to_string()))?;
+                self.metadata
+                    .lock()
+                    .expect("oidc metadata poisoned")
+                    .insert(provider.id.clone(), metadata.clone());
+                metadata
+            }
+        };
+        let redirect_url = RedirectUrl::new(format!(
+            "{}/auth/oidc/{}/callback",
+            self.public_url, provider.id
+        ))
+        .map_err(|error| OidcError::Discovery(error.to_string()))?;
+        Ok(CoreClient::from_provider_metadata(
+            metadata,
+            ClientId::new(provider.client_id.clone()),
+            provider.client_secret.clone().map(ClientSecret::new),
+        )
+        .set_redirect_uri(redirect_url))
+    }
+
+    /// Starts a sign in, returns the url of the provider and the flow to keep in the session.
+    pub async fn authorize(
+        &self,
+        provider_id: &str,
+        next: String,
+        link_user_id: Option<Uuid>,
+    ) -> Result<(String, OidcFlow), OidcError> {
+        let provider = self.provider(provider_id)?;
+        let client = self.client(provider).await?;
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let (url, csrf_state, nonce) = client
+            .authorize_url(
+                CoreAuthenticationFlow::AuthorizationCode,
+                CsrfToken::new_random,
+                Nonce::new_random,
+            )
+            .add_scope(Scope::new("profile".to_string()))
+            .add_scope(Scope::new("email".to_string()))
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+        let flow = OidcFlow {
+            provider: provider.id.clone(),
+            csrf_state: csrf_state.secret().clone(),
+            nonce: nonce.secret().clone(),
+            pkce_verifier: pkce_verifier.secret().clone(),
+            next,
+            link_user_id,
+        };
+        Ok((url.to_string(), flow))
+    }
+
+    /// Exchanges the authorization code and verifies the id token of the callback.
+    pub async fn exchange(
+        &self,
+        provider_id: &str,
+        flow: &OidcFlow,
+        code: String,
+        state: &str,
+    ) -> Result<OidcIdentity, OidcError> {
+        if flow.provider != provider_id || flow.csrf_state != state {
+            return Err(OidcError::StateMismatch);
+        }
+        let provider = self.provider(provider_id)?;
+        let client = self.client(provider).await?;
+        let response = client
+            .exchange_code(AuthorizationCode::new(code))
+            .map_err(|error| OidcError::Exchange(error.to_string()))?
+            .set_pkce_verifier(PkceCodeVerifier::new(flow.pkce_verifier.clone()))
+            .request_async(&self.http)
+            .await
+            .map_err(|error| OidcError::Exchange(error.to_string()))?;
+        let id_token = response.id_token().ok_or(OidcError::MissingIdToken)?;
+        let claims = id_token
+            .claims(&client.id_token_verifier(), &Nonce::new(flow.nonce.clone()))
+            .map_err(|error| OidcError::InvalidIdToken(error.to_string()))?;
+        Ok(OidcIdentity {
+            provider: provider.id.clone(),
+            subject: claims.subject().to_string(),
+            preferred_username: claims
+                .preferred_username()
+                .map(|username| username.to_string()),
+            email: claims
+                .email()
+                .filter(|_| claims.email_verified().unwrap_or(false))
+                .map(|email| email.to_string()),
+        })
+    }
+}
+
+/// The login page, showing `message` as the reason a sign in failed.
+pub fn login_error_path(message: &str) -> String {
+    format!(
+        "/login?error={}",
+        form_urlencoded::byte_serialize(message.as_bytes()).collect::<String>()
+    )
+}
+
+/// Resolves the user of a verified identity, linking or creating the account if needed.
+pub async fn sign_in(
+    state: &AppState,
+    identity: OidcIdentity,
+    link_user_id: Option<Uuid>,
+) -> Result<Uuid, OidcError> {
+    if let Some(existing) = state
+        .identity_repository
+        .get_by_subject(&identity.provider, &identity.subject)
+        .await?
+    {
+        return match link_user_id {
+            Some(user_id) if user_id != existing.user_id => Err(OidcError::AlreadyLinked),
+            _ => Ok(existing.user_id),
+        };
+    }
+
+    if let Some(user_id) = link_user_id {
+        state
+            .identity_repository
+            .create(UserIdentity::new(
+                user_id,
+                identity.provider,
+                identity.subject,
+                identity.email,
+                false,
+            ))
+            .await?;
+        return Ok(user_id);
+    }
+
+    if !state.config.features.signup {
+        return Err(OidcError::SignupDisabled);
+    }
+    let username = available_username(state, &identity).await?;
+    // Users signing up through a provider have no password they know of
+    let password = password_auth::generate_hash(nanoid::nanoid!(32));
+    let user_id = state
+        .user_repository
+        .create(User::new(username, password))
+        .await?;
+    state
+        .identity_repository
+        .create(UserIdentity::new(
+            user_id,
+            identity.provider,
+            identity.subject,
+            identity.email,
+            true,
+        ))
+        .await?;
+    Ok(user_id)
+}
+
+/// Derives a free username from the claims of the identity.
+async fn available_username(
+    state: &AppState,
+    identity: &OidcIdentity,
+) -> Result<String, sqlx::Error> {
+    let base: String = identity
+        .preferred_username
+        .as_deref()
+        .or_else(|| identity.email.as_deref().and_then(|email| email.split('@').next()))
+        .unwrap_or("user")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-' || *c == '.')
+        .take(24)
+        .collect();
+    let base = if base.is_empty() { "user".to_string() } else { base };
+
+    let mut username = base.clone();
+    loop {
+        match state.user_repository.get_by_username(username.clone()).await {
+            Err(sqlx::Error::RowNotFound) => return Ok(username),
+            Err(error) => return Err(error),
+            Ok(_) => username = format!("{base}-{}", nanoid::nanoid!(6, &nanoid::alphabet::SAFE)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{
+        Form, Json, Router,
+        extract::State,
+        http::StatusCode,
+        routing::{get, post},
+    };
+    use chrono::{Duration, Utc};
+    use openidconnect::{
+        Audience, EmptyAdditionalClaims, EndUserUsername, IssuerUrl, JsonWebKeyId, Nonce,
+        PkceCodeChallenge, PkceCodeVerifier, PrivateSigningKey, StandardClaims, SubjectIdentifier,
+        core::{
+            CoreIdToken, CoreIdTokenClaims, CoreJsonWebKeySet, CoreJwsSigningAlgorithm,
+            CoreRsaPrivateSigningKey,
+        },
+        url::Url,
+    };
+    use serde_json::{Value, json};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{config::Config, test_support};
+
+    const PROVIDER: &str = "test";
+    const CLIENT_ID: &str = "chat";
+    const SIGNING_KEY: &str = include_str!("../testdata/oidc_rsa_key.pem");
+
+    /// What the issuer remembers of an authorization code it handed out.
+    struct Grant {
+        code_challenge: String,
+        nonce: String,
+        subject: String,
+    }
+
+    /// An identity provider on a local port, with discovery, keys and token endpoint.
+    #[derive(Clone)]
+    struct Issuer {
+        url: String,
+        key: Arc<CoreRsaPrivateSigningKey>,
+        grants: Arc<Mutex<HashMap<String, Grant>>>,
+    }
+
+    impl Issuer {
+        async fn start() -> Self {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let key = CoreRsaPrivateSigningKey::from_pem(
+                SIGNING_KEY,
+                Some(JsonWebKeyId::new("k1".into())),
+            )
+            .unwrap();
+            let issuer = Issuer {
+                url: format!("http://{}", listener.local_addr().unwrap()),
+                key: Arc::new(key),
+                grants: Arc::default(),
+            };
+            let app = Router::new()
+                .route("/.well-known/openid-configuration", get(discovery))
+                .route("/jwks", get(jwks))
+                .route("/token", post(token))
+                .with_state(issuer.clone());
+            tokio::spawn(async move { axum::serve(listener, app).await });
+            issuer
+        }
+
+        fn oidc(&self) -> Oidc {
+            Oidc::new(
+                vec![OidcProviderConfig {
+                    id: PROVIDER.to_string(),
+                    name: "Test".to_string(),
+                    issuer_url: self.url.clone(),
+                    client_id: CLIENT_ID.to_string(),
+                    client_secret: Some("secret".to_string()),
+                }],
+                "http://chat.test".to_string(),
+            )
+        }
+
+        /// The user approving the sign in, returns the code the provider redirects back with.
+        fn approve(&self, authorize_url: &str, subject: &str) -> String {
+            self.grant(
+                query_param(authorize_url, "code_challenge"),
+                query_param(authorize_url, "nonce"),
+                subject,
+            )
+        }
+
+        fn grant(&self, code_challenge: String, nonce: String, subject: &str) -> String {
+            let code = nanoid::nanoid!();
+            self.grants.lock().unwrap().insert(
+                code.clone(),
+                Grant {
+                    code_challenge,
+                    nonce,
+                    subject: subject.to_string(),
+                },
+            );
+            code
+        }
+    }
+
+    fn query_param(url: &str, name: &str) -> String {
+        Url::parse(url)
+            .unwrap()
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap_or_else(|| panic!("{name} missing from {url}"))
+    }
+
+    async fn discovery(State(issuer): State<Issuer>) -> Json<Value> {
+        Json(json!({
+            "issuer": issuer.url,
+            "authorization_endpoint": format!("{}/authorize", issuer.url),
+            "token_endpoint": format!("{}/token", issuer.url),
+            "jwks_uri": format!("{}/jwks", issuer.url),
+            "response_types_supported": ["code"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["RS256"],
+        }))
+    }
+
+    async fn jwks(State(issuer): State<Issuer>) -> Json<CoreJsonWebKeySet> {
+        Json(CoreJsonWebKeySet::new(vec![
+            issuer.key.as_verification_key(),
+        ]))
+    }
+
+    async fn token(
+        State(issuer): State<Issuer>,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+        let invalid_grant = || {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "invalid_grant" })),
+            )
+        };
+        let code = form.get("code").ok_or_else(invalid_grant)?;
+        let grant = issuer
+            .grants
+            .lock()
+            .unwrap()
+            .remove(code)
+            .ok_or_else(invalid_grant)?;
+        let verifier = PkceCodeVerifier::new(
+            form.get("code_verifier")
+                .cloned()
+                .ok_or_else(invalid_grant)?,
+        );
+        if PkceCodeChallenge::from_code_verifier_sha256(&verifier).as_str() != grant.code_challenge
+        {
+            return Err(invalid_grant());
+        }
+        let claims = CoreIdTokenClaims::new(
+            IssuerUrl::new(issuer.url.clone()).unwrap(),
+            vec![Audience::new(CLIENT_ID.to_string())],
+            Utc::now() + Duration::minutes(5),
+            Utc::now(),
+            StandardClaims::new(SubjectIdentifier::new(grant.subject.clone()))
+                .set_preferred_username(Some(EndUserUsername::new(grant.subject))),
+            EmptyAdditionalClaims {},
+        )
+        .set_nonce(Some(Nonce::new(grant.nonce)));
+        let id_token = CoreIdToken::new(
+            claims,
+            issuer.key.as_ref(),
+            CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256,
+            None,
+            None,
+        )
+        .unwrap();
+        Ok(Json(json!({
+            "access_token": "access",
+            "token_type": "bearer",
+            "id_token": id_token,
+        })))
+    }
+
+    async fn sign_in_as(
+        state: &AppState,
+        issuer: &Issuer,
+        subject: &str,
+        link_user_id: Option<Uuid>,
+    ) -> Result<Uuid, OidcError> {
+        let (url, flow) = state
+            .oidc
+            .authorize(PROVIDER, "/".to_string(), link_user_id)
+            .await?;
+        let code = issuer.approve(&url, subject);
+        let identity = state
+            .oidc
+            .exchange(PROVIDER, &flow, code, &flow.csrf_state)
+            .await?;
+        sign_in(state, identity, flow.link_user_id).await
+    }
+
+    #[tokio::test]
+    async fn exchanges_the_code_with_the_pkce_verifier() {
+        let issuer = Issuer::start().await;
+        let oidc = issuer.oidc();
+        let (url, flow) = oidc
+            .authorize(PROVIDER, "/".to_string(), None)
+            .await
+            .unwrap();
+        let code = issuer.approve(&url, "alice");
+
+        let identity = oidc
+            .exchange(PROVIDER, &flow, code, &flow.csrf_state)
+            .await
+            .unwrap();
+        assert_eq!(identity.provider, PROVIDER);
+        assert_eq!(identity.subject, "alice");
+        assert_eq!(identity.preferred_username.as_deref(), Some("alice"));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_wrong_pkce_verifier() {
+        let issuer = Issuer::start().await;
+        let oidc = issuer.oidc();
+        let (url, flow) = oidc
+            .authorize(PROVIDER, "/".to_string(), None)
+            .await
+            .unwrap();
+        let code = issuer.approve(&url, "alice");
+        let flow = OidcFlow {
+            pkce_verifier: "x".repeat(43),
+            ..flow
+        };
+
+        let result = oidc.exchange(PROVIDER, &flow, code, &flow.csrf_state).await;
+        assert!(matches!(result, Err(OidcError::Exchange(_))), "{result:?}");
+    }
+
+    #[tokio::test]
+    async fn rejects_a_state_mismatch() {
+        let issuer = Issuer::start().await;
+        let oidc = issuer.oidc();
+        let (url, flow) = oidc
+            .authorize(PROVIDER, "/".to_string(), None)
+            .await
+            .unwrap();
+        let code = issuer.approve(&url, "alice");
+
+        let result = oidc.exchange(PROVIDER, &flow, code, "forged").await;
+        assert!(
+            matches!(result, Err(OidcError::StateMismatch)),
+            "{result:?}"
+        );
+        // The code is still unused, the mismatch is caught before the exchange
+        assert_eq!(issuer.grants.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rejects_a_nonce_mismatch() {
+        let issuer = Issuer::start().await;
+        let oidc = issuer.oidc();
+        let (url, flow) = oidc
+            .authorize(PROVIDER, "/".to_string(), None)
+            .await
+            .unwrap();
+        let code = issuer.grant(
+            query_param(&url, "code_challenge"),
+            "replayed".to_string(),
+            "alice",
+        );
+
+        let result = oidc.exchange(PROVIDER, &flow, code, &flow.csrf_state).await;
+        assert!(
+            matches!(result, Err(OidcError::InvalidIdToken(_))),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn links_an_identity_to_the_signed_in_user() {
+        let issuer = Issuer::start().await;
+        let state = AppState {
+            oidc: issuer.oidc(),
+            ..test_support::state(Config::default()).await
+        };
+        let alice = test_support::user(&state, "alice").await;
+
+        let linked = sign_in_as(&state, &issuer, "subject-1", Some(alice)).await;
+        assert_eq!(linked.unwrap(), alice);
+        // Signing in later finds the linked account
+        let signed_in = sign_in_as(&state, &issuer, "subject-1", None).await;
+        assert_eq!(signed_in.unwrap(), alice);
+    }
+
+    #[tokio::test]
+    async fn refuses_to_link_an_identity_twice() {
+        let issuer = Issuer::start().await;
+        let state = AppState {
+            oidc: issuer.oidc(),
+            ..test_support::state(Config::default()).await
+        };
+        let alice = test_support::user(&state, "alice").await;
+        let bob = test_support::user(&state, "bob").await;
+        sign_in_as(&state, &issuer, "subject-1", Some(alice))
+            .await
+            .unwrap();
+
+        let result = sign_in_as(&state, &issuer, "subject-1", Some(bob)).await;
+        assert!(
+            matches!(result, Err(OidcError::AlreadyLinked)),
+            "{result:?}"
+        );
+        // Linking it to the account it already belongs to is fine
+        let relinked = sign_in_as(&state, &issuer, "subject-1", Some(alice)).await;
+        assert_eq!(relinked.unwrap(), alice);
+    }
+}
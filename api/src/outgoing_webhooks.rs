@@ -0,0 +1,439 @@
+//! Delivery of group events to outgoing webhooks.
+//!
+//! Events are queued in the database and sent by a background worker, failed
+//! deliveries are retried with exponential back-off. Every request carries an
+//! HMAC-SHA256 signature of `{timestamp}.{body}` keyed with the secret of the
+//! webhook, so receivers can check it came from this server. Like links of
+//! previews, webhooks are only sent to public addresses unless allow-listed.
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    domain::outgoing_webhook::{OutgoingWebhook, WebhookDelivery},
+    link_previews,
+    server_fn::webhooks::WebhookEvent,
+};
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Deliveries attempted per poll.
+const BATCH_SIZE: i64 = 20;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a worker has for an attempt before others may retry the delivery.
+const CLAIM_DURATION: Duration = Duration::from_secs(60);
+/// Attempts before a delivery is given up, the last one about six hours after the first.
+const MAX_ATTEMPTS: i64 = 12;
+const FIRST_RETRY_AFTER: Duration = Duration::from_secs(10);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(6 * 60 * 60);
+/// Most characters of a response kept as error of a failed attempt.
+const MAX_ERROR_LENGTH: usize = 500;
+
+#[derive(Serialize)]
+struct Payload {
+    id: String,
+    event: WebhookEvent,
+    group_id: String,
+    created_at: chrono::DateTime<Utc>,
+    data: serde_json::Value,
+}
+
+/// Queues the event for the webhooks of the group subscribed to it.
+///
+/// Failing to queue is only logged, the action causing the event already happened.
+pub async fn enqueue(state: &AppState, group_id: Uuid, event: WebhookEvent, data: serde_json::Value) {
+    if !state.config.features.webhooks {
+        return;
+    }
+    let webhooks = match state.outgoing_webhook_repository.list_by_group(group_id).await {
+        Ok(webhooks) => webhooks,
+        Err(error) => {
+            log::error!("Failed to load outgoing webhooks of {group_id}: {error}");
+            return;
+        }
+    };
+    for webhook in webhooks.iter().filter(|webhook| webhook.subscribes_to(event)) {
+        let id = Uuid::new_v4();
+        let payload = Payload {
+            id: id.to_string(),
+            event,
+            group_id: group_id.to_string(),
+            created_at: Utc::now(),
+            data: data.clone(),
+        };
+        let payload = match serde_json::to_string(&payload) {
+            Ok(payload) => payload,
+            Err(error) => {
+                log::error!("Failed to serialize webhook payload: {error}");
+                return;
+            }
+        };
+        let delivery = WebhookDelivery::new(id, webhook.id, event, payload);
+        if let Err(error) = state.outgoing_webhook_repository.enqueue(delivery).await {
+            log::error!("Failed to queue delivery to webhook {}: {error}", webhook.id);
+        }
+    }
+}
+
+/// Signature of a payload sent at `timestamp`, in the format of [`SIGNATURE_HEADER`].
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Delay before the attempt following `attempts` failed ones.
+fn retry_after(attempts: i64) -> Duration {
+    let exponent = u32::try_from(attempts.saturating_sub(1)).unwrap_or(u32::MAX).min(16);
+    FIRST_RETRY_AFTER
+        .saturating_mul(2u32.pow(exponent))
+        .min(MAX_RETRY_AFTER)
+}
+
+/// Sends due deliveries in the background for as long as the server runs.
+pub fn spawn_worker(state: AppState) {
+    if !state.config.features.webhooks {
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let due = match state
+                .outgoing_webhook_repository
+                .due(Utc::now(), BATCH_SIZE)
+                .await
+            {
+                Ok(due) => due,
+                Err(error) => {
+                    log::error!("Failed to load due webhook deliveries: {error}");
+                    continue;
+                }
+            };
+            for delivery in due {
+                if let Err(error) = attempt(&state, delivery).await {
+                    log::error!("Failed to record webhook delivery: {error}");
+                }
+            }
+        }
+    });
+}
+
+async fn attempt(state: &AppState, delivery: WebhookDelivery) -> Result<(), sqlx::Error> {
+    let repository = &state.outgoing_webhook_repository;
+    let claimed_until = Utc::now()
+        + chrono::Duration::from_std(CLAIM_DURATION).expect("claim duration is in range");
+    if !repository.claim(&delivery, claimed_until).await? {
+        return Ok(());
+    }
+    // Deliveries of deleted webhooks are deleted with them
+    let Some(webhook) = repository.get_by_id(delivery.webhook_id).await? else {
+        return Ok(());
+    };
+    let attempts = delivery.attempts + 1;
+    match send(state, &webhook, &delivery).await {
+        Ok(()) => {
+            repository
+                .record_attempt(delivery.id, attempts, None, None, Some(Utc::now()))
+                .await
+        }
+        Err(error) => {
+            let next_attempt_at = (attempts < MAX_ATTEMPTS).then(|| {
+                Utc::now() + chrono::Duration::from_std(retry_after(attempts)).unwrap_or_default()
+            });
+            if next_attempt_at.is_none() {
+                log::warn!(
+                    "Giving up delivery {} to webhook {} after {attempts} attempts: {error}",
+                    delivery.id,
+                    webhook.id
+                );
+            }
+            repository
+                .record_attempt(delivery.id, attempts, next_attempt_at, Some(error), None)
+                .await
+        }
+    }
+}
+
+/// A client for the url of a webhook, which must not point into the server's network.
+///
+/// Checked when the webhook is created and again before every delivery, since the
+/// addresses a host resolves to can change in between.
+pub(crate) async fn client_for(state: &AppState, url: &str) -> Result<reqwest::Client, String> {
+    let url = reqwest::Url::parse(url).map_err(|error| error.to_string())?;
+    link_previews::checked_client(&state.config.outgoing_webhooks.allowed_hosts, &url)
+        .await
+        .map_err(|error| error.to_string())?
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|error| error.to_string())
+}
+
+/// Posts the payload, any response but a 2xx one is a failed attempt.
+async fn send(
+    state: &AppState,
+    webhook: &OutgoingWebhook,
+    delivery: &WebhookDelivery,
+) -> Result<(), String> {
+    let http = client_for(state, &webhook.url).await?;
+    let timestamp = Utc::now().timestamp();
+    let response = http
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, sign(&webhook.secret, timestamp, &delivery.payload))
+        .header(TIMESTAMP_HEADER, timestamp)
+        .header(EVENT_HEADER, delivery.event.to_string())
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .body(delivery.payload.clone())
+        .send()
+        .await
+        .map_err(|error| error.to_string())?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(format!("{status}: {}", body.chars().take(MAX_ERROR_LENGTH).collect::<String>())
+        .trim_end_matches([' ', ':'])
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{Config, OutgoingWebhookConfig},
+        domain::group::Group,
+        test_support,
+    };
+    use axum::{
+        Router,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+    };
+    use std::sync::{
+        Arc, Mutex,
+        atomic::{AtomicU16, Ordering},
+    };
+
+    /// A local endpoint recording the requests it gets and answering them with `status`.
+    #[derive(Clone)]
+    struct Receiver {
+        url: String,
+        status: Arc<AtomicU16>,
+        requests: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+    }
+
+    impl Receiver {
+        async fn start() -> Self {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let receiver = Receiver {
+                url: format!("http://{}/hook", listener.local_addr().unwrap()),
+                status: Arc::new(AtomicU16::new(204)),
+                requests: Arc::default(),
+            };
+            let app = Router::new()
+                .route("/hook", post(receive))
+                .with_state(receiver.clone());
+            tokio::spawn(async move { axum::serve(listener, app).await });
+            receiver
+        }
+
+        fn answer_with(&self, status: StatusCode) {
+            self.status.store(status.as_u16(), Ordering::SeqCst);
+        }
+
+        fn requests(&self) -> Vec<(HeaderMap, String)> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    async fn receive(
+        State(receiver): State<Receiver>,
+        headers: HeaderMap,
+        body: String,
+    ) -> (StatusCode, &'static str) {
+        receiver.requests.lock().unwrap().push((headers, body));
+        let status = StatusCode::from_u16(receiver.status.load(Ordering::SeqCst)).unwrap();
+        (status, "boom")
+    }
+
+    async fn state(allowed_hosts: &[&str]) -> AppState {
+        test_support::state(Config {
+            outgoing_webhooks: OutgoingWebhookConfig {
+                allowed_hosts: allowed_hosts.iter().map(ToString::to_string).collect(),
+            },
+            ..Config::default()
+        })
+        .await
+    }
+
+    async fn webhook(state: &AppState, url: &str) -> OutgoingWebhook {
+        let owner = test_support::user(state, "alice").await;
+        let group_id = state
+            .group_repository
+            .create_group(Group::new("builds".to_string()))
+            .await
+            .unwrap();
+        let events = vec![WebhookEvent::MessageCreated];
+        let webhook = OutgoingWebhook::generate(group_id, url.to_string(), events, owner);
+        state
+            .outgoing_webhook_repository
+            .create(webhook.clone())
+            .await
+            .unwrap();
+        enqueue(
+            state,
+            group_id,
+            WebhookEvent::MessageCreated,
+            serde_json::json!({ "text": "Build passed" }),
+        )
+        .await;
+        webhook
+    }
+
+    /// Attempts the deliveries due at `at` the way the worker does.
+    async fn attempt_due(state: &AppState, at: chrono::DateTime<Utc>) -> usize {
+        let due = state
+            .outgoing_webhook_repository
+            .due(at, BATCH_SIZE)
+            .await
+            .unwrap();
+        let count = due.len();
+        for delivery in due {
+            attempt(state, delivery).await.unwrap();
+        }
+        count
+    }
+
+    async fn pending(state: &AppState) -> Vec<WebhookDelivery> {
+        let later = Utc::now() + chrono::Duration::days(1);
+        state
+            .outgoing_webhook_repository
+            .due(later, BATCH_SIZE)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payloads() {
+        let receiver = Receiver::start().await;
+        let state = state(&["127.0.0.1"]).await;
+        let webhook = webhook(&state, &receiver.url).await;
+
+        assert_eq!(attempt_due(&state, Utc::now()).await, 1);
+        let requests = receiver.requests();
+        let [(headers, body)] = &requests[..] else {
+            panic!("Expected one request, got {requests:?}");
+        };
+        let header = |name: &str| headers.get(name).unwrap().to_str().unwrap();
+        let timestamp: i64 = header(TIMESTAMP_HEADER).parse().unwrap();
+        assert!((Utc::now().timestamp() - timestamp).abs() < 60);
+        assert_eq!(
+            header(SIGNATURE_HEADER),
+            sign(&webhook.secret, timestamp, body)
+        );
+        assert_eq!(
+            header(EVENT_HEADER),
+            WebhookEvent::MessageCreated.to_string()
+        );
+        assert_eq!(header("content-type"), "application/json");
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(payload["id"], header(DELIVERY_HEADER));
+        assert_eq!(payload["data"]["text"], "Build passed");
+        assert!(pending(&state).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn retries_failed_deliveries_later() {
+        let receiver = Receiver::start().await;
+        receiver.answer_with(StatusCode::INTERNAL_SERVER_ERROR);
+        let state = state(&["127.0.0.1"]).await;
+        webhook(&state, &receiver.url).await;
+
+        let attempted_at = Utc::now();
+        assert_eq!(attempt_due(&state, attempted_at).await, 1);
+        assert_eq!(attempt_due(&state, Utc::now()).await, 0);
+        let retried = pending(&state).await;
+        let [delivery] = &retried[..] else {
+            panic!("Expected the delivery to be retried, got {retried:?}");
+        };
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(
+            delivery.last_error.as_deref(),
+            Some("500 Internal Server Error: boom")
+        );
+        let retry_at = delivery.next_attempt_at.unwrap();
+        assert!(retry_at >= attempted_at + chrono::Duration::seconds(9));
+        assert!(retry_at <= Utc::now() + chrono::Duration::from_std(FIRST_RETRY_AFTER).unwrap());
+
+        receiver.answer_with(StatusCode::OK);
+        assert_eq!(attempt_due(&state, retry_at).await, 1);
+        assert_eq!(receiver.requests().len(), 2);
+        assert!(pending(&state).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuses_private_addresses() {
+        let receiver = Receiver::start().await;
+        let state = state(&[]).await;
+        webhook(&state, &receiver.url).await;
+
+        assert_eq!(attempt_due(&state, Utc::now()).await, 1);
+        assert!(receiver.requests().is_empty());
+        let failed = pending(&state).await;
+        let error = failed[0].last_error.as_deref().unwrap();
+        assert!(error.contains("not a public address"), "{error}");
+        for url in [
+            "http://169.254.169.254/latest",
+            "http://[::1]/",
+            "file:///etc/passwd",
+        ] {
+            assert!(client_for(&state, url).await.is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn signature_is_hmac_of_timestamp_and_body() {
+        assert_eq!(
+            sign(
+                "whsec_test",
+                1_700_000_000,
+                r#"{"event":"message.created"}"#
+            ),
+            "sha256=9884eb2fcc09ffc10f00127fff0a0c5686da2fef61d0363442271c6dfa1917eb"
+        );
+    }
+
+    #[test]
+    fn signature_depends_on_secret_and_timestamp() {
+        let signature = sign("whsec_test", 1_700_000_000, "{}");
+        assert_ne!(signature, sign("whsec_other", 1_700_000_000, "{}"));
+        assert_ne!(signature, sign("whsec_test", 1_700_000_001, "{}"));
+    }
+
+    #[test]
+    fn retries_back_off_exponentially() {
+        let delays: Vec<u64> = (1..=6)
+            .map(|attempts| retry_after(attempts).as_secs())
+            .collect();
+        assert_eq!(delays, [10, 20, 40, 80, 160, 320]);
+    }
+
+    #[test]
+    fn retry_delay_is_capped() {
+        assert_eq!(retry_after(MAX_ATTEMPTS).as_secs(), 20_480);
+        assert_eq!(retry_after(MAX_ATTEMPTS + 1), MAX_RETRY_AFTER);
+        assert_eq!(retry_after(i64::MAX), MAX_RETRY_AFTER);
+    }
+}
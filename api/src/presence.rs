@@ -0,0 +1,287 @@
+//! Server owned presence of group members.
+//!
+//! Clients report what they are doing on the `{group_id}-presence` channel, the
+//! websocket endpoint attributes each event to its authenticated connection and
+//! the [`PresenceService`] publishes the result as the read-only
+//! `{group_id}-activity` signal. Entries expire on the server, so clients can
+//! neither spoof other users nor keep stale entries alive. Changes go through the
+//! [`Backplane`](crate::backplane::Backplane), so every instance knows all entries.
+use serde::{Deserialize, Serialize};
+
+/// Name of the channel clients send [`PresenceEvent`]s to.
+pub fn presence_channel_name(group_id: &str) -> String {
+    format!("{group_id}-presence")
+}
+
+/// Name of the read-only signal with the [`GroupPresence`] of a group.
+pub fn activity_signal_name(group_id: &str) -> String {
+    format!("{group_id}-activity")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresenceEvent {
+    /// The chat is open, sent periodically as a heartbeat.
+    Viewing,
+    Typing,
+    StoppedTyping,
+    /// The chat was closed.
+    Left,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PresentUser {
+    pub username: String,
+    pub avatar_url: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupPresence {
+    /// Users with the chat open that aren't typing.
+    pub readers: Vec<PresentUser>,
+    pub writers: Vec<PresentUser>,
+}
+
+#[cfg(feature = "ssr")]
+pub use service::{PresenceService, PresenceUpdate};
+
+#[cfg(feature = "ssr")]
+mod service {
+    use std::{
+        collections::{BTreeSet, HashMap},
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use leptos::prelude::{Owner, Update, provide_context};
+    use leptos_ws::{ReadOnlySignal, WsSignals};
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use super::{GroupPresence, PresenceEvent, PresentUser, activity_signal_name};
+    use crate::backplane::{Backplane, BackplaneEvent};
+
+    /// A change of presence, applied by every instance.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub enum PresenceUpdate {
+        Record {
+            group_id: Uuid,
+            connection_id: String,
+            user: PresentUser,
+            event: PresenceEvent,
+        },
+        Disconnect {
+            connection_id: String,
+        },
+        RemoveUser {
+            group_id: Uuid,
+            username: String,
+        },
+    }
+
+    /// Clients send [`PresenceEvent::Viewing`] more often than this.
+    const VIEWING_TTL: Duration = Duration::from_secs(30);
+    const TYPING_TTL: Duration = Duration::from_secs(5);
+    const SWEEP_INTERVAL: Duration = Duration::from_secs(2);
+
+    struct Entry {
+        user: PresentUser,
+        seen_until: Instant,
+        typing_until: Option<Instant>,
+    }
+
+    #[derive(Default)]
+    struct Group {
+        /// Entries by websocket connection, a user may have several tabs open.
+        entries: HashMap<String, Entry>,
+        published: GroupPresence,
+    }
+
+    #[derive(Clone)]
+    pub struct PresenceService {
+        server_signals: WsSignals,
+        backplane: Arc<dyn Backplane>,
+        groups: Arc<Mutex<HashMap<Uuid, Group>>>,
+    }
+
+    impl PresenceService {
+        /// Creates the service and starts expiring stale entries in the background.
+        pub fn new(server_signals: WsSignals, backplane: Arc<dyn Backplane>) -> Self {
+            let service = Self {
+                server_signals,
+                backplane,
+                groups: Arc::default(),
+            };
+            tokio::spawn({
+                let service = service.clone();
+                async move {
+                    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+                    loop {
+                        interval.tick().await;
+                        service.sweep();
+                    }
+                }
+            });
+            service
+        }
+
+        /// The activity signal of a group, created if no client asked for it yet.
+        pub fn signal(&self, group_id: Uuid) -> Option<ReadOnlySignal<GroupPresence>> {
+            let owner = Owner::new();
+            owner.with(|| {
+                provide_context(self.server_signals.clone());
+                ReadOnlySignal::new(
+                    &activity_signal_name(&group_id.to_string()),
+                    GroupPresence::default(),
+                )
+                .inspect_err(|error| {
+                    log::error!("Failed to create activity signal of {group_id}: {error:?}")
+                })
+                .ok()
+            })
+        }
+
+        /// Records an event of an authenticated websocket connection.
+        pub fn record(
+            &self,
+            group_id: Uuid,
+            connection_id: &str,
+            user: &PresentUser,
+            event: PresenceEvent,
+        ) {
+            self.backplane
+                .publish(BackplaneEvent::Presence(PresenceUpdate::Record {
+                    group_id,
+                    connection_id: connection_id.to_string(),
+                    user: user.clone(),
+                    event,
+                }));
+        }
+
+        /// Removes a closed connection from every group.
+        pub fn disconnect(&self, connection_id: &str) {
+            self.backplane
+                .publish(BackplaneEvent::Presence(PresenceUpdate::Disconnect {
+                    connection_id: connection_id.to_string(),
+                }));
+        }
+
+        /// Removes a user that left a group.
+        pub fn remove_user(&self, group_id: Uuid, username: &str) {
+            self.backplane
+                .publish(BackplaneEvent::Presence(PresenceUpdate::RemoveUser {
+                    group_id,
+                    username: username.to_string(),
+                }));
+        }
+
+        /// Applies an update published by any instance.
+        pub fn apply(&self, update: PresenceUpdate) {
+            match update {
+                PresenceUpdate::Record {
+                    group_id,
+                    connection_id,
+                    user,
+                    event,
+                } => self.apply_record(group_id, &connection_id, &user, event),
+                PresenceUpdate::Disconnect { connection_id } => {
+                    self.apply_disconnect(&connection_id)
+                }
+                PresenceUpdate::RemoveUser { group_id, username } => {
+                    self.apply_remove_user(group_id, &username)
+                }
+            }
+        }
+
+        fn apply_record(
+            &self,
+            group_id: Uuid,
+            connection_id: &str,
+            user: &PresentUser,
+            event: PresenceEvent,
+        ) {
+            let now = Instant::now();
+            let mut groups = self.groups.lock().expect("presence poisoned");
+            let group = groups.entry(group_id).or_default();
+            match event {
+                PresenceEvent::Left => {
+                    group.entries.remove(connection_id);
+                }
+                event => {
+                    let entry = group
+                        .entries
+                        .entry(connection_id.to_string())
+                        .or_insert_with(|| Entry {
+                            user: user.clone(),
+                            seen_until: now,
+                            typing_until: None,
+                        });
+                    entry.seen_until = now + VIEWING_TTL;
+                    match event {
+                        PresenceEvent::Typing => entry.typing_until = Some(now + TYPING_TTL),
+                        PresenceEvent::StoppedTyping => entry.typing_until = None,
+                        _ => {}
+                    }
+                }
+            }
+            self.publish(group_id, group, now);
+        }
+
+        fn apply_disconnect(&self, connection_id: &str) {
+            let now = Instant::now();
+            let mut groups = self.groups.lock().expect("presence poisoned");
+            for (group_id, group) in groups.iter_mut() {
+                if group.entries.remove(connection_id).is_some() {
+                    self.publish(*group_id, group, now);
+                }
+            }
+        }
+
+        fn apply_remove_user(&self, group_id: Uuid, username: &str) {
+            let now = Instant::now();
+            let mut groups = self.groups.lock().expect("presence poisoned");
+            if let Some(group) = groups.get_mut(&group_id) {
+                group
+                    .entries
+                    .retain(|_, entry| entry.user.username != username);
+                self.publish(group_id, group, now);
+            }
+        }
+
+        fn sweep(&self) {
+            let now = Instant::now();
+            let mut groups = self.groups.lock().expect("presence poisoned");
+            for (group_id, group) in groups.iter_mut() {
+                self.publish(*group_id, group, now);
+            }
+            groups.retain(|_, group| !group.entries.is_empty());
+        }
+
+        /// Expires entries and updates the signal if the presence changed.
+        fn publish(&self, group_id: Uuid, group: &mut Group, now: Instant) {
+            group.entries.retain(|_, entry| entry.seen_until > now);
+            let writers = group
+                .entries
+                .values()
+                .filter(|entry| entry.typing_until.is_some_and(|until| until > now))
+                .map(|entry| entry.user.clone())
+                .collect::<BTreeSet<_>>();
+            let readers = group
+                .entries
+                .values()
+                .map(|entry| entry.user.clone())
+                .filter(|user| !writers.contains(user))
+                .collect::<BTreeSet<_>>();
+            let presence = GroupPresence {
+                readers: readers.into_iter().collect(),
+                writers: writers.into_iter().collect(),
+            };
+            if presence == group.published {
+                return;
+            }
+            group.published = presence.clone();
+            if let Some(signal) = self.signal(group_id) {
+                signal.update(|value| *value = presence);
+            }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Flood control for publishing messages.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use uuid::Uuid;
+
+use crate::config::RateLimitConfig;
+
+/// Buckets are only dropped once there are this many.
+const MAX_BUCKETS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Buckets {
+    buckets: HashMap<(Uuid, Uuid), Bucket>,
+    /// Size at which full buckets are dropped next. Doubles with the buckets
+    /// still in use, so the scan for full ones is paid for by the inserts before it.
+    prune_at: usize,
+}
+
+/// Token buckets per user and group, refilled continuously up to the burst size.
+#[derive(Clone)]
+pub struct RateLimiter {
+    burst: f64,
+    per_second: f64,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            burst: f64::from(config.burst),
+            per_second: f64::from(config.messages_per_minute) / 60.0,
+            buckets: Arc::new(Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                prune_at: MAX_BUCKETS,
+            })),
+        }
+    }
+
+    /// Takes a token of the user in the group.
+    /// # Errors
+    /// Returns how long to wait for the next token if the bucket is empty
+    pub fn check(&self, user_id: Uuid, group_id: Uuid) -> Result<(), Duration> {
+        self.check_at(user_id, group_id, Instant::now())
+    }
+
+    fn check_at(&self, user_id: Uuid, group_id: Uuid, now: Instant) -> Result<(), Duration> {
+        let mut state = self.buckets.lock().expect("rate limiter poisoned");
+        let Buckets { buckets, prune_at } = &mut *state;
+        if buckets.len() >= *prune_at && !buckets.contains_key(&(user_id, group_id)) {
+            // Full buckets behave like missing ones
+            buckets.retain(|_, bucket| self.refill(bucket, now) < self.burst);
+            *prune_at = (buckets.len() * 2).max(MAX_BUCKETS);
+        }
+        let bucket = buckets.entry((user_id, group_id)).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let tokens = self.refill(bucket, now);
+        bucket.updated = now;
+        if tokens >= 1.0 {
+            bucket.tokens = tokens - 1.0;
+            Ok(())
+        } else {
+            bucket.tokens = tokens;
+            Err(Duration::from_secs_f64((1.0 - tokens) / self.per_second))
+        }
+    }
+
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.per_second).min(self.burst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three messages at once, then one a second.
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig {
+            burst: 3,
+            messages_per_minute: 60,
+        })
+    }
+
+    fn bucket_count(limiter: &RateLimiter) -> usize {
+        limiter.buckets.lock().unwrap().buckets.len()
+    }
+
+    #[test]
+    fn allows_a_burst() {
+        let limiter = limiter();
+        let (user, group) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(user, group, now), Ok(()));
+        }
+        assert_eq!(
+            limiter.check_at(user, group, now),
+            Err(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let limiter = limiter();
+        let (user, group) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Instant::now();
+        for _ in 0..3 {
+            limiter.check_at(user, group, now).unwrap();
+        }
+
+        let later = now + Duration::from_millis(500);
+        assert_eq!(
+            limiter.check_at(user, group, later),
+            Err(Duration::from_millis(500))
+        );
+        let later = now + Duration::from_secs(1);
+        assert_eq!(limiter.check_at(user, group, later), Ok(()));
+        assert!(limiter.check_at(user, group, later).is_err());
+
+        // A long pause refills no more than the burst
+        let later = now + Duration::from_secs(3600);
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(user, group, later), Ok(()));
+        }
+        assert!(limiter.check_at(user, group, later).is_err());
+    }
+
+    #[test]
+    fn keeps_users_and_groups_apart() {
+        let limiter = limiter();
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let (lunch, work) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Instant::now();
+        for _ in 0..3 {
+            limiter.check_at(alice, lunch, now).unwrap();
+        }
+        assert!(limiter.check_at(alice, lunch, now).is_err());
+
+        assert_eq!(limiter.check_at(alice, work, now), Ok(()));
+        assert_eq!(limiter.check_at(bob, lunch, now), Ok(()));
+    }
+
+    #[test]
+    fn drops_full_buckets_once_there_are_many() {
+        let limiter = limiter();
+        let group = Uuid::new_v4();
+        let flooder = Uuid::new_v4();
+        let now = Instant::now();
+        for _ in 0..3 {
+            limiter.check_at(flooder, group, now).unwrap();
+        }
+        for _ in 1..MAX_BUCKETS {
+            limiter.check_at(Uuid::new_v4(), group, now).unwrap();
+        }
+        assert_eq!(bucket_count(&limiter), MAX_BUCKETS);
+
+        // One token later the other buckets are full again, the flooder's isn't
+        let later = now + Duration::from_secs(1);
+        limiter.check_at(Uuid::new_v4(), group, later).unwrap();
+        assert_eq!(bucket_count(&limiter), 2);
+        assert_eq!(limiter.check_at(flooder, group, later), Ok(()));
+        assert!(limiter.check_at(flooder, group, later).is_err());
+    }
+
+    #[test]
+    fn prunes_less_often_while_buckets_stay_in_use() {
+        let limiter = limiter();
+        let group = Uuid::new_v4();
+        let now = Instant::now();
+        // Every bucket stays in use, so none can be dropped
+        for _ in 0..MAX_BUCKETS {
+            let user = Uuid::new_v4();
+            for _ in 0..3 {
+                limiter.check_at(user, group, now).unwrap();
+            }
+        }
+        limiter.check_at(Uuid::new_v4(), group, now).unwrap();
+        let prune_at = limiter.buckets.lock().unwrap().prune_at;
+        assert_eq!(prune_at, 2 * MAX_BUCKETS);
+        assert_eq!(bucket_count(&limiter), MAX_BUCKETS + 1);
+    }
+}
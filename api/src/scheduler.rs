@@ -0,0 +1,85 @@
+//! Sending of scheduled messages.
+//!
+//! Due messages are claimed before they are sent, so several server instances
+//! can run the scheduler without sending a message twice. They go through
+//! [`publish`] like any other message, under the id of the scheduled message,
+//! which makes sending one again after a crash a no-op.
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{
+    AppState,
+    domain::scheduled_message::ScheduledMessage,
+    server_fn::chat::{ChatError, publish},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Messages sent per poll.
+const BATCH_SIZE: i64 = 20;
+/// How long a scheduler has to send a message before others may try.
+const CLAIM_DURATION: Duration = Duration::from_secs(60);
+/// Delay before retrying a message that failed for reasons other than the rate limit.
+const RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Sends due messages in the background for as long as the server runs.
+pub fn spawn(state: AppState) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let due = match state
+                .scheduled_message_repository
+                .due(Utc::now(), BATCH_SIZE)
+                .await
+            {
+                Ok(due) => due,
+                Err(error) => {
+                    log::error!("Failed to load due scheduled messages: {error}");
+                    continue;
+                }
+            };
+            for message in due {
+                if let Err(error) = send(&state, message).await {
+                    log::error!("Failed to send scheduled message: {error}");
+                }
+            }
+        }
+    });
+}
+
+async fn send(state: &AppState, message: ScheduledMessage) -> Result<(), sqlx::Error> {
+    let repository = &state.scheduled_message_repository;
+    let claimed_until = Utc::now()
+        + chrono::Duration::from_std(CLAIM_DURATION).expect("claim duration is in range");
+    if !repository.claim(&message, claimed_until).await? {
+        return Ok(());
+    }
+    let user = state.user_repository.get_by_id(message.user_id).await?;
+    let result = publish(
+        state,
+        user.id,
+        user.username,
+        message.group_id.to_string(),
+        message.content,
+        Some(message.id.to_string()),
+    )
+    .await;
+    match result {
+        Ok(()) => repository.finish(message.id).await,
+        Err(ChatError::SlowDown { retry_after_ms }) => {
+            let send_at = Utc::now()
+                + chrono::Duration::milliseconds(i64::try_from(retry_after_ms).unwrap_or(i64::MAX));
+            repository.reschedule(message.id, send_at).await
+        }
+        Err(ChatError::ServerFnError(error)) => {
+            log::error!("Failed to send scheduled message {}: {error}", message.id);
+            let send_at = Utc::now() + chrono::Duration::from_std(RETRY_AFTER).unwrap_or_default();
+            repository.reschedule(message.id, send_at).await
+        }
+        // The sender left the group or the message became invalid, it can't ever be sent
+        Err(error) => {
+            log::warn!("Dropping scheduled message {}: {error}", message.id);
+            repository.finish(message.id).await
+        }
+    }
+}
@@ -1,6 +1,15 @@
+pub mod blocks;
 pub mod chat;
+pub mod commands;
 pub mod groups;
 pub mod login;
 pub mod logout;
+pub mod oidc;
+pub mod polls;
+pub mod profile;
+pub mod scheduled;
+pub mod sessions;
 pub mod settings;
 pub mod signup;
+pub mod tokens;
+pub mod webhooks;
@@ -0,0 +1,214 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use leptos::{prelude::*, server_fn::codec::JsonEncoding};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockedUserInfo {
+    /// What live messages are filtered by, like the server filters history.
+    pub user_id: String,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub blocked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum BlockError {
+    Unauthorized,
+    NotFound,
+    CannotBlockSelf,
+    ServerFnError(ServerFnErrorErr),
+}
+
+impl Display for BlockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockError::Unauthorized => write!(f, "You need to be logged in"),
+            BlockError::NotFound => write!(f, "No user with this name"),
+            BlockError::CannotBlockSelf => write!(f, "You can't block yourself"),
+            BlockError::ServerFnError(err) => write!(f, "Server error: {err}"),
+        }
+    }
+}
+
+impl FromStr for BlockError {
+    type Err = ServerFnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Unauthorized" => Ok(BlockError::Unauthorized),
+            "NotFound" => Ok(BlockError::NotFound),
+            "CannotBlockSelf" => Ok(BlockError::CannotBlockSelf),
+            _ => Err(ServerFnError::ServerError(s.into())),
+        }
+    }
+}
+
+impl FromServerFnError for BlockError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        BlockError::ServerFnError(value)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ServerFnError> for BlockError {
+    fn from(value: ServerFnError) -> Self {
+        BlockError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for BlockError {
+    fn from(value: sqlx::Error) -> Self {
+        match value {
+            sqlx::Error::RowNotFound => BlockError::NotFound,
+            value => BlockError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string())),
+        }
+    }
+}
+
+/// Hides all messages of `username` from the current user.
+#[server]
+pub async fn block_user(username: String) -> Result<(), BlockError> {
+    use crate::AppState;
+    use crate::auth::get_user;
+    use crate::domain::block::UserBlock;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let Some(user) = get_user().await? else {
+        return Err(BlockError::Unauthorized);
+    };
+    let blocked = state.user_repository.get_by_username(username).await?;
+    if blocked.id == user.id {
+        return Err(BlockError::CannotBlockSelf);
+    }
+    state
+        .block_repository
+        .create(UserBlock::new(user.id, blocked.id))
+        .await?;
+    Ok(())
+}
+
+#[server]
+pub async fn unblock_user(username: String) -> Result<(), BlockError> {
+    use crate::AppState;
+    use crate::auth::get_user;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let Some(user) = get_user().await? else {
+        return Err(BlockError::Unauthorized);
+    };
+    let blocked = state.user_repository.get_by_username(username).await?;
+    state.block_repository.delete(user.id, blocked.id).await?;
+    Ok(())
+}
+
+#[server]
+pub async fn list_blocked_users() -> Result<Vec<BlockedUserInfo>, BlockError> {
+    use crate::AppState;
+    use crate::auth::get_user;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let Some(user) = get_user().await? else {
+        return Err(BlockError::Unauthorized);
+    };
+    let blocked = state.block_repository.list_blocked(user.id).await?;
+    Ok(blocked
+        .into_iter()
+        .map(|blocked| BlockedUserInfo {
+            user_id: blocked.user_id.to_string(),
+            username: blocked.username,
+            display_name: blocked.display_name,
+            blocked_at: blocked.blocked_at,
+        })
+        .collect())
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        AppState,
+        backplane::BackplaneEvent,
+        config::Config,
+        domain::{block::UserBlock, group::Group, message::Message},
+        server_fn::chat::{ChatChannelMessages, SentChatMessage, broadcast},
+        test_support,
+    };
+
+    /// Broadcasts the message and returns what the clients of the group receive.
+    async fn live(state: &AppState, message: &Message, username: &str) -> SentChatMessage {
+        let mut events = state.backplane.subscribe();
+        broadcast(state, message, username.to_string(), None)
+            .await
+            .unwrap();
+        loop {
+            if let BackplaneEvent::Chat {
+                message: ChatChannelMessages::NewMessage(sent),
+                ..
+            } = events.recv().await.unwrap()
+            {
+                return sent;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn history_and_live_messages_hide_the_same_senders() {
+        let state = test_support::state(Config::default()).await;
+        let alice = test_support::user(&state, "alice").await;
+        let bob = test_support::user(&state, "bob").await;
+        let spam_bot = test_support::user(&state, "webhook-spam").await;
+        let ci_bot = test_support::user(&state, "webhook-ci").await;
+        let group_id = state
+            .group_repository
+            .create_group(Group::new("builds".to_string()))
+            .await
+            .unwrap();
+        for blocked in [bob, spam_bot] {
+            state
+                .block_repository
+                .create(UserBlock::new(alice, blocked))
+                .await
+                .unwrap();
+        }
+        let from_bob = Message::new(group_id, bob, "Hi".to_string());
+        let mut from_spam = Message::new(group_id, spam_bot, "Buy now".to_string());
+        from_spam.sender_name = Some("Deals".to_string());
+        // A webhook of someone else may well be named like a blocked user
+        let mut from_ci = Message::new(group_id, ci_bot, "Build passed".to_string());
+        from_ci.sender_name = Some("bob".to_string());
+        for message in [&from_bob, &from_spam, &from_ci] {
+            state
+                .message_repository
+                .create(message.clone())
+                .await
+                .unwrap();
+        }
+
+        let history = state
+            .message_repository
+            .get_by_group_paginated(group_id, alice, 0, 10)
+            .await
+            .unwrap();
+        let shown: Vec<_> = history.iter().map(|message| message.id).collect();
+        assert_eq!(shown, [from_ci.id]);
+
+        let blocked: HashSet<String> = state
+            .block_repository
+            .list_blocked(alice)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|blocked| blocked.user_id.to_string())
+            .collect();
+        assert!(live(&state, &from_bob, "bob").await.is_from_any(&blocked));
+        assert!(
+            live(&state, &from_spam, "Deals")
+                .await
+                .is_from_any(&blocked)
+        );
+        assert!(!live(&state, &from_ci, "bob").await.is_from_any(&blocked));
+    }
+}
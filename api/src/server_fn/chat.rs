@@ -1,55 +1,363 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    str::FromStr,
+};
 
 use chrono::{DateTime, Utc};
-use leptos::prelude::*;
+use leptos::{prelude::*, server_fn::codec::JsonEncoding};
 use serde::{Deserialize, Serialize};
 
+pub use crate::domain::message_content::{
+    MAX_MESSAGE_LENGTH, MessageContent, MessageContentError,
+};
+use crate::server_fn::polls::{ChatPoll, PollResults};
+
 #[derive(Clone, Serialize, Debug, Deserialize)]
 pub struct SentChatMessage {
+    pub id: String,
     pub text: String,
     pub time: DateTime<Utc>,
     pub username: String,
+    /// Id of the user that sent the message, or that owns the webhook.
+    #[serde(default)]
+    pub user_id: String,
+    /// Webhook messages are never shown as sent by the viewer.
+    #[serde(default)]
+    pub is_webhook: bool,
+    /// Set if the message is a poll asking `text`.
+    #[serde(default)]
+    pub poll: Option<ChatPoll>,
+}
+
+impl SentChatMessage {
+    /// Whether the sender is one of `blocked_user_ids`. Usernames can change and webhooks
+    /// post under names of their choosing, so only the id is compared.
+    pub fn is_from_any(&self, blocked_user_ids: &HashSet<String>) -> bool {
+        blocked_user_ids.contains(&self.user_id)
+    }
 }
 
 #[derive(Clone, Serialize, Debug, Deserialize)]
 pub enum ChatChannelMessages {
     NewMessage(SentChatMessage),
+    /// Someone voted in a poll of the group, or it was closed.
+    PollUpdated(PollResults),
+    /// A message was removed from the group, e.g. because it expired.
+    Deleted { message_id: String },
+    /// The page linked in a message was fetched after the message was sent.
+    LinkPreview {
+        message_id: String,
+        preview: LinkPreview,
+    },
+}
+
+/// Metadata of the first page linked in a message.
+#[derive(Clone, Serialize, Debug, Deserialize, PartialEq, Eq, Hash)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+}
+
+/// Channel of every websocket connection for [`ChatCommand`]s, which the
+/// server answers on the same connection only.
+pub const COMMAND_CHANNEL: &str = "chat-commands";
+
+#[derive(Clone, Serialize, Debug, Deserialize)]
+pub enum ChatCommand {
+    /// Same as [`publish_message`], answered with [`ChatCommand::Ack`].
+    Publish {
+        group_id: String,
+        text: String,
+        message_id: String,
+    },
+    Ack {
+        message_id: String,
+        result: Result<(), ChatError>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum ChatError {
+    Unauthorized,
+    InvalidGroupId,
+    InvalidMessageId,
+    NotAMember,
+    EmptyMessage,
+    MessageTooLong,
+    /// Too many messages were sent to the group, the next one is accepted in
+    /// `retry_after_ms`.
+    SlowDown {
+        retry_after_ms: u64,
+    },
+    ServerFnError(ServerFnErrorErr),
 }
 
+impl Display for ChatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChatError::Unauthorized => write!(f, "You need to be logged in"),
+            ChatError::InvalidGroupId => write!(f, "Invalid group id"),
+            ChatError::InvalidMessageId => write!(f, "Invalid message id"),
+            ChatError::NotAMember => write!(f, "You are not a member of this group"),
+            ChatError::EmptyMessage => write!(f, "Messages can't be empty"),
+            ChatError::MessageTooLong => write!(
+                f,
+                "Messages can't be longer than {MAX_MESSAGE_LENGTH} characters"
+            ),
+            ChatError::SlowDown { retry_after_ms } => write!(
+                f,
+                "You are sending messages too fast, try again in {}s",
+                retry_after_ms.div_ceil(1000)
+            ),
+            ChatError::ServerFnError(err) => write!(f, "Server error: {err}"),
+        }
+    }
+}
+
+impl FromStr for ChatError {
+    type Err = ServerFnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Unauthorized" => Ok(ChatError::Unauthorized),
+            "InvalidGroupId" => Ok(ChatError::InvalidGroupId),
+            "InvalidMessageId" => Ok(ChatError::InvalidMessageId),
+            "NotAMember" => Ok(ChatError::NotAMember),
+            "EmptyMessage" => Ok(ChatError::EmptyMessage),
+            "MessageTooLong" => Ok(ChatError::MessageTooLong),
+            _ => Err(ServerFnError::ServerError(s.into())),
+        }
+    }
+}
+
+impl From<MessageContentError> for ChatError {
+    fn from(value: MessageContentError) -> Self {
+        match value {
+            MessageContentError::Empty => ChatError::EmptyMessage,
+            MessageContentError::TooLong => ChatError::MessageTooLong,
+        }
+    }
+}
+
+impl FromServerFnError for ChatError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        ChatError::ServerFnError(value)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ServerFnError> for ChatError {
+    fn from(value: ServerFnError) -> Self {
+        ChatError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for ChatError {
+    fn from(value: sqlx::Error) -> Self {
+        ChatError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+    }
+}
+
+/// Sends a message to a group.
+///
+/// Clients may choose the id of the message. Sending the same id again has no effect,
+/// so a message whose result got lost can safely be retried. Connected clients send
+/// [`ChatCommand::Publish`] over the websocket instead, this is the fallback.
 #[server]
-pub async fn publish_message(group_id: String, message: String) -> Result<(), ServerFnError> {
+pub async fn publish_message(
+    group_id: String,
+    message: String,
+    message_id: Option<String>,
+) -> Result<(), ChatError> {
     use crate::AppState;
-    use crate::domain::message::Message;
-    use uuid::Uuid;
     let state = use_context::<AppState>().expect("AppState not found");
-    use crate::auth::get_user;
-    let user = get_user().await?;
+    use crate::auth::get_user_with_scope;
+    use crate::server_fn::tokens::TokenScope;
+    let user = get_user_with_scope(TokenScope::MessagesWrite).await?;
     let Some(user) = user else {
-        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+        return Err(ChatError::Unauthorized);
     };
+    publish(&state, user.id, user.username, group_id, message, message_id).await
+}
+
+/// A message id that already exists was sent before, unless it belongs to
+/// someone else's message.
+#[cfg(feature = "ssr")]
+fn check_resent(
+    existing: &crate::domain::message::Message,
+    user_id: uuid::Uuid,
+    group_id: uuid::Uuid,
+) -> Result<(), ChatError> {
+    if existing.user_id != user_id || existing.group_id != group_id {
+        return Err(ChatError::InvalidMessageId);
+    }
+    Ok(())
+}
+
+/// Stores and broadcasts a message of an authenticated user.
+#[cfg(feature = "ssr")]
+pub(crate) async fn publish(
+    state: &crate::AppState,
+    user_id: uuid::Uuid,
+    username: String,
+    group_id: String,
+    text: String,
+    message_id: Option<String>,
+) -> Result<(), ChatError> {
+    if let Some(message) = store(state, user_id, group_id, text, message_id).await? {
+        broadcast(state, &message, username, None).await?;
+    }
+    Ok(())
+}
+
+/// Stores a message of an authenticated user, `None` if it was sent before and
+/// mustn't be broadcast again.
+#[cfg(feature = "ssr")]
+pub(crate) async fn store(
+    state: &crate::AppState,
+    user_id: uuid::Uuid,
+    group_id: String,
+    text: String,
+    message_id: Option<String>,
+) -> Result<Option<crate::domain::message::Message>, ChatError> {
+    use crate::domain::message::Message;
+    use uuid::Uuid;
+    let content = text.parse::<MessageContent>()?;
     let Ok(group_id_uuid) = group_id.parse() else {
-        return Err(ServerFnError::ServerError("Invalid group id".to_string()));
-    };
-    let message = Message::new(group_id_uuid, user.id, message);
-    let message_id = state.message_repository.create(message.clone()).await?;
-    let Ok(new_messages) = leptos_ws::ChannelSignal::<ChatChannelMessages>::new(&group_id) else {
-        return Err(ServerFnError::ServerError(
-            "Failed to create channel signal".to_string(),
-        ));
+        return Err(ChatError::InvalidGroupId);
     };
-    new_messages.send_message(ChatChannelMessages::NewMessage(SentChatMessage {
-        text: message.content,
-        time: message.created_at,
-        username: user.username,
-    }));
+    if !state
+        .group_repository
+        .is_member(group_id_uuid, user_id)
+        .await?
+    {
+        return Err(ChatError::NotAMember);
+    }
+    let mut message = Message::new(group_id_uuid, user_id, content.into());
+    if let Some(message_id) = message_id {
+        let Ok(message_id) = message_id.parse::<Uuid>() else {
+            return Err(ChatError::InvalidMessageId);
+        };
+        message.id = message_id;
+        // Retries of a delivered message must not count against the limit
+        match state.message_repository.get_by_id(message_id).await {
+            Ok(existing) => {
+                return check_resent(&existing, user_id, group_id_uuid).map(|()| None);
+            }
+            Err(sqlx::Error::RowNotFound) => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    check_rate_limit(state, user_id, group_id_uuid)?;
+    if !state
+        .message_repository
+        .create_if_absent(message.clone())
+        .await?
+    {
+        let existing = state.message_repository.get_by_id(message.id).await?;
+        return check_resent(&existing, user_id, group_id_uuid).map(|()| None);
+    }
+    Ok(Some(message))
+}
+
+/// Takes a token of the user's flood control in the group.
+#[cfg(feature = "ssr")]
+pub(crate) fn check_rate_limit(
+    state: &crate::AppState,
+    user_id: uuid::Uuid,
+    group_id: uuid::Uuid,
+) -> Result<(), ChatError> {
+    state
+        .message_limiter
+        .check(user_id, group_id)
+        .map_err(|retry_after| ChatError::SlowDown {
+            retry_after_ms: u64::try_from(retry_after.as_millis()).unwrap_or(u64::MAX),
+        })
+}
+
+/// Pushes a stored message to the group's channel and the group lists of its members.
+#[cfg(feature = "ssr")]
+pub(crate) async fn broadcast(
+    state: &crate::AppState,
+    message: &crate::domain::message::Message,
+    username: String,
+    poll: Option<ChatPoll>,
+) -> Result<(), sqlx::Error> {
+    use crate::backplane::BackplaneEvent;
+    use crate::server_fn::webhooks::WebhookEvent;
+    use crate::user_events::UserEvent;
+    let group_id = message.group_id.to_string();
+    let unfurl = poll.is_none();
+    state.backplane.publish(BackplaneEvent::Chat {
+        group_id: group_id.clone(),
+        message: ChatChannelMessages::NewMessage(SentChatMessage {
+            id: message.id.to_string(),
+            text: message.content.clone(),
+            time: message.created_at,
+            username: username.clone(),
+            user_id: message.user_id.to_string(),
+            is_webhook: message.sender_name.is_some(),
+            poll,
+        }),
+    });
+    // Published after the message, which clients need to attach the preview to
+    if unfurl {
+        crate::link_previews::spawn_unfurl(state, message);
+    }
+    for member in state.group_repository.list_members(message.group_id).await? {
+        state.user_events.send(
+            member.user_id,
+            UserEvent::MessagePreview {
+                group_id: group_id.clone(),
+                text: message.content.clone(),
+                time: message.created_at,
+            },
+        );
+    }
+    crate::outgoing_webhooks::enqueue(
+        state,
+        message.group_id,
+        WebhookEvent::MessageCreated,
+        serde_json::json!({
+            "id": message.id,
+            "user_id": message.user_id,
+            "username": username,
+            "text": message.content,
+            "time": message.created_at,
+        }),
+    )
+    .await;
     Ok(())
 }
 
 #[derive(Clone, Serialize, Debug, Deserialize, PartialEq, Eq, Hash)]
 pub struct ChatMessage {
+    pub id: String,
     pub text: String,
     pub time: DateTime<Utc>,
     pub sender: ChatSender,
+    /// Only messages that are still being sent by this client aren't delivered.
+    #[serde(default)]
+    pub delivery: Delivery,
+    #[serde(default)]
+    pub poll: Option<ChatPoll>,
+    #[serde(default)]
+    pub link_preview: Option<LinkPreview>,
+}
+
+#[derive(Clone, Copy, Serialize, Debug, Default, Deserialize, PartialEq, Eq, Hash)]
+pub enum Delivery {
+    #[default]
+    Delivered,
+    Pending,
+    Failed,
 }
 
 #[derive(Clone, Serialize, Debug, Deserialize, PartialEq, Eq, Hash)]
@@ -63,18 +371,17 @@ pub async fn fetch_messages(
     group_id: String,
     offset: i64,
     limit: i64,
-) -> Result<Vec<ChatMessage>, ServerFnError> {
+) -> Result<Vec<ChatMessage>, ChatError> {
     use crate::AppState;
-    use crate::domain::message::Message;
-    use uuid::Uuid;
     let state = use_context::<AppState>().expect("AppState not found");
-    use crate::auth::get_user;
-    let user = get_user().await?;
+    use crate::auth::get_user_with_scope;
+    use crate::server_fn::tokens::TokenScope;
+    let user = get_user_with_scope(TokenScope::MessagesRead).await?;
     let Some(user) = user else {
-        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+        return Err(ChatError::Unauthorized);
     };
     let Ok(group_id_uuid) = group_id.parse() else {
-        return Err(ServerFnError::ServerError("Invalid group id".to_string()));
+        return Err(ChatError::InvalidGroupId);
     };
 
     // Membership check
@@ -83,37 +390,110 @@ pub async fn fetch_messages(
         .is_member(group_id_uuid, user.id)
         .await?;
     if !is_member {
-        return Err(ServerFnError::ServerError("Forbidden".to_string()));
+        return Err(ChatError::NotAMember);
     }
 
     let messages = state
         .message_repository
-        .get_by_group_paginated(group_id_uuid, offset, limit)
+        .get_by_group_paginated(group_id_uuid, user.id, offset, limit)
+        .await?;
+    to_chat_messages(&state, &user, messages).await
+}
+
+/// Most messages returned when catching up, a full page means clients should ask
+/// again after its newest message.
+pub const CATCH_UP_LIMIT: i64 = 200;
+
+/// Messages of a group sent after `after_id`, oldest first, used to catch up after reconnecting.
+#[server]
+pub async fn fetch_messages_after(
+    group_id: String,
+    after_id: String,
+) -> Result<Vec<ChatMessage>, ChatError> {
+    use crate::AppState;
+    let state = use_context::<AppState>().expect("AppState not found");
+    use crate::auth::get_user_with_scope;
+    use crate::server_fn::tokens::TokenScope;
+    let user = get_user_with_scope(TokenScope::MessagesRead).await?;
+    let Some(user) = user else {
+        return Err(ChatError::Unauthorized);
+    };
+    let Ok(group_id_uuid) = group_id.parse() else {
+        return Err(ChatError::InvalidGroupId);
+    };
+    if !state
+        .group_repository
+        .is_member(group_id_uuid, user.id)
+        .await?
+    {
+        return Err(ChatError::NotAMember);
+    }
+    let Ok(after_id) = after_id.parse() else {
+        return Err(ChatError::InvalidMessageId);
+    };
+    let after = match state.message_repository.get_by_id(after_id).await {
+        Ok(after) if after.group_id == group_id_uuid => after,
+        Ok(_) | Err(sqlx::Error::RowNotFound) => return Err(ChatError::InvalidMessageId),
+        Err(error) => return Err(error.into()),
+    };
+    let messages = state
+        .message_repository
+        .get_by_group_after(group_id_uuid, user.id, &after, CATCH_UP_LIMIT)
         .await?;
+    to_chat_messages(&state, &user, messages).await
+}
 
-    // You may need to fetch usernames for each message.user_id if not available in Message
-    // For now, assuming you can get username from user_id (pseudo-code)
+#[cfg(feature = "ssr")]
+async fn to_chat_messages(
+    state: &crate::AppState,
+    viewer: &crate::domain::user::User,
+    messages: Vec<crate::domain::message::Message>,
+) -> Result<Vec<ChatMessage>, ChatError> {
+    use crate::server_fn::polls::chat_poll_with_votes;
+    use uuid::Uuid;
     let mut result = Vec::new();
     let mut username_cache: HashMap<Uuid, String> = HashMap::new();
+    let message_ids: Vec<Uuid> = messages.iter().map(|msg| msg.id).collect();
+    let polls = state.poll_repository.get_by_message_ids(&message_ids).await?;
+    let poll_ids: Vec<Uuid> = polls.keys().copied().collect();
+    let mut votes = state
+        .poll_repository
+        .list_votes_by_poll_ids(&poll_ids)
+        .await?;
+    let texts: Vec<&str> = messages.iter().map(|msg| msg.content.as_str()).collect();
+    let link_previews = crate::link_previews::cached(state, &texts).await?;
 
-    for msg in messages {
-        // Replace this with actual username lookup if needed
-        let username = if let Some(name) = username_cache.get(&msg.user_id) {
-            name.clone()
+    for (msg, link_preview) in messages.into_iter().zip(link_previews) {
+        // Webhooks post under their own name, never as the viewer
+        let sender = if let Some(name) = msg.sender_name {
+            ChatSender::Received(name)
         } else {
-            // Fetch username from DB
-            let user = state.user_repository.get_by_id(msg.user_id).await?;
-            username_cache.insert(msg.user_id, user.username.clone());
-            user.username
+            let sender = if let Some(name) = username_cache.get(&msg.user_id) {
+                name.clone()
+            } else {
+                let user = state.user_repository.get_by_id(msg.user_id).await?;
+                username_cache.insert(msg.user_id, user.username.clone());
+                user.username
+            };
+            if msg.user_id == viewer.id {
+                ChatSender::Sent
+            } else {
+                ChatSender::Received(sender)
+            }
         };
+        let poll = polls.get(&msg.id).map(|poll| {
+            let votes = votes.remove(&poll.message_id).unwrap_or_default();
+            chat_poll_with_votes(poll, &votes, viewer.id)
+        });
+        let link_preview = link_preview.filter(|_| poll.is_none());
         result.push(ChatMessage {
+            id: msg.id.to_string(),
             text: msg.content,
             time: msg.created_at,
-            sender: if username == user.username {
-                ChatSender::Sent
-            } else {
-                ChatSender::Received(username)
-            },
+            sender,
+            delivery: Delivery::Delivered,
+            poll,
+            link_preview,
         });
     }
     Ok(result)
@@ -0,0 +1,221 @@
+use std::{fmt::Display, str::FromStr};
+
+use leptos::{prelude::*, server_fn::codec::JsonEncoding};
+use serde::{Deserialize, Serialize};
+
+use crate::server_fn::{chat::ChatError, groups::GroupError};
+
+/// A slash command offered by the composer.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandInfo {
+    pub name: String,
+    /// Hint of the arguments, e.g. `<action>`.
+    pub args: String,
+    pub description: String,
+}
+
+/// Splits `/name args` into the name and the trimmed arguments, `None` if the
+/// text is not a command.
+pub fn parse_command(text: &str) -> Option<(&str, &str)> {
+    let rest = text.trim_start().strip_prefix('/')?;
+    let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    if name.is_empty() || name.starts_with('/') {
+        return None;
+    }
+    Some((name, args.trim()))
+}
+
+/// Text starting with `//` is sent as a message starting with `/`.
+pub fn unescape_command(text: &str) -> &str {
+    match text.trim_start().strip_prefix("//") {
+        Some(_) => &text.trim_start()[1..],
+        None => text,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum CommandError {
+    Unauthorized,
+    InvalidGroupId,
+    NotAMember,
+    UnknownCommand(String),
+    /// The arguments didn't fit, with the expected ones.
+    Usage(String),
+    /// The command couldn't be carried out, with the reason.
+    Failed(String),
+    ServerFnError(ServerFnErrorErr),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::Unauthorized => write!(f, "You need to be logged in"),
+            CommandError::InvalidGroupId => write!(f, "Invalid group id"),
+            CommandError::NotAMember => write!(f, "You are not a member of this group"),
+            CommandError::UnknownCommand(name) => write!(f, "Unknown command /{name}"),
+            CommandError::Usage(usage) => write!(f, "Usage: {usage}"),
+            CommandError::Failed(reason) => write!(f, "{reason}"),
+            CommandError::ServerFnError(err) => write!(f, "Server error: {err}"),
+        }
+    }
+}
+
+impl FromStr for CommandError {
+    type Err = ServerFnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Unauthorized" => Ok(CommandError::Unauthorized),
+            "InvalidGroupId" => Ok(CommandError::InvalidGroupId),
+            "NotAMember" => Ok(CommandError::NotAMember),
+            _ => Err(ServerFnError::ServerError(s.into())),
+        }
+    }
+}
+
+impl FromServerFnError for CommandError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        CommandError::ServerFnError(value)
+    }
+}
+
+impl From<ChatError> for CommandError {
+    fn from(value: ChatError) -> Self {
+        match value {
+            ChatError::Unauthorized => CommandError::Unauthorized,
+            ChatError::InvalidGroupId => CommandError::InvalidGroupId,
+            ChatError::NotAMember => CommandError::NotAMember,
+            ChatError::ServerFnError(err) => CommandError::ServerFnError(err),
+            error => CommandError::Failed(error.to_string()),
+        }
+    }
+}
+
+impl From<GroupError> for CommandError {
+    fn from(value: GroupError) -> Self {
+        match value {
+            GroupError::Unauthorized => CommandError::Unauthorized,
+            GroupError::NotAMember => CommandError::NotAMember,
+            GroupError::ServerFnError(err) => CommandError::ServerFnError(err),
+            error => CommandError::Failed(error.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ServerFnError> for CommandError {
+    fn from(value: ServerFnError) -> Self {
+        CommandError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for CommandError {
+    fn from(value: sqlx::Error) -> Self {
+        CommandError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+    }
+}
+
+#[server]
+pub async fn list_commands() -> Result<Vec<CommandInfo>, CommandError> {
+    use crate::AppState;
+    let state = use_context::<AppState>().expect("AppState not found");
+    Ok(state.commands.list())
+}
+
+/// Runs a slash command in a group, returns the reply only the user sees.
+#[server]
+pub async fn run_command(group_id: String, text: String) -> Result<Option<String>, CommandError> {
+    use crate::AppState;
+    let state = use_context::<AppState>().expect("AppState not found");
+    use crate::auth::get_user_with_scope;
+    use crate::server_fn::tokens::TokenScope;
+    let user = get_user_with_scope(TokenScope::MessagesWrite).await?;
+    let Some(user) = user else {
+        return Err(CommandError::Unauthorized);
+    };
+    run(&state, &user, &group_id, &text).await
+}
+
+/// Commands count against the flood control like messages. The token is taken
+/// before the command runs, so a command that changed something isn't then
+/// refused to post about it.
+#[cfg(feature = "ssr")]
+pub(crate) async fn run(
+    state: &crate::AppState,
+    user: &crate::domain::user::User,
+    group_id: &str,
+    text: &str,
+) -> Result<Option<String>, CommandError> {
+    use crate::commands::{CommandContext, CommandOutcome};
+    use crate::domain::message::Message;
+    use crate::domain::message_content::MessageContent;
+    use crate::server_fn::chat::{broadcast, check_rate_limit};
+    let Ok(group_id) = group_id.parse() else {
+        return Err(CommandError::InvalidGroupId);
+    };
+    if !state.group_repository.is_member(group_id, user.id).await? {
+        return Err(CommandError::NotAMember);
+    }
+    let Some((name, args)) = parse_command(text) else {
+        return Err(CommandError::UnknownCommand(String::new()));
+    };
+    let Some(command) = state.commands.get(name) else {
+        return Err(CommandError::UnknownCommand(name.to_string()));
+    };
+    check_rate_limit(state, user.id, group_id)?;
+    let context = CommandContext {
+        state,
+        user_id: user.id,
+        username: user.username.clone(),
+        group_id,
+    };
+    match command.run(&context, args).await? {
+        CommandOutcome::Post(text) => {
+            let content = text.parse::<MessageContent>().map_err(ChatError::from)?;
+            let message = Message::new(group_id, user.id, content.into());
+            state.message_repository.create(message.clone()).await?;
+            broadcast(state, &message, user.username.clone(), None).await?;
+            Ok(None)
+        }
+        CommandOutcome::Reply(reply) => Ok(Some(reply)),
+        CommandOutcome::Done => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_name_and_arguments() {
+        assert_eq!(parse_command("/me waves"), Some(("me", "waves")));
+        assert_eq!(
+            parse_command("  /topic  Lunch at 12 \n"),
+            Some(("topic", "Lunch at 12"))
+        );
+        assert_eq!(parse_command("/invite"), Some(("invite", "")));
+        assert_eq!(parse_command("/shrug\tfine"), Some(("shrug", "fine")));
+    }
+
+    #[test]
+    fn ignores_what_is_not_a_command() {
+        for text in ["hello", "", "/", "/ me", "//me waves", "a /me"] {
+            assert_eq!(parse_command(text), None, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn unescapes_double_slashes() {
+        assert_eq!(
+            unescape_command("//me is not a command"),
+            "/me is not a command"
+        );
+        assert_eq!(unescape_command("  //shrug"), "/shrug");
+        assert_eq!(unescape_command("///"), "//");
+        assert_eq!(unescape_command("/me waves"), "/me waves");
+        assert_eq!(unescape_command("a // b"), "a // b");
+    }
+}
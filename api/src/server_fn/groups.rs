@@ -1,6 +1,7 @@
-use std::str::FromStr;
+use std::{fmt::Display, str::FromStr};
 
-use leptos::prelude::*;
+use chrono::{DateTime, Utc};
+use leptos::{prelude::*, server_fn::codec::JsonEncoding};
 use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Group {
@@ -9,21 +10,149 @@ pub struct Group {
     pub avatar_url: String,
     pub last_message: String,
     pub join_code: String,
+    pub topic: Option<String>,
+    /// Seconds after which messages disappear, `None` if they are kept.
+    #[serde(default)]
+    pub message_ttl_secs: Option<i64>,
+    /// Time of the last message, or of the creation of the group.
+    pub last_activity: DateTime<Utc>,
+}
+
+#[cfg(feature = "ssr")]
+impl Group {
+    fn new(
+        group: crate::domain::group::Group,
+        last_message: Option<crate::domain::message::Message>,
+    ) -> Self {
+        Self {
+            id: group.id.to_string(),
+            name: group.name,
+            avatar_url: group
+                .avatar
+                .unwrap_or("https://api.dicebear.com/9.x/glass/svg".to_string()),
+            join_code: group.join_code,
+            topic: group.topic,
+            message_ttl_secs: group.message_ttl_secs,
+            last_activity: last_message
+                .as_ref()
+                .map_or(group.created_at, |message| message.created_at),
+            last_message: last_message
+                .map(|m| m.content)
+                .unwrap_or("No messages yet".to_string()),
+        }
+    }
+}
+
+/// Shortest time messages can be set to disappear after.
+pub const MIN_MESSAGE_TTL_SECS: i64 = 60;
+/// Longest time messages can be set to disappear after, a year.
+pub const MAX_MESSAGE_TTL_SECS: i64 = 365 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum GroupError {
+    Unauthorized,
+    InvalidName,
+    NameTaken,
+    NotFound,
+    AlreadyMember,
+    NotAMember,
+    InvalidMessageTtl,
+    Forbidden,
+    ServerFnError(ServerFnErrorErr),
+}
+
+impl Display for GroupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GroupError::Unauthorized => write!(f, "You need to be logged in"),
+            GroupError::InvalidName => write!(f, "Group names must be between 1 and 64 characters"),
+            GroupError::NameTaken => write!(f, "A group with this name already exists"),
+            GroupError::NotFound => write!(f, "Group not found"),
+            GroupError::AlreadyMember => write!(f, "You are already a member of this group"),
+            GroupError::NotAMember => write!(f, "You are not a member of this group"),
+            GroupError::InvalidMessageTtl => write!(
+                f,
+                "Messages can disappear after between a minute and a year"
+            ),
+            GroupError::Forbidden => write!(f, "Only the group owner can do this"),
+            GroupError::ServerFnError(err) => write!(f, "Server error: {err}"),
+        }
+    }
+}
+
+impl FromStr for GroupError {
+    type Err = ServerFnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Unauthorized" => Ok(GroupError::Unauthorized),
+            "InvalidName" => Ok(GroupError::InvalidName),
+            "NameTaken" => Ok(GroupError::NameTaken),
+            "NotFound" => Ok(GroupError::NotFound),
+            "AlreadyMember" => Ok(GroupError::AlreadyMember),
+            "NotAMember" => Ok(GroupError::NotAMember),
+            "InvalidMessageTtl" => Ok(GroupError::InvalidMessageTtl),
+            "Forbidden" => Ok(GroupError::Forbidden),
+            _ => Err(ServerFnError::ServerError(s.into())),
+        }
+    }
+}
+
+impl FromServerFnError for GroupError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        GroupError::ServerFnError(value)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ServerFnError> for GroupError {
+    fn from(value: ServerFnError) -> Self {
+        GroupError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for GroupError {
+    fn from(value: sqlx::Error) -> Self {
+        match &value {
+            sqlx::Error::Database(error) if error.is_unique_violation() => {
+                if error.message().contains("groups.name") {
+                    GroupError::NameTaken
+                } else if error.message().contains("group_members.") {
+                    GroupError::AlreadyMember
+                } else {
+                    GroupError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+                }
+            }
+            _ => GroupError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string())),
+        }
+    }
 }
 
 #[server]
-pub async fn create_group(name: String, avatar: String) -> Result<(), ServerFnError> {
+pub async fn create_group(name: String, avatar: String) -> Result<(), GroupError> {
     use crate::AppState;
     use crate::domain::group::Group;
+    use crate::user_events::UserEvent;
     let state = use_context::<AppState>().expect("AppState not found");
     use crate::auth::get_user;
     let user = get_user().await?;
     let Some(user) = user else {
-        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+        return Err(GroupError::Unauthorized);
     };
+    let name = name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(GroupError::InvalidName);
+    }
     let group = Group::new_with_avatar(name, avatar);
-    let group_id = state.group_repository.create_group(group).await?;
+    let group_id = state.group_repository.create_group(group.clone()).await?;
     state.group_repository.add_member(group_id, user.id).await?;
+    state.user_events.send(
+        user.id,
+        UserEvent::AddedToGroup(self::Group::new(group, None)),
+    );
     Ok(())
 }
 
@@ -73,57 +202,262 @@ impl AsRef<str> for JoinCode {
 }
 
 #[server]
-pub async fn join_group(join_code: JoinCode) -> Result<(), ServerFnError> {
+pub async fn join_group(join_code: JoinCode) -> Result<(), GroupError> {
     use crate::AppState;
+    use crate::server_fn::webhooks::WebhookEvent;
+    use crate::user_events::UserEvent;
     let state = use_context::<AppState>().expect("AppState not found");
     use crate::auth::get_user;
     let user = get_user().await?;
     let Some(user) = user else {
-        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+        return Err(GroupError::Unauthorized);
     };
     let Some(group) = state
         .group_repository
         .get_by_join_code(join_code.as_ref())
         .await?
     else {
-        return Err(ServerFnError::ServerError("Group not found".to_string()));
+        return Err(GroupError::NotFound);
     };
     if state.group_repository.is_member(group.id, user.id).await? {
-        return Err(ServerFnError::ServerError(
-            "Already a member, username: ".to_string() + &user.username,
-        ));
+        return Err(GroupError::AlreadyMember);
     }
     state.group_repository.add_member(group.id, user.id).await?;
+    let last_message = state.message_repository.get_last_by_group(group.id).await?;
+    let group_id = group.id;
+    state
+        .user_events
+        .send(user.id, UserEvent::AddedToGroup(Group::new(group, last_message)));
+    crate::outgoing_webhooks::enqueue(
+        &state,
+        group_id,
+        WebhookEvent::MemberJoined,
+        serde_json::json!({ "user_id": user.id, "username": user.username }),
+    )
+    .await;
+    Ok(())
+}
+
+/// Leaves a group and stops its live updates on all open connections of the user.
+#[server]
+pub async fn leave_group(group_id: String) -> Result<(), GroupError> {
+    use crate::AppState;
+    let state = use_context::<AppState>().expect("AppState not found");
+    use crate::auth::get_user;
+    let user = get_user().await?;
+    let Some(user) = user else {
+        return Err(GroupError::Unauthorized);
+    };
+    let Ok(group_id) = group_id.parse() else {
+        return Err(GroupError::NotFound);
+    };
+    leave(&state, user.id, &user.username, group_id).await
+}
+
+/// Removes the user from the group, shared by [`leave_group`] and `/leave`.
+#[cfg(feature = "ssr")]
+pub(crate) async fn leave(
+    state: &crate::AppState,
+    user_id: uuid::Uuid,
+    username: &str,
+    group_id: uuid::Uuid,
+) -> Result<(), GroupError> {
+    use crate::backplane::BackplaneEvent;
+    use crate::server_fn::webhooks::WebhookEvent;
+    use crate::user_events::UserEvent;
+    if !state.group_repository.is_member(group_id, user_id).await? {
+        return Err(GroupError::NotAMember);
+    }
+    state.group_repository.remove_member(group_id, user_id).await?;
+    state
+        .backplane
+        .publish(BackplaneEvent::RevokeGroup { user_id, group_id });
+    state.presence.remove_user(group_id, username);
+    state.user_events.send(
+        user_id,
+        UserEvent::RemovedFromGroup {
+            group_id: group_id.to_string(),
+        },
+    );
+    crate::outgoing_webhooks::enqueue(
+        state,
+        group_id,
+        WebhookEvent::MemberLeft,
+        serde_json::json!({ "user_id": user_id, "username": username }),
+    )
+    .await;
     Ok(())
 }
 
+/// Renames a group, its members see the new name right away.
 #[server]
-pub async fn get_groups() -> Result<Vec<Group>, ServerFnError> {
+pub async fn rename_group(group_id: String, name: String) -> Result<(), GroupError> {
     use crate::AppState;
+    use crate::user_events::UserEvent;
     let state = use_context::<AppState>().expect("AppState not found");
     use crate::auth::get_user;
     let user = get_user().await?;
     let Some(user) = user else {
-        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+        return Err(GroupError::Unauthorized);
+    };
+    let Ok(group_id) = group_id.parse() else {
+        return Err(GroupError::NotFound);
+    };
+    if !state.group_repository.is_member(group_id, user.id).await? {
+        return Err(GroupError::NotAMember);
+    }
+    let name = name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(GroupError::InvalidName);
+    }
+    state.group_repository.rename(group_id, &name).await?;
+    for member in state.group_repository.list_members(group_id).await? {
+        state.user_events.send(
+            member.user_id,
+            UserEvent::GroupRenamed {
+                group_id: group_id.to_string(),
+                name: name.clone(),
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Whether the user may change what affects the whole group: the group owner, its
+/// longest-standing member, or a site admin.
+#[cfg(feature = "ssr")]
+pub(crate) async fn is_group_manager(
+    state: &crate::AppState,
+    group_id: uuid::Uuid,
+    user: &crate::domain::user::User,
+) -> Result<bool, sqlx::Error> {
+    Ok(user.is_admin() || state.group_repository.owner(group_id).await? == Some(user.id))
+}
+
+/// Makes messages of a group disappear `ttl_secs` after they were sent,
+/// or keeps them forever if `None`.
+#[server]
+pub async fn set_message_ttl(
+    group_id: String,
+    #[server(default)] ttl_secs: Option<i64>,
+) -> Result<(), GroupError> {
+    use crate::AppState;
+    let state = use_context::<AppState>().expect("AppState not found");
+    use crate::auth::get_user;
+    let user = get_user().await?;
+    let Some(user) = user else {
+        return Err(GroupError::Unauthorized);
+    };
+    change_message_ttl(&state, &user, &group_id, ttl_secs).await
+}
+
+/// Expired messages are deleted for good, so only the group owner or a site admin may
+/// change how long they are kept.
+#[cfg(feature = "ssr")]
+async fn change_message_ttl(
+    state: &crate::AppState,
+    user: &crate::domain::user::User,
+    group_id: &str,
+    ttl_secs: Option<i64>,
+) -> Result<(), GroupError> {
+    use crate::user_events::UserEvent;
+    let Ok(group_id) = group_id.parse() else {
+        return Err(GroupError::NotFound);
+    };
+    if !state.group_repository.is_member(group_id, user.id).await? {
+        return Err(GroupError::NotAMember);
+    }
+    if !is_group_manager(state, group_id, user).await? {
+        return Err(GroupError::Forbidden);
+    }
+    if ttl_secs.is_some_and(|ttl| !(MIN_MESSAGE_TTL_SECS..=MAX_MESSAGE_TTL_SECS).contains(&ttl)) {
+        return Err(GroupError::InvalidMessageTtl);
+    }
+    state
+        .group_repository
+        .set_message_ttl(group_id, ttl_secs)
+        .await?;
+    for member in state.group_repository.list_members(group_id).await? {
+        state.user_events.send(
+            member.user_id,
+            UserEvent::MessageTtlChanged {
+                group_id: group_id.to_string(),
+                ttl_secs,
+            },
+        );
+    }
+    Ok(())
+}
+
+#[server]
+pub async fn get_groups() -> Result<Vec<Group>, GroupError> {
+    use crate::AppState;
+    let state = use_context::<AppState>().expect("AppState not found");
+    use crate::auth::get_user_with_scope;
+    use crate::server_fn::tokens::TokenScope;
+    let user = get_user_with_scope(TokenScope::GroupsRead).await?;
+    let Some(user) = user else {
+        return Err(GroupError::Unauthorized);
     };
     let groups = state
         .group_repository
         .list_user_groups_with_last_message(user.id)
         .await?;
-    Ok(groups
+    let mut groups = groups
         .into_iter()
-        .map(|v| Group {
-            id: v.group.id.to_string(),
-            name: v.group.name,
-            avatar_url: v
-                .group
-                .avatar
-                .unwrap_or("https://api.dicebear.com/9.x/glass/svg".to_string()),
-            join_code: v.group.join_code.clone(),
-            last_message: v
-                .last_message
-                .map(|m| m.content)
-                .unwrap_or("No messages yet".to_string()),
-        })
-        .collect())
+        .map(|v| Group::new(v.group, v.last_message))
+        .collect::<Vec<_>>();
+    // Most recently active first
+    groups.sort_by_key(|group| std::cmp::Reverse(group.last_activity));
+    Ok(groups)
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+    use crate::{config::Config, domain::group::Group as StoredGroup, test_support};
+
+    #[tokio::test]
+    async fn only_the_owner_sets_the_message_ttl() {
+        let state = test_support::state(Config::default()).await;
+        let group_id = state
+            .group_repository
+            .create_group(StoredGroup::new("lunch".to_string()))
+            .await
+            .unwrap();
+        let mut users = Vec::new();
+        for name in ["alice", "bob", "eve"] {
+            let id = test_support::user(&state, name).await;
+            users.push(state.user_repository.get_by_id(id).await.unwrap());
+        }
+        let [alice, bob, eve] = &users[..] else {
+            unreachable!()
+        };
+        for member in [alice, bob] {
+            state
+                .group_repository
+                .add_member(group_id, member.id)
+                .await
+                .unwrap();
+        }
+        let group = group_id.to_string();
+        let day = 24 * 60 * 60;
+
+        let result = change_message_ttl(&state, bob, &group, Some(MIN_MESSAGE_TTL_SECS)).await;
+        assert_eq!(result, Err(GroupError::Forbidden));
+        let result = change_message_ttl(&state, eve, &group, Some(day)).await;
+        assert_eq!(result, Err(GroupError::NotAMember));
+        assert_eq!(
+            state.group_repository.list_message_ttls().await.unwrap(),
+            []
+        );
+
+        change_message_ttl(&state, alice, &group, Some(day))
+            .await
+            .unwrap();
+        assert_eq!(
+            state.group_repository.list_message_ttls().await.unwrap(),
+            [(group_id, day)]
+        );
+    }
 }
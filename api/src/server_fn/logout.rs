@@ -1,10 +1,16 @@
 use leptos::prelude::*;
 #[server]
 pub async fn logout() -> Result<(), ServerFnError> {
+    use crate::AppState;
     use crate::auth::auth;
 
+    let state = use_context::<AppState>().expect("AppState not found");
     let auth = auth().await?;
 
+    state
+        .session_repository
+        .delete_by_session_id(&auth.session.get_session_id())
+        .await?;
     auth.logout_user();
     Ok(())
 }
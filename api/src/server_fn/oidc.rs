@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OidcProviderInfo {
+    pub id: String,
+    pub name: String,
+}
+
+impl OidcProviderInfo {
+    /// Where the browser has to navigate to start signing in with this provider.
+    pub fn login_url(&self, next: &str) -> String {
+        format!("/auth/oidc/{}/login?next={}", self.id, urlencode(next))
+    }
+}
+
+fn urlencode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkedIdentity {
+    pub id: String,
+    pub provider: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[server]
+pub async fn list_oidc_providers() -> Result<Vec<OidcProviderInfo>, ServerFnError> {
+    use crate::AppState;
+    let state = use_context::<AppState>().expect("AppState not found");
+    Ok(state
+        .oidc
+        .providers()
+        .iter()
+        .map(|provider| OidcProviderInfo {
+            id: provider.id.clone(),
+            name: provider.name.clone(),
+        })
+        .collect())
+}
+
+#[server]
+pub async fn list_linked_identities() -> Result<Vec<LinkedIdentity>, ServerFnError> {
+    use crate::AppState;
+    use crate::auth::get_user;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let user = get_user().await?;
+    let Some(user) = user else {
+        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+    };
+    let identities = state.identity_repository.list_by_user(user.id).await?;
+    Ok(identities
+        .into_iter()
+        .map(|identity| LinkedIdentity {
+            id: identity.id.to_string(),
+            provider: state
+                .oidc
+                .providers()
+                .iter()
+                .find(|provider| provider.id == identity.provider)
+                .map_or(identity.provider, |provider| provider.name.clone()),
+            email: identity.email,
+            created_at: identity.created_at,
+        })
+        .collect())
+}
+
+#[server]
+pub async fn unlink_identity(id: String) -> Result<(), ServerFnError> {
+    use crate::AppState;
+    use crate::auth::get_user;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let user = get_user().await?;
+    let Some(user) = user else {
+        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+    };
+    let Ok(id) = id.parse::<uuid::Uuid>() else {
+        return Err(ServerFnError::ServerError("Invalid identity id".to_string()));
+    };
+    let identities = state.identity_repository.list_by_user(user.id).await?;
+    let Some(identity) = identities.iter().find(|identity| identity.id == id) else {
+        return Err(ServerFnError::ServerError("Identity not found".to_string()));
+    };
+    // Accounts created through a provider have no password to fall back to
+    if identity.created_account && identities.len() == 1 {
+        return Err(ServerFnError::ServerError(
+            "The identity you signed up with can't be unlinked".to_string(),
+        ));
+    }
+    state.identity_repository.delete(id, user.id).await?;
+    Ok(())
+}
@@ -0,0 +1,400 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use leptos::{prelude::*, server_fn::codec::JsonEncoding};
+use serde::{Deserialize, Serialize};
+
+use crate::server_fn::chat::ChatError;
+
+/// Most options a poll can have.
+pub const MAX_POLL_OPTIONS: usize = 10;
+/// Maximum number of characters of an option.
+pub const MAX_OPTION_LENGTH: usize = 256;
+
+/// A poll as shown in the chat, the question is the text of its message.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChatPoll {
+    pub multiple_choice: bool,
+    pub anonymous: bool,
+    pub results: PollResults,
+    /// Ids of the options the viewer voted for, empty in broadcasts.
+    pub own_votes: Vec<String>,
+}
+
+impl ChatPoll {
+    pub fn is_closed(&self, now: DateTime<Utc>) -> bool {
+        self.results
+            .closes_at
+            .is_some_and(|closes_at| closes_at <= now)
+    }
+}
+
+/// The current state of a poll, pushed as
+/// [`ChatChannelMessages::PollUpdated`](crate::server_fn::chat::ChatChannelMessages::PollUpdated)
+/// whenever someone votes or the poll is closed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PollResults {
+    /// The id of the message holding the question.
+    pub poll_id: String,
+    pub options: Vec<PollOptionResult>,
+    /// Number of users that voted, with multiple choice less than the votes.
+    pub voters: u32,
+    pub closes_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PollOptionResult {
+    pub id: String,
+    pub text: String,
+    pub votes: u32,
+    /// Usernames of the voters, always empty for anonymous polls.
+    pub voters: Vec<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl PollResults {
+    fn new(poll: &crate::domain::poll::Poll, votes: &[crate::domain::poll::PollVote]) -> Self {
+        use std::collections::HashSet;
+        let options = poll
+            .options
+            .iter()
+            .map(|option| {
+                let voters = votes
+                    .iter()
+                    .filter(|vote| vote.option_id == option.id)
+                    .map(|vote| vote.username.clone())
+                    .collect::<Vec<_>>();
+                PollOptionResult {
+                    id: option.id.to_string(),
+                    text: option.text.clone(),
+                    votes: voters.len() as u32,
+                    voters: if poll.anonymous { Vec::new() } else { voters },
+                }
+            })
+            .collect();
+        Self {
+            poll_id: poll.message_id.to_string(),
+            options,
+            voters: votes
+                .iter()
+                .map(|vote| vote.user_id)
+                .collect::<HashSet<_>>()
+                .len() as u32,
+            closes_at: poll.closes_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum PollError {
+    Unauthorized,
+    InvalidGroupId,
+    InvalidPollId,
+    NotAMember,
+    EmptyQuestion,
+    QuestionTooLong,
+    TooFewOptions,
+    TooManyOptions,
+    OptionTooLong,
+    /// A vote for an option that isn't part of the poll.
+    InvalidOption,
+    /// More than one option was chosen in a single choice poll.
+    SingleChoice,
+    CloseTimeInPast,
+    Closed,
+    /// Only the creator of a poll can close it.
+    NotTheCreator,
+    /// Too many messages were sent to the group, the next one is accepted in
+    /// `retry_after_ms`.
+    SlowDown {
+        retry_after_ms: u64,
+    },
+    ServerFnError(ServerFnErrorErr),
+}
+
+impl Display for PollError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PollError::Unauthorized => write!(f, "You need to be logged in"),
+            PollError::InvalidGroupId => write!(f, "Invalid group id"),
+            PollError::InvalidPollId => write!(f, "This poll doesn't exist"),
+            PollError::NotAMember => write!(f, "You are not a member of this group"),
+            PollError::EmptyQuestion => write!(f, "Polls need a question"),
+            PollError::QuestionTooLong => write!(f, "The question is too long"),
+            PollError::TooFewOptions => write!(f, "Polls need at least two options"),
+            PollError::TooManyOptions => {
+                write!(f, "Polls can't have more than {MAX_POLL_OPTIONS} options")
+            }
+            PollError::OptionTooLong => write!(
+                f,
+                "Options can't be longer than {MAX_OPTION_LENGTH} characters"
+            ),
+            PollError::InvalidOption => write!(f, "This option is not part of the poll"),
+            PollError::SingleChoice => write!(f, "Only one option can be chosen"),
+            PollError::CloseTimeInPast => write!(f, "The close time must be in the future"),
+            PollError::Closed => write!(f, "This poll is closed"),
+            PollError::NotTheCreator => write!(f, "Only the creator can close this poll"),
+            PollError::SlowDown { retry_after_ms } => write!(
+                f,
+                "You are sending messages too fast, try again in {}s",
+                retry_after_ms.div_ceil(1000)
+            ),
+            PollError::ServerFnError(err) => write!(f, "Server error: {err}"),
+        }
+    }
+}
+
+impl FromStr for PollError {
+    type Err = ServerFnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Unauthorized" => Ok(PollError::Unauthorized),
+            "InvalidGroupId" => Ok(PollError::InvalidGroupId),
+            "InvalidPollId" => Ok(PollError::InvalidPollId),
+            "NotAMember" => Ok(PollError::NotAMember),
+            "EmptyQuestion" => Ok(PollError::EmptyQuestion),
+            "QuestionTooLong" => Ok(PollError::QuestionTooLong),
+            "TooFewOptions" => Ok(PollError::TooFewOptions),
+            "TooManyOptions" => Ok(PollError::TooManyOptions),
+            "OptionTooLong" => Ok(PollError::OptionTooLong),
+            "InvalidOption" => Ok(PollError::InvalidOption),
+            "SingleChoice" => Ok(PollError::SingleChoice),
+            "CloseTimeInPast" => Ok(PollError::CloseTimeInPast),
+            "Closed" => Ok(PollError::Closed),
+            "NotTheCreator" => Ok(PollError::NotTheCreator),
+            _ => Err(ServerFnError::ServerError(s.into())),
+        }
+    }
+}
+
+impl FromServerFnError for PollError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        PollError::ServerFnError(value)
+    }
+}
+
+impl From<ChatError> for PollError {
+    fn from(value: ChatError) -> Self {
+        match value {
+            ChatError::Unauthorized => PollError::Unauthorized,
+            ChatError::InvalidGroupId => PollError::InvalidGroupId,
+            ChatError::InvalidMessageId => PollError::InvalidPollId,
+            ChatError::NotAMember => PollError::NotAMember,
+            ChatError::EmptyMessage => PollError::EmptyQuestion,
+            ChatError::MessageTooLong => PollError::QuestionTooLong,
+            ChatError::SlowDown { retry_after_ms } => PollError::SlowDown { retry_after_ms },
+            ChatError::ServerFnError(err) => PollError::ServerFnError(err),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ServerFnError> for PollError {
+    fn from(value: ServerFnError) -> Self {
+        PollError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for PollError {
+    fn from(value: sqlx::Error) -> Self {
+        PollError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+    }
+}
+
+/// The poll of a message as seen by `viewer_id`.
+#[cfg(feature = "ssr")]
+pub(crate) async fn chat_poll(
+    state: &crate::AppState,
+    poll: &crate::domain::poll::Poll,
+    viewer_id: uuid::Uuid,
+) -> Result<ChatPoll, sqlx::Error> {
+    let votes = state.poll_repository.list_votes(poll.message_id).await?;
+    Ok(chat_poll_with_votes(poll, &votes, viewer_id))
+}
+
+/// The poll as `viewer_id` sees it, given all of its votes.
+#[cfg(feature = "ssr")]
+pub(crate) fn chat_poll_with_votes(
+    poll: &crate::domain::poll::Poll,
+    votes: &[crate::domain::poll::PollVote],
+    viewer_id: uuid::Uuid,
+) -> ChatPoll {
+    ChatPoll {
+        multiple_choice: poll.multiple_choice,
+        anonymous: poll.anonymous,
+        results: PollResults::new(poll, votes),
+        own_votes: votes
+            .iter()
+            .filter(|vote| vote.user_id == viewer_id)
+            .map(|vote| vote.option_id.to_string())
+            .collect(),
+    }
+}
+
+/// Pushes the current results of a poll to the group's channel.
+#[cfg(feature = "ssr")]
+fn broadcast_results(state: &crate::AppState, group_id: uuid::Uuid, results: PollResults) {
+    use crate::backplane::BackplaneEvent;
+    use crate::server_fn::chat::ChatChannelMessages;
+    state.backplane.publish(BackplaneEvent::Chat {
+        group_id: group_id.to_string(),
+        message: ChatChannelMessages::PollUpdated(results),
+    });
+}
+
+/// The poll with its message, if the signed in user may see it.
+#[cfg(feature = "ssr")]
+async fn member_poll(
+    state: &crate::AppState,
+    user_id: uuid::Uuid,
+    poll_id: &str,
+) -> Result<(crate::domain::message::Message, crate::domain::poll::Poll), PollError> {
+    let Ok(poll_id) = poll_id.parse() else {
+        return Err(PollError::InvalidPollId);
+    };
+    let Some(poll) = state.poll_repository.get_by_message_id(poll_id).await? else {
+        return Err(PollError::InvalidPollId);
+    };
+    let message = state.message_repository.get_by_id(poll_id).await?;
+    if !state
+        .group_repository
+        .is_member(message.group_id, user_id)
+        .await?
+    {
+        return Err(PollError::NotAMember);
+    }
+    Ok((message, poll))
+}
+
+/// Posts a poll to a group, its question is the text of the message.
+#[server]
+pub async fn create_poll(
+    group_id: String,
+    question: String,
+    options: Vec<String>,
+    multiple_choice: bool,
+    anonymous: bool,
+    closes_at: Option<DateTime<Utc>>,
+) -> Result<(), PollError> {
+    use crate::AppState;
+    use crate::domain::{message::Message, poll::Poll};
+    use crate::domain::message_content::MessageContent;
+    use crate::server_fn::chat::{broadcast, check_rate_limit};
+    let state = use_context::<AppState>().expect("AppState not found");
+    use crate::auth::get_user_with_scope;
+    use crate::server_fn::tokens::TokenScope;
+    let user = get_user_with_scope(TokenScope::MessagesWrite).await?;
+    let Some(user) = user else {
+        return Err(PollError::Unauthorized);
+    };
+    let question = question
+        .parse::<MessageContent>()
+        .map_err(ChatError::from)?;
+    let Ok(group_id) = group_id.parse() else {
+        return Err(PollError::InvalidGroupId);
+    };
+    if !state.group_repository.is_member(group_id, user.id).await? {
+        return Err(PollError::NotAMember);
+    }
+    let options = options
+        .iter()
+        .map(|option| option.trim().to_string())
+        .filter(|option| !option.is_empty())
+        .collect::<Vec<_>>();
+    if options.len() < 2 {
+        return Err(PollError::TooFewOptions);
+    }
+    if options.len() > MAX_POLL_OPTIONS {
+        return Err(PollError::TooManyOptions);
+    }
+    if options
+        .iter()
+        .any(|option| option.chars().count() > MAX_OPTION_LENGTH)
+    {
+        return Err(PollError::OptionTooLong);
+    }
+    if closes_at.is_some_and(|closes_at| closes_at <= Utc::now()) {
+        return Err(PollError::CloseTimeInPast);
+    }
+    check_rate_limit(&state, user.id, group_id)?;
+
+    let message = Message::new(group_id, user.id, question.into());
+    let poll = Poll::new(message.id, options, multiple_choice, anonymous, closes_at);
+    state.poll_repository.create(&message, &poll).await?;
+    let chat_poll = chat_poll(&state, &poll, user.id).await?;
+    broadcast(&state, &message, user.username, Some(chat_poll)).await?;
+    Ok(())
+}
+
+/// Replaces the votes of the signed in user, no options withdraws them.
+#[server]
+pub async fn vote_poll(
+    poll_id: String,
+    #[server(default)] option_ids: Vec<String>,
+) -> Result<ChatPoll, PollError> {
+    use crate::AppState;
+    use uuid::Uuid;
+    let state = use_context::<AppState>().expect("AppState not found");
+    use crate::auth::get_user_with_scope;
+    use crate::server_fn::tokens::TokenScope;
+    let user = get_user_with_scope(TokenScope::MessagesWrite).await?;
+    let Some(user) = user else {
+        return Err(PollError::Unauthorized);
+    };
+    let (message, poll) = member_poll(&state, user.id, &poll_id).await?;
+    if poll.is_closed(Utc::now()) {
+        return Err(PollError::Closed);
+    }
+    let mut chosen = Vec::new();
+    for option_id in option_ids {
+        let Ok(option_id) = option_id.parse::<Uuid>() else {
+            return Err(PollError::InvalidOption);
+        };
+        if !poll.options.iter().any(|option| option.id == option_id) {
+            return Err(PollError::InvalidOption);
+        }
+        if !chosen.contains(&option_id) {
+            chosen.push(option_id);
+        }
+    }
+    if !poll.multiple_choice && chosen.len() > 1 {
+        return Err(PollError::SingleChoice);
+    }
+    state
+        .poll_repository
+        .set_votes(poll.message_id, user.id, &chosen, Utc::now())
+        .await?;
+    let chat_poll = chat_poll(&state, &poll, user.id).await?;
+    broadcast_results(&state, message.group_id, chat_poll.results.clone());
+    Ok(chat_poll)
+}
+
+/// Closes a poll before its close time, only its creator can do that.
+#[server]
+pub async fn close_poll(poll_id: String) -> Result<(), PollError> {
+    use crate::AppState;
+    let state = use_context::<AppState>().expect("AppState not found");
+    use crate::auth::get_user_with_scope;
+    use crate::server_fn::tokens::TokenScope;
+    let user = get_user_with_scope(TokenScope::MessagesWrite).await?;
+    let Some(user) = user else {
+        return Err(PollError::Unauthorized);
+    };
+    let (message, mut poll) = member_poll(&state, user.id, &poll_id).await?;
+    if message.user_id != user.id {
+        return Err(PollError::NotTheCreator);
+    }
+    let now = Utc::now();
+    if poll.is_closed(now) {
+        return Err(PollError::Closed);
+    }
+    state.poll_repository.close(poll.message_id, now).await?;
+    poll.closes_at = Some(now);
+    let chat_poll = chat_poll(&state, &poll, user.id).await?;
+    broadcast_results(&state, message.group_id, chat_poll.results);
+    Ok(())
+}
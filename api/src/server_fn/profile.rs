@@ -0,0 +1,216 @@
+use std::{fmt::Display, str::FromStr};
+
+use leptos::{prelude::*, server_fn::codec::JsonEncoding};
+use serde::{Deserialize, Serialize};
+use server_fn::codec::{MultipartData, MultipartFormData};
+
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 64;
+pub const MAX_BIO_LENGTH: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub username: String,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: String,
+    /// Whether the current user has blocked this user.
+    pub blocked: bool,
+}
+
+impl UserProfile {
+    /// The display name if one is set, the username otherwise.
+    pub fn name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.username)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::domain::profile::Profile> for UserProfile {
+    fn from(profile: crate::domain::profile::Profile) -> Self {
+        UserProfile {
+            avatar_url: profile.avatar_url(),
+            username: profile.username,
+            display_name: profile.display_name,
+            bio: profile.bio,
+            blocked: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum ProfileError {
+    Unauthorized,
+    NotFound,
+    InvalidDisplayName,
+    BioTooLong,
+    MissingAvatar,
+    AvatarTooLarge,
+    InvalidImage,
+    ServerFnError(ServerFnErrorErr),
+}
+
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileError::Unauthorized => write!(f, "You need to be logged in"),
+            ProfileError::NotFound => write!(f, "No user with this name"),
+            ProfileError::InvalidDisplayName => write!(
+                f,
+                "Display names can't be longer than {MAX_DISPLAY_NAME_LENGTH} characters"
+            ),
+            ProfileError::BioTooLong => {
+                write!(f, "Bios can't be longer than {MAX_BIO_LENGTH} characters")
+            }
+            ProfileError::MissingAvatar => write!(f, "Choose an image to upload"),
+            ProfileError::AvatarTooLarge => write!(f, "The image is too large"),
+            ProfileError::InvalidImage => {
+                write!(f, "The file is not a supported image (PNG, JPEG, WebP or GIF)")
+            }
+            ProfileError::ServerFnError(err) => write!(f, "Server error: {err}"),
+        }
+    }
+}
+
+impl FromStr for ProfileError {
+    type Err = ServerFnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Unauthorized" => Ok(ProfileError::Unauthorized),
+            "NotFound" => Ok(ProfileError::NotFound),
+            "InvalidDisplayName" => Ok(ProfileError::InvalidDisplayName),
+            "BioTooLong" => Ok(ProfileError::BioTooLong),
+            "MissingAvatar" => Ok(ProfileError::MissingAvatar),
+            "AvatarTooLarge" => Ok(ProfileError::AvatarTooLarge),
+            "InvalidImage" => Ok(ProfileError::InvalidImage),
+            _ => Err(ServerFnError::ServerError(s.into())),
+        }
+    }
+}
+
+impl FromServerFnError for ProfileError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        ProfileError::ServerFnError(value)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ServerFnError> for ProfileError {
+    fn from(value: ServerFnError) -> Self {
+        ProfileError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for ProfileError {
+    fn from(value: sqlx::Error) -> Self {
+        ProfileError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+    }
+}
+
+#[server]
+pub async fn get_profile(username: String) -> Result<UserProfile, ProfileError> {
+    use crate::AppState;
+    use crate::auth::get_user;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let Some(user) = get_user().await? else {
+        return Err(ProfileError::Unauthorized);
+    };
+    let profile = state
+        .profile_repository
+        .get_by_username(&username)
+        .await?
+        .ok_or(ProfileError::NotFound)?;
+    let blocked = state
+        .block_repository
+        .is_blocked(user.id, profile.user_id)
+        .await?;
+    Ok(UserProfile {
+        blocked,
+        ..profile.into()
+    })
+}
+
+#[server]
+pub async fn update_profile(
+    display_name: String,
+    bio: String,
+) -> Result<UserProfile, ProfileError> {
+    use crate::AppState;
+    use crate::auth::get_user;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let Some(user) = get_user().await? else {
+        return Err(ProfileError::Unauthorized);
+    };
+    let display_name = Some(display_name.trim().to_string()).filter(|name| !name.is_empty());
+    if display_name
+        .as_ref()
+        .is_some_and(|name| name.chars().count() > MAX_DISPLAY_NAME_LENGTH)
+    {
+        return Err(ProfileError::InvalidDisplayName);
+    }
+    let bio = Some(bio.trim().to_string()).filter(|bio| !bio.is_empty());
+    if bio
+        .as_ref()
+        .is_some_and(|bio| bio.chars().count() > MAX_BIO_LENGTH)
+    {
+        return Err(ProfileError::BioTooLong);
+    }
+    state
+        .profile_repository
+        .update(user.id, display_name, bio)
+        .await?;
+    Ok(state.profile_repository.get_by_user_id(user.id).await?.into())
+}
+
+/// Replaces the avatar with the image in the `avatar` field of the form.
+#[server(input = MultipartFormData)]
+pub async fn upload_avatar(data: MultipartData) -> Result<UserProfile, ProfileError> {
+    use crate::AppState;
+    use crate::auth::get_user;
+    use crate::domain::profile::Avatar;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let Some(user) = get_user().await? else {
+        return Err(ProfileError::Unauthorized);
+    };
+    let max_bytes = state.config.uploads.max_avatar_bytes;
+
+    let mut data = data.into_inner().expect("multipart data on the server");
+    let mut upload = Vec::new();
+    while let Some(mut field) = data.next_field().await.map_err(ServerFnError::new)? {
+        if field.name() != Some("avatar") {
+            continue;
+        }
+        while let Some(chunk) = field.chunk().await.map_err(ServerFnError::new)? {
+            if upload.len() + chunk.len() > max_bytes {
+                return Err(ProfileError::AvatarTooLarge);
+            }
+            upload.extend_from_slice(&chunk);
+        }
+    }
+    if upload.is_empty() {
+        return Err(ProfileError::MissingAvatar);
+    }
+
+    // Decoding and resizing is cpu bound, keep it off the async workers
+    let avatar = tokio::task::spawn_blocking(move || Avatar::from_upload(user.id, &upload))
+        .await
+        .map_err(|error| ServerFnError::new(error.to_string()))?
+        .map_err(|_| ProfileError::InvalidImage)?;
+    state.profile_repository.set_avatar(avatar).await?;
+    Ok(state.profile_repository.get_by_user_id(user.id).await?.into())
+}
+
+#[server]
+pub async fn remove_avatar() -> Result<UserProfile, ProfileError> {
+    use crate::AppState;
+    use crate::auth::get_user;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let Some(user) = get_user().await? else {
+        return Err(ProfileError::Unauthorized);
+    };
+    state.profile_repository.remove_avatar(user.id).await?;
+    Ok(state.profile_repository.get_by_user_id(user.id).await?.into())
+}
@@ -0,0 +1,224 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use leptos::{prelude::*, server_fn::codec::JsonEncoding};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::message_content::{MAX_MESSAGE_LENGTH, MessageContentError};
+
+/// A message of the signed in user waiting to be sent.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledMessageInfo {
+    pub id: String,
+    pub text: String,
+    pub send_at: DateTime<Utc>,
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::domain::scheduled_message::ScheduledMessage> for ScheduledMessageInfo {
+    fn from(message: crate::domain::scheduled_message::ScheduledMessage) -> Self {
+        Self {
+            id: message.id.to_string(),
+            text: message.content,
+            send_at: message.send_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum ScheduleError {
+    Unauthorized,
+    InvalidGroupId,
+    NotAMember,
+    /// No pending message with this id, it may have been sent already.
+    NotFound,
+    EmptyMessage,
+    MessageTooLong,
+    SendTimeInPast,
+    ServerFnError(ServerFnErrorErr),
+}
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleError::Unauthorized => write!(f, "You need to be logged in"),
+            ScheduleError::InvalidGroupId => write!(f, "Invalid group id"),
+            ScheduleError::NotAMember => write!(f, "You are not a member of this group"),
+            ScheduleError::NotFound => write!(f, "This message was already sent or cancelled"),
+            ScheduleError::EmptyMessage => write!(f, "Messages can't be empty"),
+            ScheduleError::MessageTooLong => write!(
+                f,
+                "Messages can't be longer than {MAX_MESSAGE_LENGTH} characters"
+            ),
+            ScheduleError::SendTimeInPast => write!(f, "The send time must be in the future"),
+            ScheduleError::ServerFnError(err) => write!(f, "Server error: {err}"),
+        }
+    }
+}
+
+impl FromStr for ScheduleError {
+    type Err = ServerFnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Unauthorized" => Ok(ScheduleError::Unauthorized),
+            "InvalidGroupId" => Ok(ScheduleError::InvalidGroupId),
+            "NotAMember" => Ok(ScheduleError::NotAMember),
+            "NotFound" => Ok(ScheduleError::NotFound),
+            "EmptyMessage" => Ok(ScheduleError::EmptyMessage),
+            "MessageTooLong" => Ok(ScheduleError::MessageTooLong),
+            "SendTimeInPast" => Ok(ScheduleError::SendTimeInPast),
+            _ => Err(ServerFnError::ServerError(s.into())),
+        }
+    }
+}
+
+impl From<MessageContentError> for ScheduleError {
+    fn from(value: MessageContentError) -> Self {
+        match value {
+            MessageContentError::Empty => ScheduleError::EmptyMessage,
+            MessageContentError::TooLong => ScheduleError::MessageTooLong,
+        }
+    }
+}
+
+impl FromServerFnError for ScheduleError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        ScheduleError::ServerFnError(value)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ServerFnError> for ScheduleError {
+    fn from(value: ServerFnError) -> Self {
+        ScheduleError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for ScheduleError {
+    fn from(value: sqlx::Error) -> Self {
+        ScheduleError::ServerFnError(ServerFnErrorErr::ServerError(value.to_string()))
+    }
+}
+
+/// The signed in user and the group, if they are a member of it.
+#[cfg(feature = "ssr")]
+async fn group_member(
+    state: &crate::AppState,
+    group_id: &str,
+    scope: crate::server_fn::tokens::TokenScope,
+) -> Result<(crate::domain::user::User, uuid::Uuid), ScheduleError> {
+    use crate::auth::get_user_with_scope;
+    let Some(user) = get_user_with_scope(scope).await? else {
+        return Err(ScheduleError::Unauthorized);
+    };
+    let Ok(group_id) = group_id.parse() else {
+        return Err(ScheduleError::InvalidGroupId);
+    };
+    if !state.group_repository.is_member(group_id, user.id).await? {
+        return Err(ScheduleError::NotAMember);
+    }
+    Ok((user, group_id))
+}
+
+/// Schedules a message to be sent to a group at `send_at`.
+#[server]
+pub async fn schedule_message(
+    group_id: String,
+    text: String,
+    send_at: DateTime<Utc>,
+) -> Result<ScheduledMessageInfo, ScheduleError> {
+    use crate::AppState;
+    use crate::domain::scheduled_message::ScheduledMessage;
+    use crate::domain::message_content::MessageContent;
+    use crate::server_fn::tokens::TokenScope;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let (user, group_id) = group_member(&state, &group_id, TokenScope::MessagesWrite).await?;
+    let content = text.parse::<MessageContent>()?;
+    if send_at <= Utc::now() {
+        return Err(ScheduleError::SendTimeInPast);
+    }
+    let message = ScheduledMessage::new(group_id, user.id, content.into(), send_at);
+    state
+        .scheduled_message_repository
+        .create(message.clone())
+        .await?;
+    Ok(message.into())
+}
+
+/// The pending messages the signed in user scheduled in a group, the next one first.
+#[server]
+pub async fn list_scheduled_messages(
+    group_id: String,
+) -> Result<Vec<ScheduledMessageInfo>, ScheduleError> {
+    use crate::AppState;
+    use crate::server_fn::tokens::TokenScope;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let (user, group_id) = group_member(&state, &group_id, TokenScope::MessagesRead).await?;
+    Ok(state
+        .scheduled_message_repository
+        .list_by_user_and_group(user.id, group_id)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect())
+}
+
+/// Changes the text and send time of a pending message.
+#[server]
+pub async fn update_scheduled_message(
+    id: String,
+    text: String,
+    send_at: DateTime<Utc>,
+) -> Result<(), ScheduleError> {
+    use crate::AppState;
+    use crate::domain::message_content::MessageContent;
+    use crate::server_fn::tokens::TokenScope;
+    let state = use_context::<AppState>().expect("AppState not found");
+    use crate::auth::get_user_with_scope;
+    let Some(user) = get_user_with_scope(TokenScope::MessagesWrite).await? else {
+        return Err(ScheduleError::Unauthorized);
+    };
+    let Ok(id) = id.parse() else {
+        return Err(ScheduleError::NotFound);
+    };
+    let content = text.parse::<MessageContent>()?;
+    let now = Utc::now();
+    if send_at <= now {
+        return Err(ScheduleError::SendTimeInPast);
+    }
+    if !state
+        .scheduled_message_repository
+        .update(id, user.id, content.into(), send_at, now)
+        .await?
+    {
+        return Err(ScheduleError::NotFound);
+    }
+    Ok(())
+}
+
+/// Cancels a pending message.
+#[server]
+pub async fn cancel_scheduled_message(id: String) -> Result<(), ScheduleError> {
+    use crate::AppState;
+    use crate::server_fn::tokens::TokenScope;
+    let state = use_context::<AppState>().expect("AppState not found");
+    use crate::auth::get_user_with_scope;
+    let Some(user) = get_user_with_scope(TokenScope::MessagesWrite).await? else {
+        return Err(ScheduleError::Unauthorized);
+    };
+    let Ok(id) = id.parse() else {
+        return Err(ScheduleError::NotFound);
+    };
+    if !state
+        .scheduled_message_repository
+        .delete(id, user.id)
+        .await?
+    {
+        return Err(ScheduleError::NotFound);
+    }
+    Ok(())
+}
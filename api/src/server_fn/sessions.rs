@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub device: String,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub current: bool,
+}
+
+#[server]
+pub async fn list_sessions() -> Result<Vec<SessionInfo>, ServerFnError> {
+    use crate::AppState;
+    use crate::auth::auth;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let auth = auth().await?;
+    let Some(user) = auth.current_user else {
+        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+    };
+    let current_session_id = auth.session.get_session_id();
+    let sessions = state
+        .session_repository
+        .list_active_by_user(user.id)
+        .await?;
+    Ok(sessions
+        .into_iter()
+        .map(|session| SessionInfo {
+            id: session.id.to_string(),
+            device: session.device(),
+            ip_address: session.ip_address,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            current: session.session_id == current_session_id,
+        })
+        .collect())
+}
+
+#[server]
+pub async fn revoke_session(id: String) -> Result<(), ServerFnError> {
+    use crate::AppState;
+    use crate::backplane::BackplaneEvent;
+    use crate::auth::get_user;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let user = get_user().await?;
+    let Some(user) = user else {
+        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+    };
+    let Ok(id) = id.parse() else {
+        return Err(ServerFnError::ServerError("Invalid session id".to_string()));
+    };
+    let Some(session_id) = state.session_repository.revoke(id, user.id).await? else {
+        return Err(ServerFnError::ServerError("Session not found".to_string()));
+    };
+    state.backplane.publish(BackplaneEvent::CloseSessions {
+        session_ids: vec![session_id],
+    });
+    Ok(())
+}
+
+#[server]
+pub async fn revoke_all_other_sessions() -> Result<(), ServerFnError> {
+    use crate::AppState;
+    use crate::backplane::BackplaneEvent;
+    use crate::auth::auth;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let auth = auth().await?;
+    let Some(user) = auth.current_user else {
+        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+    };
+    let revoked = state
+        .session_repository
+        .revoke_all_except(user.id, &auth.session.get_session_id())
+        .await?;
+    state.backplane.publish(BackplaneEvent::CloseSessions {
+        session_ids: revoked,
+    });
+    Ok(())
+}
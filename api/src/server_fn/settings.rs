@@ -2,7 +2,10 @@ use leptos::prelude::*;
 use serde::{Deserialize, Serialize};
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct LoggedIn {
+    pub id: String,
     pub username: String,
+    pub display_name: Option<String>,
+    pub avatar_url: String,
 }
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Account {
@@ -15,21 +18,41 @@ impl Account {
         matches!(self, Account::LoggedIn(_))
     }
 
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Account::LoggedIn(logged_in) => Some(&logged_in.id),
+            Account::NotLoggedIn => None,
+        }
+    }
+
     pub fn username(&self) -> Option<&str> {
         match self {
             Account::LoggedIn(logged_in) => Some(&logged_in.username),
             Account::NotLoggedIn => None,
         }
     }
+
+    pub fn avatar_url(&self) -> Option<&str> {
+        match self {
+            Account::LoggedIn(logged_in) => Some(&logged_in.avatar_url),
+            Account::NotLoggedIn => None,
+        }
+    }
 }
 
 #[server]
 pub async fn get_account() -> Result<Account, ServerFnError> {
+    use crate::AppState;
     use crate::auth::get_user;
+    let state = use_context::<AppState>().expect("AppState not found");
     let user = get_user().await?;
     if let Some(user) = user {
+        let profile = state.profile_repository.get_by_user_id(user.id).await?;
         Ok(Account::LoggedIn(LoggedIn {
-            username: user.username,
+            id: user.id.to_string(),
+            avatar_url: profile.avatar_url(),
+            username: profile.username,
+            display_name: profile.display_name,
         }))
     } else {
         Ok(Account::NotLoggedIn)
@@ -17,6 +17,9 @@ pub async fn signup(
     Pow::validate(&pow).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
     let app_state = use_context::<AppState>()
         .ok_or_else(|| ServerFnError::ServerError("AppState not found".into()))?;
+    if !app_state.config.features.signup {
+        return Err(ServerFnError::ServerError("Sign up is disabled".into()));
+    }
     let user_repo = app_state.user_repository;
     let pool = app_state.pool;
     let auth = auth()
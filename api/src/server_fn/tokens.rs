@@ -0,0 +1,127 @@
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub use crate::domain::token_scope::TokenScope;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiTokenInfo {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl ApiTokenInfo {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::domain::api_token::ApiToken> for ApiTokenInfo {
+    fn from(token: crate::domain::api_token::ApiToken) -> Self {
+        Self {
+            id: token.id.to_string(),
+            name: token.name,
+            prefix: token.prefix,
+            scopes: token.scopes,
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+        }
+    }
+}
+
+/// A freshly created token, `secret` is only ever returned here.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreatedApiToken {
+    pub token: ApiTokenInfo,
+    pub secret: String,
+}
+
+#[server]
+pub async fn list_api_tokens() -> Result<Vec<ApiTokenInfo>, ServerFnError> {
+    use crate::AppState;
+    use crate::auth::get_user;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let user = get_user().await?;
+    let Some(user) = user else {
+        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+    };
+    let tokens = state.api_token_repository.list_by_user(user.id).await?;
+    Ok(tokens.into_iter().map(ApiTokenInfo::from).collect())
+}
+
+#[server]
+pub async fn create_api_token(
+    name: String,
+    scopes: Vec<TokenScope>,
+    expires_in_days: Option<i64>,
+) -> Result<CreatedApiToken, ServerFnError> {
+    use crate::AppState;
+    use crate::auth::get_user;
+    use crate::domain::api_token::ApiToken;
+    use chrono::Duration;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let user = get_user().await?;
+    let Some(user) = user else {
+        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+    };
+    if !state.config.features.api_tokens {
+        return Err(ServerFnError::ServerError(
+            "Personal access tokens are disabled".to_string(),
+        ));
+    }
+    let name = name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(ServerFnError::ServerError(
+            "Token name must be between 1 and 64 characters".to_string(),
+        ));
+    }
+    if scopes.is_empty() {
+        return Err(ServerFnError::ServerError(
+            "Select at least one scope".to_string(),
+        ));
+    }
+    let expires_at = match expires_in_days {
+        Some(days @ 1..=365) => Some(Utc::now() + Duration::days(days)),
+        Some(_) => {
+            return Err(ServerFnError::ServerError(
+                "Tokens can expire after 1 to 365 days".to_string(),
+            ));
+        }
+        None => None,
+    };
+    let mut scopes = scopes;
+    scopes.sort_by_key(|scope| *scope as u8);
+    scopes.dedup();
+    let (token, secret) = ApiToken::generate(user.id, name, scopes, expires_at);
+    state.api_token_repository.create(token.clone()).await?;
+    Ok(CreatedApiToken {
+        token: token.into(),
+        secret,
+    })
+}
+
+#[server]
+pub async fn revoke_api_token(id: String) -> Result<(), ServerFnError> {
+    use crate::AppState;
+    use crate::auth::get_user;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let user = get_user().await?;
+    let Some(user) = user else {
+        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+    };
+    let Ok(id) = id.parse() else {
+        return Err(ServerFnError::ServerError("Invalid token id".to_string()));
+    };
+    if !state.api_token_repository.delete(id, user.id).await? {
+        return Err(ServerFnError::ServerError("Token not found".to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,292 @@
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Route of the server receiving webhook messages.
+pub const WEBHOOK_ROUTE: &str = "/hooks/{token}";
+
+/// Events of a group outgoing webhooks can subscribe to.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumString,
+)]
+pub enum WebhookEvent {
+    #[serde(rename = "message.created")]
+    #[strum(serialize = "message.created")]
+    MessageCreated,
+    #[serde(rename = "member.joined")]
+    #[strum(serialize = "member.joined")]
+    MemberJoined,
+    #[serde(rename = "member.left")]
+    #[strum(serialize = "member.left")]
+    MemberLeft,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 3] = [
+        WebhookEvent::MessageCreated,
+        WebhookEvent::MemberJoined,
+        WebhookEvent::MemberLeft,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            WebhookEvent::MessageCreated => "New messages",
+            WebhookEvent::MemberJoined => "Members joining",
+            WebhookEvent::MemberLeft => "Members leaving",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookInfo {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::domain::webhook::Webhook> for WebhookInfo {
+    fn from(webhook: crate::domain::webhook::Webhook) -> Self {
+        Self {
+            id: webhook.id.to_string(),
+            name: webhook.name,
+            prefix: webhook.prefix,
+            created_at: webhook.created_at,
+            last_used_at: webhook.last_used_at,
+        }
+    }
+}
+
+/// A freshly created webhook, `url` contains the token and is only ever returned here.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreatedWebhook {
+    pub webhook: WebhookInfo,
+    pub url: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutgoingWebhookInfo {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::domain::outgoing_webhook::OutgoingWebhook> for OutgoingWebhookInfo {
+    fn from(webhook: crate::domain::outgoing_webhook::OutgoingWebhook) -> Self {
+        Self {
+            id: webhook.id.to_string(),
+            url: webhook.url,
+            events: webhook.events,
+            created_at: webhook.created_at,
+        }
+    }
+}
+
+/// A freshly created outgoing webhook, `secret` is only ever returned here.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreatedOutgoingWebhook {
+    pub webhook: OutgoingWebhookInfo,
+    pub secret: String,
+}
+
+/// The signed in user if they are a member of the group.
+#[cfg(feature = "ssr")]
+async fn group_member(
+    state: &crate::AppState,
+    group_id: &str,
+) -> Result<(crate::domain::user::User, uuid::Uuid), ServerFnError> {
+    use crate::auth::get_user;
+    let user = get_user().await?;
+    let Some(user) = user else {
+        return Err(ServerFnError::ServerError("Unauthorized".to_string()));
+    };
+    let Ok(group_id) = group_id.parse() else {
+        return Err(ServerFnError::ServerError("Invalid group id".to_string()));
+    };
+    if !state.group_repository.is_member(group_id, user.id).await? {
+        return Err(ServerFnError::ServerError(
+            "You are not a member of this group".to_string(),
+        ));
+    }
+    Ok((user, group_id))
+}
+
+/// Outgoing webhooks send group content off-site, so only the group owner or a site admin
+/// may manage them.
+#[cfg(feature = "ssr")]
+async fn group_manager(
+    state: &crate::AppState,
+    group_id: &str,
+) -> Result<(crate::domain::user::User, uuid::Uuid), ServerFnError> {
+    let (user, group_id) = group_member(state, group_id).await?;
+    if !crate::server_fn::groups::is_group_manager(state, group_id, &user).await? {
+        return Err(ServerFnError::ServerError(
+            "Only the group owner can manage outgoing webhooks".to_string(),
+        ));
+    }
+    Ok((user, group_id))
+}
+
+#[server]
+pub async fn list_webhooks(group_id: String) -> Result<Vec<WebhookInfo>, ServerFnError> {
+    use crate::AppState;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let (_, group_id) = group_member(&state, &group_id).await?;
+    let webhooks = state.webhook_repository.list_by_group(group_id).await?;
+    Ok(webhooks.into_iter().map(WebhookInfo::from).collect())
+}
+
+#[server]
+pub async fn create_webhook(
+    group_id: String,
+    name: String,
+) -> Result<CreatedWebhook, ServerFnError> {
+    use crate::AppState;
+    use crate::domain::{user::User, webhook::Webhook};
+    let state = use_context::<AppState>().expect("AppState not found");
+    let (user, group_id) = group_member(&state, &group_id).await?;
+    if !state.config.features.webhooks {
+        return Err(ServerFnError::ServerError(
+            "Webhooks are disabled".to_string(),
+        ));
+    }
+    let name = name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(ServerFnError::ServerError(
+            "Webhook name must be between 1 and 64 characters".to_string(),
+        ));
+    }
+    if state.user_repository.username_exists(&name).await? {
+        return Err(ServerFnError::ServerError(
+            "Webhooks can't be named like a user".to_string(),
+        ));
+    }
+    // Nobody knows the password of the webhook's user, so it can't sign in
+    let username = format!("webhook-{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+    let password = password_auth::generate_hash(nanoid::nanoid!(32));
+    let user_id = state
+        .user_repository
+        .create(User::new(username, password))
+        .await?;
+    let (webhook, secret) = Webhook::generate(group_id, user_id, name, user.id);
+    state.webhook_repository.create(webhook.clone()).await?;
+    let public_url = state
+        .config
+        .public_url
+        .clone()
+        .unwrap_or_else(|| format!("http://{}", state.options.site_addr));
+    Ok(CreatedWebhook {
+        webhook: webhook.into(),
+        url: format!(
+            "{}{}",
+            public_url.trim_end_matches('/'),
+            WEBHOOK_ROUTE.replace("{token}", &secret)
+        ),
+    })
+}
+
+#[server]
+pub async fn delete_webhook(group_id: String, id: String) -> Result<(), ServerFnError> {
+    use crate::AppState;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let (_, group_id) = group_member(&state, &group_id).await?;
+    let Ok(id) = id.parse() else {
+        return Err(ServerFnError::ServerError("Invalid webhook id".to_string()));
+    };
+    if !state.webhook_repository.delete(id, group_id).await? {
+        return Err(ServerFnError::ServerError("Webhook not found".to_string()));
+    }
+    Ok(())
+}
+
+#[server]
+pub async fn list_outgoing_webhooks(
+    group_id: String,
+) -> Result<Vec<OutgoingWebhookInfo>, ServerFnError> {
+    use crate::AppState;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let (_, group_id) = group_manager(&state, &group_id).await?;
+    let webhooks = state
+        .outgoing_webhook_repository
+        .list_by_group(group_id)
+        .await?;
+    Ok(webhooks.into_iter().map(OutgoingWebhookInfo::from).collect())
+}
+
+#[server]
+pub async fn create_outgoing_webhook(
+    group_id: String,
+    url: String,
+    events: Vec<WebhookEvent>,
+) -> Result<CreatedOutgoingWebhook, ServerFnError> {
+    use crate::AppState;
+    use crate::domain::outgoing_webhook::OutgoingWebhook;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let (user, group_id) = group_manager(&state, &group_id).await?;
+    if !state.config.features.webhooks {
+        return Err(ServerFnError::ServerError(
+            "Webhooks are disabled".to_string(),
+        ));
+    }
+    let url = url.trim().to_string();
+    let valid = reqwest::Url::parse(&url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host());
+    if !valid {
+        return Err(ServerFnError::ServerError(
+            "Enter an http(s) url".to_string(),
+        ));
+    }
+    if let Err(error) = crate::outgoing_webhooks::client_for(&state, &url).await {
+        return Err(ServerFnError::ServerError(format!(
+            "Events can't be sent to this url: {error}"
+        )));
+    }
+    if events.is_empty() {
+        return Err(ServerFnError::ServerError(
+            "Select at least one event".to_string(),
+        ));
+    }
+    let mut events = events;
+    events.sort_by_key(|event| *event as u8);
+    events.dedup();
+    let webhook = OutgoingWebhook::generate(group_id, url, events, user.id);
+    state
+        .outgoing_webhook_repository
+        .create(webhook.clone())
+        .await?;
+    Ok(CreatedOutgoingWebhook {
+        secret: webhook.secret.clone(),
+        webhook: webhook.into(),
+    })
+}
+
+#[server]
+pub async fn delete_outgoing_webhook(group_id: String, id: String) -> Result<(), ServerFnError> {
+    use crate::AppState;
+    let state = use_context::<AppState>().expect("AppState not found");
+    let (_, group_id) = group_manager(&state, &group_id).await?;
+    let Ok(id) = id.parse() else {
+        return Err(ServerFnError::ServerError("Invalid webhook id".to_string()));
+    };
+    if !state
+        .outgoing_webhook_repository
+        .delete(id, group_id)
+        .await?
+    {
+        return Err(ServerFnError::ServerError("Webhook not found".to_string()));
+    }
+    Ok(())
+}
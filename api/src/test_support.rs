@@ -0,0 +1,31 @@
+//! Shared setup of the server side tests.
+use std::sync::Arc;
+
+use leptos::config::LeptosOptions;
+use uuid::Uuid;
+
+use crate::{AppState, config::Config, domain::user::User};
+
+/// State backed by a fresh in-memory database with all migrations applied.
+pub async fn state(mut config: Config) -> AppState {
+    config.database.url = "sqlite::memory:".to_string();
+    // Every connection would open a database of its own otherwise
+    config.database.max_connections = 1;
+    let options = LeptosOptions::builder().output_name("test").build();
+    let state = AppState::new(Arc::new(config), options, None)
+        .await
+        .expect("Failed to open the test database");
+    migrator::migrate(&mut state.pool.clone())
+        .await
+        .expect("Failed to migrate the test database");
+    state
+}
+
+/// Creates a user that can't sign in with a password.
+pub async fn user(state: &AppState, username: &str) -> Uuid {
+    state
+        .user_repository
+        .create(User::new(username.to_string(), "!".to_string()))
+        .await
+        .expect("Failed to create user")
+}
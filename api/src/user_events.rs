@@ -0,0 +1,97 @@
+//! Live updates for a single user, e.g. of their group list.
+//!
+//! Every user has a `user-{user_id}` channel that only their own websocket
+//! connections may join, see [`crate::ws`]. The server is the only sender.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::server_fn::groups::Group;
+
+/// Name of the channel with the [`UserEvent`]s of a user.
+pub fn user_channel_name(user_id: &str) -> String {
+    format!("user-{user_id}")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UserEvent {
+    /// A message was sent to one of the user's groups.
+    MessagePreview {
+        group_id: String,
+        text: String,
+        time: DateTime<Utc>,
+    },
+    AddedToGroup(Group),
+    RemovedFromGroup {
+        group_id: String,
+    },
+    GroupRenamed {
+        group_id: String,
+        name: String,
+    },
+    /// The topic of a group was set, or cleared if `None`.
+    TopicChanged {
+        group_id: String,
+        topic: Option<String>,
+    },
+    /// Messages of a group now disappear after `ttl_secs`, or are kept if `None`.
+    MessageTtlChanged {
+        group_id: String,
+        ttl_secs: Option<i64>,
+    },
+}
+
+#[cfg(feature = "ssr")]
+pub use service::UserEventService;
+
+#[cfg(feature = "ssr")]
+mod service {
+    use std::sync::Arc;
+
+    use leptos::prelude::{Owner, provide_context};
+    use leptos_ws::{ChannelSignal, WsSignals};
+    use uuid::Uuid;
+
+    use super::{UserEvent, user_channel_name};
+    use crate::backplane::{Backplane, BackplaneEvent};
+
+    #[derive(Clone)]
+    pub struct UserEventService {
+        server_signals: WsSignals,
+        backplane: Arc<dyn Backplane>,
+    }
+
+    impl UserEventService {
+        pub fn new(server_signals: WsSignals, backplane: Arc<dyn Backplane>) -> Self {
+            Self {
+                server_signals,
+                backplane,
+            }
+        }
+
+        /// The channel of a user, created if it doesn't exist yet.
+        pub fn channel(&self, user_id: Uuid) -> Option<ChannelSignal<UserEvent>> {
+            let owner = Owner::new();
+            owner.with(|| {
+                provide_context(self.server_signals.clone());
+                ChannelSignal::new(&user_channel_name(&user_id.to_string()))
+                    .inspect_err(|error| {
+                        log::error!("Failed to create event channel of {user_id}: {error:?}")
+                    })
+                    .ok()
+            })
+        }
+
+        /// Sends an event to all open connections of a user, on every instance.
+        pub fn send(&self, user_id: Uuid, event: UserEvent) {
+            self.backplane
+                .publish(BackplaneEvent::User { user_id, event });
+        }
+
+        /// Sends an event to the connections of a user on this instance.
+        pub fn deliver(&self, user_id: Uuid, event: UserEvent) {
+            if let Some(channel) = self.channel(user_id) {
+                let _ = channel.send_message(event);
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Incoming webhooks posting into groups without a user account.
+use serde::Deserialize;
+
+use crate::{
+    AppState,
+    domain::{
+        api_token::ApiToken,
+        message::Message,
+        message_content::{MessageContent, MessageContentError},
+    },
+    server_fn::chat::broadcast,
+};
+
+/// Most attachments of a single webhook message.
+const MAX_ATTACHMENTS: usize = 10;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookPayload {
+    pub text: String,
+    /// Shown as the sender instead of the name of the webhook.
+    pub username: Option<String>,
+    #[serde(default)]
+    pub attachments: Vec<WebhookAttachment>,
+}
+
+/// A link posted along with the text, one per line below it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookAttachment {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookError {
+    #[error("Webhooks are disabled")]
+    Disabled,
+    #[error("Unknown webhook")]
+    NotFound,
+    #[error("Usernames must be between 1 and 64 characters")]
+    InvalidUsername,
+    #[error("Webhooks can't post as a user")]
+    UsernameTaken,
+    #[error("Attachments must be at most {MAX_ATTACHMENTS} http(s) urls")]
+    InvalidAttachments,
+    #[error(transparent)]
+    InvalidContent(#[from] MessageContentError),
+    #[error("Too many messages, try again in {}s", .retry_after.as_secs().max(1))]
+    SlowDown { retry_after: std::time::Duration },
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+fn message_text(payload: &WebhookPayload) -> Result<String, WebhookError> {
+    if payload.attachments.len() > MAX_ATTACHMENTS {
+        return Err(WebhookError::InvalidAttachments);
+    }
+    let mut text = payload.text.clone();
+    for attachment in &payload.attachments {
+        let url = attachment.url.trim();
+        let valid = (url.starts_with("https://") || url.starts_with("http://"))
+            && !url.chars().any(char::is_whitespace);
+        if !valid {
+            return Err(WebhookError::InvalidAttachments);
+        }
+        text.push('\n');
+        match attachment.title.as_deref().map(str::trim) {
+            Some(title) if !title.is_empty() => text.push_str(&format!("{title}: {url}")),
+            _ => text.push_str(url),
+        }
+    }
+    Ok(text)
+}
+
+/// Stores the message of the webhook with the token and broadcasts it to the group,
+/// returns the id of the message.
+pub async fn post(
+    state: &AppState,
+    token: &str,
+    payload: WebhookPayload,
+) -> Result<String, WebhookError> {
+    if !state.config.features.webhooks {
+        return Err(WebhookError::Disabled);
+    }
+    let Some(webhook) = state
+        .webhook_repository
+        .get_by_hash(&ApiToken::hash(token))
+        .await?
+    else {
+        return Err(WebhookError::NotFound);
+    };
+    let sender_name = match payload.username.as_deref().map(str::trim) {
+        Some(name) if name.is_empty() || name.chars().count() > 64 => {
+            return Err(WebhookError::InvalidUsername);
+        }
+        Some(name) => name.to_string(),
+        None => webhook.name.clone(),
+    };
+    // Messages under a user's name would pass for theirs
+    if state.user_repository.username_exists(&sender_name).await? {
+        return Err(WebhookError::UsernameTaken);
+    }
+    let content = message_text(&payload)?.parse::<MessageContent>()?;
+    if let Err(retry_after) = state.message_limiter.check(webhook.user_id, webhook.group_id) {
+        return Err(WebhookError::SlowDown { retry_after });
+    }
+    let mut message = Message::new(webhook.group_id, webhook.user_id, content.into());
+    message.sender_name = Some(sender_name.clone());
+    state.message_repository.create(message.clone()).await?;
+    state
+        .webhook_repository
+        .touch(webhook.id, message.created_at)
+        .await?;
+    broadcast(state, &message, sender_name, None).await?;
+    Ok(message.id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backplane::BackplaneEvent,
+        config::Config,
+        domain::{group::Group, webhook::Webhook},
+        server_fn::chat::ChatChannelMessages,
+        test_support,
+    };
+
+    fn payload(username: Option<&str>) -> WebhookPayload {
+        WebhookPayload {
+            text: "Build passed".to_string(),
+            username: username.map(str::to_string),
+            attachments: Vec::new(),
+        }
+    }
+
+    async fn webhook(state: &AppState) -> String {
+        let alice = test_support::user(state, "alice").await;
+        let bot = test_support::user(state, "webhook-bot").await;
+        let group_id = state
+            .group_repository
+            .create_group(Group::new("builds".to_string()))
+            .await
+            .unwrap();
+        let (webhook, secret) = Webhook::generate(group_id, bot, "CI".to_string(), alice);
+        state.webhook_repository.create(webhook).await.unwrap();
+        secret
+    }
+
+    #[tokio::test]
+    async fn refuses_to_post_as_a_user() {
+        let state = test_support::state(Config::default()).await;
+        let secret = webhook(&state).await;
+
+        for name in ["alice", "Alice"] {
+            let result = post(&state, &secret, payload(Some(name))).await;
+            assert!(matches!(result, Err(WebhookError::UsernameTaken)), "{result:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn marks_its_messages_as_webhook_messages() {
+        let state = test_support::state(Config::default()).await;
+        let secret = webhook(&state).await;
+        let mut events = state.backplane.subscribe();
+
+        post(&state, &secret, payload(Some("Deploy bot")))
+            .await
+            .unwrap();
+        let BackplaneEvent::Chat {
+            message: ChatChannelMessages::NewMessage(message),
+            ..
+        } = events.recv().await.unwrap()
+        else {
+            panic!("Expected the new message");
+        };
+        assert_eq!(message.username, "Deploy bot");
+        assert!(message.is_webhook);
+    }
+}
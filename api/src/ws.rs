@@ -0,0 +1,722 @@
+//! Authenticated websocket endpoint for `leptos_ws`.
+//!
+//! The client side of `leptos_ws` always connects to its own `leptos_ws_websocket`
+//! server function. The server routes that path to [`authorized_websocket`] instead,
+//! which speaks the same protocol but knows which session and user owns the
+//! connection, so connections can be closed from the outside.
+//!
+//! Channels and signals named after a group (`{group_id}`, `{group_id}-activity` and
+//! `{group_id}-presence`) may only be joined by members of that group, see
+//! [`WsConnections::revoke_group`]. Presence events are handled here as well, as
+//! only this endpoint knows who sent them. The `user-{user_id}` event channel may
+//! only be joined by that user and is written to by the server alone. Messages
+//! published over the [`COMMAND_CHANNEL`](crate::server_fn::chat::COMMAND_CHANNEL) are acknowledged to the sending
+//! connection only, saving the HTTP round-trip of `publish_message`.
+#[cfg(feature = "ssr")]
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use leptos::{
+    prelude::*,
+    server_fn::{BoxedStream, Websocket, codec::JsonEncoding},
+};
+use leptos_ws::messages::Messages;
+#[cfg(feature = "ssr")]
+use uuid::Uuid;
+
+#[cfg(feature = "ssr")]
+use crate::server_fn::chat::COMMAND_CHANNEL;
+
+/// Read-only signal the server bumps every [`HEARTBEAT_INTERVAL`],
+/// clients consider a connection without heartbeats dropped.
+pub const HEARTBEAT_SIGNAL: &str = "ws-heartbeat";
+pub const HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Starts bumping the [`HEARTBEAT_SIGNAL`].
+#[cfg(feature = "ssr")]
+pub fn spawn_heartbeat(server_signals: leptos_ws::WsSignals) {
+    let owner = Owner::new();
+    let Ok(heartbeat) = owner.with(|| {
+        provide_context(server_signals);
+        leptos_ws::ReadOnlySignal::new(HEARTBEAT_SIGNAL, 0_u64)
+    }) else {
+        log::error!("Failed to create the websocket heartbeat");
+        return;
+    };
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            heartbeat.update(|beat| *beat = beat.wrapping_add(1));
+        }
+    });
+}
+
+#[cfg(feature = "ssr")]
+struct Connection {
+    session_id: String,
+    user_id: Option<Uuid>,
+    close: tokio::sync::watch::Sender<bool>,
+    /// Forwarding tasks of the group channels this connection joined.
+    subscriptions: HashMap<Uuid, Vec<tokio::task::AbortHandle>>,
+}
+
+/// Registry of all open websocket connections of this server instance.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Default)]
+pub struct WsConnections {
+    connections: Arc<Mutex<HashMap<String, Connection>>>,
+}
+
+#[cfg(feature = "ssr")]
+impl WsConnections {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn register(
+        &self,
+        session_id: String,
+        user_id: Option<Uuid>,
+    ) -> (String, tokio::sync::watch::Receiver<bool>) {
+        let id = nanoid::nanoid!();
+        let (close, closed) = tokio::sync::watch::channel(false);
+        self.connections
+            .lock()
+            .expect("websocket registry poisoned")
+            .insert(
+                id.clone(),
+                Connection {
+                    session_id,
+                    user_id,
+                    close,
+                    subscriptions: HashMap::new(),
+                },
+            );
+        (id, closed)
+    }
+
+    fn unregister(&self, id: &str) {
+        self.connections
+            .lock()
+            .expect("websocket registry poisoned")
+            .remove(id);
+    }
+
+    fn track_subscription(
+        &self,
+        id: &str,
+        group_id: Uuid,
+        task: Option<tokio::task::AbortHandle>,
+    ) {
+        if let Some(connection) = self
+            .connections
+            .lock()
+            .expect("websocket registry poisoned")
+            .get_mut(id)
+        {
+            connection
+                .subscriptions
+                .entry(group_id)
+                .or_default()
+                .extend(task);
+        }
+    }
+
+    fn is_subscribed(&self, id: &str, group_id: Uuid) -> bool {
+        self.connections
+            .lock()
+            .expect("websocket registry poisoned")
+            .get(id)
+            .is_some_and(|connection| connection.subscriptions.contains_key(&group_id))
+    }
+
+    /// Stops delivering the channels of a group to all connections of a user,
+    /// call this whenever the user stops being a member.
+    pub fn revoke_group(&self, user_id: Uuid, group_id: Uuid) {
+        let mut connections = self
+            .connections
+            .lock()
+            .expect("websocket registry poisoned");
+        for connection in connections
+            .values_mut()
+            .filter(|connection| connection.user_id == Some(user_id))
+        {
+            for task in connection
+                .subscriptions
+                .remove(&group_id)
+                .unwrap_or_default()
+            {
+                task.abort();
+            }
+        }
+    }
+
+    /// Closes every websocket that was opened by one of the given sessions.
+    pub fn close_sessions(&self, session_ids: &[String]) {
+        let connections = self
+            .connections
+            .lock()
+            .expect("websocket registry poisoned");
+        for connection in connections
+            .values()
+            .filter(|connection| session_ids.contains(&connection.session_id))
+        {
+            log::info!(
+                "Closing websocket of revoked session for user {:?}",
+                connection.user_id
+            );
+            connection.close.send_replace(true);
+        }
+    }
+}
+
+#[server(protocol = Websocket<JsonEncoding, JsonEncoding>, endpoint = "authorized_websocket")]
+pub async fn authorized_websocket(
+    input: BoxedStream<Messages, ServerFnError>,
+) -> Result<BoxedStream<Messages, ServerFnError>, ServerFnError> {
+    use crate::AppState;
+    use crate::auth::auth;
+    use crate::presence::PresentUser;
+    use futures::{StreamExt, channel::mpsc};
+    use leptos_ws::WsSignals;
+
+    let Some(state) = use_context::<AppState>() else {
+        return Err(ServerFnError::ServerError("AppState not found".into()));
+    };
+    let Some(server_signals) = use_context::<WsSignals>() else {
+        return Err(ServerFnError::ServerError("WsSignals not found".into()));
+    };
+    let auth = auth().await?;
+    let user_id = auth.current_user.as_ref().map(|user| user.id);
+    let present_user = match user_id {
+        Some(user_id) => {
+            let profile = state.profile_repository.get_by_user_id(user_id).await?;
+            Some(PresentUser {
+                avatar_url: profile.avatar_url(),
+                username: profile.username,
+            })
+        }
+        None => None,
+    };
+    // Registered last, nothing may fail before the task below unregisters it
+    let (connection_id, closed) = state
+        .ws_connections
+        .register(auth.session.get_session_id(), user_id);
+
+    let mut input = input;
+    let (tx, rx) = mpsc::channel(1);
+    let mut input_closed = closed.clone();
+    tokio::spawn(async move {
+        loop {
+            let message = tokio::select! {
+                _ = input_closed.wait_for(|closed| *closed) => break,
+                message = input.next() => message,
+            };
+            let Some(Ok(message)) = message else {
+                break;
+            };
+            let connection = ConnectionInfo {
+                state: &state,
+                id: &connection_id,
+                user_id,
+                user: present_user.as_ref(),
+            };
+            handle_message(&server_signals, connection, message, &tx).await;
+        }
+        state.ws_connections.unregister(&connection_id);
+        state.presence.disconnect(&connection_id);
+    });
+
+    let mut output_closed = closed;
+    Ok(rx
+        .take_until(async move {
+            let _ = output_closed.wait_for(|closed| *closed).await;
+        })
+        .into())
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Copy)]
+struct ConnectionInfo<'a> {
+    state: &'a crate::AppState,
+    id: &'a str,
+    user_id: Option<Uuid>,
+    user: Option<&'a crate::presence::PresentUser>,
+}
+
+/// The group a channel or signal belongs to, `None` for names that aren't group scoped.
+#[cfg(feature = "ssr")]
+fn channel_group(name: &str) -> Option<Uuid> {
+    name.strip_suffix("-activity")
+        .or_else(|| name.strip_suffix("-presence"))
+        .unwrap_or(name)
+        .parse()
+        .ok()
+}
+
+/// The group of a presence event channel.
+#[cfg(feature = "ssr")]
+fn presence_group(name: &str) -> Option<Uuid> {
+    name.strip_suffix("-presence")?.parse().ok()
+}
+
+/// The group of a read-only activity signal.
+#[cfg(feature = "ssr")]
+fn activity_group(name: &str) -> Option<Uuid> {
+    name.strip_suffix("-activity")?.parse().ok()
+}
+
+/// The owner of a user event channel.
+#[cfg(feature = "ssr")]
+fn user_channel_owner(name: &str) -> Option<Uuid> {
+    name.strip_prefix("user-")?.parse().ok()
+}
+
+/// Whether the connection may join the channel or signal `name`.
+#[cfg(feature = "ssr")]
+async fn may_join(connection: ConnectionInfo<'_>, name: &str) -> bool {
+    if let Some(owner) = user_channel_owner(name) {
+        return connection.user_id == Some(owner);
+    }
+    let Some(group_id) = channel_group(name) else {
+        return true;
+    };
+    let Some(user_id) = connection.user_id else {
+        return false;
+    };
+    match connection
+        .state
+        .group_repository
+        .is_member(group_id, user_id)
+        .await
+    {
+        Ok(is_member) => is_member,
+        Err(error) => {
+            log::error!("Failed to check membership of {user_id} in {group_id}: {error}");
+            false
+        }
+    }
+}
+
+/// Whether the connection may write to `name`, which requires having joined it.
+#[cfg(feature = "ssr")]
+fn may_write(connection: ConnectionInfo<'_>, name: &str) -> bool {
+    if user_channel_owner(name).is_some() {
+        return false;
+    }
+    channel_group(name).is_none_or(|group_id| {
+        connection
+            .state
+            .ws_connections
+            .is_subscribed(connection.id, group_id)
+    })
+}
+
+#[cfg(feature = "ssr")]
+async fn handle_message(
+    server_signals: &leptos_ws::WsSignals,
+    connection: ConnectionInfo<'_>,
+    message: Messages,
+    tx: &futures::channel::mpsc::Sender<Result<Messages, ServerFnError>>,
+) {
+    use futures::SinkExt;
+    use leptos_ws::messages::{BiDirectionalMessage, ChannelMessage, ServerSignalMessage};
+
+    match message {
+        Messages::ServerSignal(ServerSignalMessage::Establish(name)) => {
+            if !may_join(connection, &name).await {
+                log::warn!("Rejected subscription of {:?} to {name}", connection.user_id);
+                return;
+            }
+            if let Some(group_id) = activity_group(&name) {
+                connection.state.presence.signal(group_id);
+            }
+            let (Some(receiver), Some(Ok(value))) =
+                (server_signals.add_observer(&name), server_signals.json(&name))
+            else {
+                log::warn!("Client tried to establish unknown signal {name}");
+                return;
+            };
+            let response = Messages::ServerSignal(ServerSignalMessage::EstablishResponse((
+                name.clone(),
+                value,
+            )));
+            subscribe(connection, &name, response, receiver, tx).await;
+        }
+        Messages::BiDirectional(BiDirectionalMessage::Establish(name)) => {
+            if !may_join(connection, &name).await {
+                log::warn!("Rejected subscription of {:?} to {name}", connection.user_id);
+                return;
+            }
+            let (Some(receiver), Some(Ok(value))) =
+                (server_signals.add_observer(&name), server_signals.json(&name))
+            else {
+                log::warn!("Client tried to establish unknown signal {name}");
+                return;
+            };
+            let response = Messages::BiDirectional(BiDirectionalMessage::EstablishResponse((
+                name.clone(),
+                value,
+            )));
+            subscribe(connection, &name, response, receiver, tx).await;
+        }
+        Messages::BiDirectional(BiDirectionalMessage::Update(update)) => {
+            let Some(name) = update_name(&update) else {
+                return;
+            };
+            // Activity is owned by the server
+            if activity_group(&name).is_some() || !may_write(connection, &name) {
+                log::warn!("Rejected update of {:?} to {name}", connection.user_id);
+                return;
+            }
+            server_signals
+                .update(&name, update, Some(connection.id.to_string()))
+                .await;
+        }
+        Messages::Channel(ChannelMessage::Establish(name)) if name == COMMAND_CHANNEL => {
+            // Commands are answered directly, nothing to forward
+            let response = Messages::Channel(ChannelMessage::EstablishResponse(name));
+            let _ = tx.clone().send(Ok(response)).await;
+        }
+        Messages::Channel(ChannelMessage::Message(name, value)) if name == COMMAND_CHANNEL => {
+            handle_command(connection, value, tx).await;
+        }
+        Messages::Channel(ChannelMessage::Establish(name)) => {
+            if !may_join(connection, &name).await {
+                log::warn!("Rejected subscription of {:?} to {name}", connection.user_id);
+                return;
+            }
+            if let Some(group_id) = presence_group(&name) {
+                // Presence events only go from the client to this endpoint, nothing to forward
+                let response = Messages::Channel(ChannelMessage::EstablishResponse(name));
+                if tx.clone().send(Ok(response)).await.is_ok() {
+                    connection
+                        .state
+                        .ws_connections
+                        .track_subscription(connection.id, group_id, None);
+                }
+                return;
+            }
+            if let Some(user_id) = user_channel_owner(&name) {
+                connection.state.user_events.channel(user_id);
+            }
+            let Some(receiver) = server_signals.add_observer_channel(&name) else {
+                log::warn!("Client tried to establish unknown channel {name}");
+                return;
+            };
+            let response = Messages::Channel(ChannelMessage::EstablishResponse(name.clone()));
+            subscribe(connection, &name, response, receiver, tx).await;
+        }
+        Messages::Channel(ChannelMessage::Message(name, value)) => {
+            if !may_write(connection, &name) {
+                log::warn!("Rejected message of {:?} to {name}", connection.user_id);
+                return;
+            }
+            match (presence_group(&name), connection.user) {
+                (Some(group_id), Some(user)) => match serde_json::from_value(value) {
+                    Ok(event) => {
+                        connection
+                            .state
+                            .presence
+                            .record(group_id, connection.id, user, event)
+                    }
+                    Err(error) => log::warn!("Invalid presence event: {error}"),
+                },
+                (Some(_), None) => {}
+                (None, _) => {
+                    server_signals.handle_message(&name, value);
+                }
+            }
+        }
+        _ => log::error!("Unexpected websocket message from client"),
+    }
+}
+
+/// Runs a [`ChatCommand`] of the connection's user and acknowledges it as soon
+/// as the message is stored.
+#[cfg(feature = "ssr")]
+async fn handle_command(
+    connection: ConnectionInfo<'_>,
+    value: serde_json::Value,
+    tx: &futures::channel::mpsc::Sender<Result<Messages, ServerFnError>>,
+) {
+    use crate::server_fn::chat::{ChatCommand, ChatError, broadcast, store};
+
+    let command = match serde_json::from_value(value) {
+        Ok(command) => command,
+        Err(error) => {
+            log::warn!("Invalid chat command: {error}");
+            return;
+        }
+    };
+    let ChatCommand::Publish {
+        group_id,
+        text,
+        message_id,
+    } = command
+    else {
+        log::warn!("Unexpected chat command from client");
+        return;
+    };
+    let (Some(user_id), Some(user)) = (connection.user_id, connection.user) else {
+        send_ack(message_id, Err(ChatError::Unauthorized), tx).await;
+        return;
+    };
+    let stored = store(
+        connection.state,
+        user_id,
+        group_id,
+        text,
+        Some(message_id.clone()),
+    )
+    .await;
+    // The sender only waits for the message to be stored, not for it to reach the group
+    let message = match stored {
+        Ok(message) => {
+            send_ack(message_id, Ok(()), tx).await;
+            message
+        }
+        Err(error) => {
+            send_ack(message_id, Err(error), tx).await;
+            return;
+        }
+    };
+    if let Some(message) = message
+        && let Err(error) = broadcast(connection.state, &message, user.username.clone(), None).await
+    {
+        log::error!("Failed to broadcast message {}: {error}", message.id);
+    }
+}
+
+#[cfg(feature = "ssr")]
+async fn send_ack(
+    message_id: String,
+    result: Result<(), crate::server_fn::chat::ChatError>,
+    tx: &futures::channel::mpsc::Sender<Result<Messages, ServerFnError>>,
+) {
+    use crate::server_fn::chat::ChatCommand;
+    use futures::SinkExt;
+    use leptos_ws::messages::ChannelMessage;
+
+    let Ok(ack) = serde_json::to_value(ChatCommand::Ack { message_id, result }) else {
+        return;
+    };
+    let response = Messages::Channel(ChannelMessage::Message(COMMAND_CHANNEL.to_string(), ack));
+    let _ = tx.clone().send(Ok(response)).await;
+}
+
+/// Confirms the subscription and starts forwarding its broadcasts,
+/// remembering the task of group channels so it can be revoked.
+#[cfg(feature = "ssr")]
+async fn subscribe(
+    connection: ConnectionInfo<'_>,
+    name: &str,
+    response: Messages,
+    receiver: tokio::sync::broadcast::Receiver<(Option<String>, Messages)>,
+    tx: &futures::channel::mpsc::Sender<Result<Messages, ServerFnError>>,
+) {
+    use futures::SinkExt;
+
+    if tx.clone().send(Ok(response)).await.is_err() {
+        return;
+    }
+    let task = tokio::spawn(forward_broadcasts(
+        connection.id.to_string(),
+        receiver,
+        tx.clone(),
+    ));
+    if let Some(group_id) = channel_group(name) {
+        connection
+            .state
+            .ws_connections
+            .track_subscription(connection.id, group_id, Some(task.abort_handle()));
+    }
+}
+
+/// `SignalUpdate` keeps its signal name crate private, but serializes it.
+#[cfg(feature = "ssr")]
+fn update_name(update: &leptos_ws::messages::SignalUpdate) -> Option<String> {
+    serde_json::to_value(update)
+        .ok()?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Forwards broadcasts of one signal or channel to the websocket,
+/// skipping updates that originated from this connection.
+#[cfg(feature = "ssr")]
+async fn forward_broadcasts(
+    connection_id: String,
+    mut receiver: tokio::sync::broadcast::Receiver<(Option<String>, Messages)>,
+    mut sink: futures::channel::mpsc::Sender<Result<Messages, ServerFnError>>,
+) {
+    use futures::SinkExt;
+
+    loop {
+        let (origin, message) = match receiver.recv().await {
+            Ok(update) => update,
+            // A slow connection misses the skipped updates, but keeps getting new ones
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                log::warn!("Connection {connection_id} skipped {skipped} updates");
+                continue;
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+        };
+        if origin.is_some_and(|origin| origin == connection_id) {
+            continue;
+        }
+        if sink.send(Ok(message)).await.is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures::{StreamExt as _, channel::mpsc};
+    use leptos_ws::messages::ChannelMessage;
+    use tokio::sync::broadcast;
+
+    use super::*;
+    use crate::{
+        AppState,
+        backplane::{Backplane, BackplaneEvent},
+        config::Config,
+        domain::group::Group,
+        server_fn::chat::{ChatChannelMessages, ChatCommand, ChatError},
+        test_support,
+    };
+
+    type Acks = mpsc::Receiver<Result<Messages, ServerFnError>>;
+
+    /// Records for every new message whether its sender was acknowledged already.
+    struct AckCheckingBackplane {
+        acks: Mutex<Acks>,
+        acked_before_broadcast: Mutex<Vec<bool>>,
+    }
+
+    impl Backplane for AckCheckingBackplane {
+        fn publish(&self, event: BackplaneEvent) {
+            if let BackplaneEvent::Chat {
+                message: ChatChannelMessages::NewMessage(_),
+                ..
+            } = event
+            {
+                let acked = matches!(self.acks.lock().unwrap().try_next(), Ok(Some(_)));
+                self.acked_before_broadcast.lock().unwrap().push(acked);
+            }
+        }
+
+        fn subscribe(&self) -> broadcast::Receiver<BackplaneEvent> {
+            broadcast::channel(1).1
+        }
+    }
+
+    async fn member(state: &AppState) -> (Uuid, Uuid) {
+        let user_id = test_support::user(state, "alice").await;
+        let group_id = state
+            .group_repository
+            .create_group(Group::new("acks".to_string()))
+            .await
+            .unwrap();
+        state
+            .group_repository
+            .add_member(group_id, user_id)
+            .await
+            .unwrap();
+        (user_id, group_id)
+    }
+
+    fn publish_command(group_id: Uuid, message_id: &str) -> serde_json::Value {
+        serde_json::to_value(ChatCommand::Publish {
+            group_id: group_id.to_string(),
+            text: "Hello".to_string(),
+            message_id: message_id.to_string(),
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn acks_before_broadcasting() {
+        let mut state = test_support::state(Config::default()).await;
+        let (user_id, group_id) = member(&state).await;
+        let (tx, acks) = mpsc::channel(1);
+        let backplane = Arc::new(AckCheckingBackplane {
+            acks: Mutex::new(acks),
+            acked_before_broadcast: Mutex::default(),
+        });
+        state.backplane = backplane.clone();
+        let user = crate::presence::PresentUser {
+            username: "alice".to_string(),
+            avatar_url: String::new(),
+        };
+        let connection = ConnectionInfo {
+            state: &state,
+            id: "acks",
+            user_id: Some(user_id),
+            user: Some(&user),
+        };
+
+        let message_id = Uuid::new_v4().to_string();
+        handle_command(connection, publish_command(group_id, &message_id), &tx).await;
+        // A retry is acknowledged again but not broadcast
+        handle_command(connection, publish_command(group_id, &message_id), &tx).await;
+
+        assert_eq!(*backplane.acked_before_broadcast.lock().unwrap(), [true]);
+        let mut acks = backplane.acks.lock().unwrap();
+        let Ok(Some(Ok(Messages::Channel(ChannelMessage::Message(channel, ack))))) =
+            acks.try_next()
+        else {
+            panic!("Expected the ack of the retry");
+        };
+        assert_eq!(channel, COMMAND_CHANNEL);
+        let ChatCommand::Ack {
+            message_id: acked,
+            result,
+        } = serde_json::from_value(ack).unwrap()
+        else {
+            panic!("Expected an ack");
+        };
+        assert_eq!(acked, message_id);
+        assert!(result.is_ok(), "{result:?}");
+    }
+
+    #[tokio::test]
+    async fn acks_failures_without_broadcasting() {
+        let state = test_support::state(Config::default()).await;
+        let (_, group_id) = member(&state).await;
+        let mut events = state.backplane.subscribe();
+        let (tx, mut acks) = mpsc::channel(1);
+        let connection = ConnectionInfo {
+            state: &state,
+            id: "anonymous",
+            user_id: None,
+            user: None,
+        };
+
+        let message_id = Uuid::new_v4().to_string();
+        handle_command(connection, publish_command(group_id, &message_id), &tx).await;
+
+        let Some(Ok(Messages::Channel(ChannelMessage::Message(_, ack)))) = acks.next().await else {
+            panic!("Expected an ack");
+        };
+        let ack = serde_json::from_value(ack).unwrap();
+        assert!(
+            matches!(
+                ack,
+                ChatCommand::Ack {
+                    result: Err(ChatError::Unauthorized),
+                    ..
+                }
+            ),
+            "{ack:?}"
+        );
+        assert!(events.try_recv().is_err());
+    }
+}
@@ -1,16 +1,26 @@
+pub mod api_tokens;
+pub mod blocked_users;
 pub mod button;
 pub mod card;
 pub mod chat;
 pub mod checkbox;
+pub mod connection;
 pub mod detect_mobile;
 pub mod dialog;
 pub mod dropdown;
 pub mod groups;
 pub mod header;
+pub mod identities;
 pub mod include_svg;
 pub mod input;
 pub mod input_bar;
+pub mod link_preview;
 pub mod multi_step;
+pub mod polls;
+pub mod profile;
+pub mod scheduled;
+pub mod sessions;
 pub mod spinner;
 pub mod text_box;
 pub mod theme_switcher;
+pub mod webhooks;
@@ -0,0 +1,148 @@
+use api::server_fn::tokens::{
+    ApiTokenInfo, CreateApiToken, RevokeApiToken, TokenScope, list_api_tokens,
+};
+use chrono::{DateTime, Local};
+use leptos::prelude::*;
+
+use crate::components::{
+    button::{Button, ButtonVariant, Sizing},
+    checkbox::Checkbox,
+    input::InputField,
+};
+
+leptos_styling::style_sheet!(
+    api_tokens_styles,
+    "src/components/api_tokens/api_tokens.module.scss",
+    "api-tokens"
+);
+
+fn scope_checkbox_id(scope: TokenScope) -> &'static str {
+    match scope {
+        TokenScope::MessagesRead => "scope-messages-read",
+        TokenScope::MessagesWrite => "scope-messages-write",
+        TokenScope::GroupsRead => "scope-groups-read",
+    }
+}
+
+#[component]
+pub fn ApiTokens() -> impl IntoView {
+    let create = ServerAction::<CreateApiToken>::new();
+    let revoke = ServerAction::<RevokeApiToken>::new();
+    let tokens = Resource::new(
+        move || (create.version().get(), revoke.version().get()),
+        |_| list_api_tokens(),
+    );
+
+    let name = RwSignal::new(String::new());
+    let scopes = RwSignal::new(vec![TokenScope::MessagesWrite]);
+    let expires_in_days = RwSignal::new(Some(30));
+
+    let created = move || create.value().get().and_then(|result| result.ok());
+    let error = move || {
+        create
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .or_else(|| revoke.value().get().and_then(|result| result.err()))
+            .map(|error| error.to_string())
+    };
+
+    view! {
+        <div class=api_tokens_styles::API_TOKENS>
+            <div class=api_tokens_styles::FORM>
+                <InputField name="token_name" label="Name" placeholder="CI notifications" maxlength=64 value=name/>
+                <span class=api_tokens_styles::LABEL>"Scopes"</span>
+                {TokenScope::ALL.map(|scope| view! {
+                    <Checkbox
+                        id=scope_checkbox_id(scope)
+                        label=scope.description()
+                        checked=Signal::derive(move || scopes.read().contains(&scope))
+                        on_change=Callback::new(move |checked| scopes.update(|scopes| {
+                            scopes.retain(|s| *s != scope);
+                            if checked {
+                                scopes.push(scope);
+                            }
+                        }))
+                    />
+                })}
+                <label class=api_tokens_styles::LABEL for="token_expiry">"Expiration"</label>
+                <select id="token_expiry" on:change=move |ev| {
+                    expires_in_days.set(event_target_value(&ev).parse().ok());
+                }>
+                    <option value="7">"7 days"</option>
+                    <option value="30" selected>"30 days"</option>
+                    <option value="90">"90 days"</option>
+                    <option value="365">"1 year"</option>
+                    <option value="never">"Never"</option>
+                </select>
+                <Button variant=ButtonVariant::Primary sizing={Sizing::Small} {..} on:click=move |_| {
+                    create.dispatch(CreateApiToken {
+                        name: name.get_untracked(),
+                        scopes: scopes.get_untracked(),
+                        expires_in_days: expires_in_days.get_untracked(),
+                    });
+                }>
+                    "Generate token"
+                </Button>
+            </div>
+            {move || created().map(|created| view! {
+                <div class=api_tokens_styles::SECRET>
+                    <code>{created.secret}</code>
+                    <p>"Copy this token now, it won't be shown again. Send it as "<code>"Authorization: Bearer <token>"</code>"."</p>
+                </div>
+            })}
+            {move || error().map(|error| view! { <p class=api_tokens_styles::ERROR>{error}</p> })}
+            <Suspense>
+                {move || {
+                    tokens.and_then(|tokens| {
+                        let tokens = tokens.to_owned();
+                        view! {
+                            <For each=move || tokens.clone() key=|token| token.id.clone() let:token>
+                                <ApiTokenItem token revoke/>
+                            </For>
+                        }
+                    })
+                }}
+            </Suspense>
+        </div>
+    }
+}
+
+#[component]
+fn ApiTokenItem(token: ApiTokenInfo, revoke: ServerAction<RevokeApiToken>) -> impl IntoView {
+    let expired = token.is_expired();
+    let scopes = token
+        .scopes
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let expires = token.expires_at.map_or("never expires".to_string(), |expires_at| {
+        let expires_at: DateTime<Local> = DateTime::from(expires_at);
+        format!("expires {}", expires_at.format("%d.%m.%Y"))
+    });
+    let last_used = token.last_used_at.map_or("never used".to_string(), |last_used_at| {
+        let last_used_at: DateTime<Local> = DateTime::from(last_used_at);
+        format!("last used {}", last_used_at.format("%d.%m.%Y %H:%M"))
+    });
+    let id = token.id.clone();
+    view! {
+        <div class=api_tokens_styles::TOKEN>
+            <div class=api_tokens_styles::DETAILS>
+                <h3>
+                    {token.name.clone()}
+                    <Show when=move || expired>
+                        <span class=api_tokens_styles::EXPIRED>"Expired"</span>
+                    </Show>
+                </h3>
+                <p>{format!("{}… · {scopes}", token.prefix)}</p>
+                <p>{format!("{expires} · {last_used}")}</p>
+            </div>
+            <Button variant=ButtonVariant::Danger sizing={Sizing::Small} {..} on:click=move |_| {
+                revoke.dispatch(RevokeApiToken { id: id.clone() });
+            }>
+                "Revoke"
+            </Button>
+        </div>
+    }
+}
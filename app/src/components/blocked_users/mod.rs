@@ -0,0 +1,67 @@
+use api::server_fn::blocks::{BlockedUserInfo, UnblockUser, list_blocked_users};
+use chrono::{DateTime, Local};
+use leptos::prelude::*;
+
+use crate::components::button::{Button, ButtonVariant, Sizing};
+
+leptos_styling::style_sheet!(
+    blocked_users_styles,
+    "src/components/blocked_users/blocked_users.module.scss",
+    "blocked-users"
+);
+
+#[component]
+pub fn BlockedUsers() -> impl IntoView {
+    let unblock = ServerAction::<UnblockUser>::new();
+    let blocked = Resource::new(move || unblock.version().get(), |_| list_blocked_users());
+    let error = move || {
+        unblock
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|error| error.to_string())
+    };
+
+    view! {
+        <div class=blocked_users_styles::BLOCKED_USERS>
+            <Suspense>
+                {move || {
+                    blocked.and_then(|blocked| {
+                        let blocked = blocked.to_owned();
+                        view! {
+                            <Show when={
+                                let blocked = blocked.clone();
+                                move || blocked.is_empty()
+                            }>
+                                <p class=blocked_users_styles::EMPTY>"You haven't blocked anyone."</p>
+                            </Show>
+                            <For each=move || blocked.clone() key=|user| user.username.clone() let:user>
+                                <BlockedUserItem user unblock/>
+                            </For>
+                        }
+                    })
+                }}
+            </Suspense>
+            {move || error().map(|error| view! { <p class=blocked_users_styles::ERROR>{error}</p> })}
+        </div>
+    }
+}
+
+#[component]
+fn BlockedUserItem(user: BlockedUserInfo, unblock: ServerAction<UnblockUser>) -> impl IntoView {
+    let blocked_at: DateTime<Local> = DateTime::from(user.blocked_at);
+    let username = user.username.clone();
+    view! {
+        <div class=blocked_users_styles::USER>
+            <div class=blocked_users_styles::DETAILS>
+                <h3>{user.display_name.clone().unwrap_or_else(|| user.username.clone())}</h3>
+                <p>{format!("@{} · blocked {}", user.username, blocked_at.format("%d.%m.%Y"))}</p>
+            </div>
+            <Button variant=ButtonVariant::Secondary sizing={Sizing::Small} {..} on:click=move |_| {
+                unblock.dispatch(UnblockUser { username: username.clone() });
+            }>
+                "Unblock"
+            </Button>
+        </div>
+    }
+}
@@ -1,42 +1,105 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashSet, VecDeque};
 
+use api::presence::{GroupPresence, PresenceEvent, activity_signal_name, presence_channel_name};
+use api::server_fn::blocks::list_blocked_users;
 use api::server_fn::chat::{
-    ChatChannelMessages, ChatMessage, ChatSender, fetch_messages, publish_message,
+    CATCH_UP_LIMIT, COMMAND_CHANNEL, ChatChannelMessages, ChatCommand, ChatError, ChatMessage, ChatSender,
+    Delivery, LinkPreview, MessageContent, SentChatMessage, fetch_messages, fetch_messages_after,
+    publish_message,
 };
-use chrono::{DateTime, Duration, Local, Utc};
-use leptos::{prelude::*, task::spawn_local};
+use api::server_fn::commands::{
+    CommandError, list_commands, parse_command, run_command, unescape_command,
+};
+use api::server_fn::polls::{ChatPoll, PollResults};
+use chrono::{DateTime, Local, Utc};
+use leptos::{either::Either, prelude::*, task::spawn_local};
 use leptos_styling::style_sheet;
-use serde::{Deserialize, Serialize};
+use leptos_ws::ChannelSignal;
 
 use crate::{
     components::{
         card::{Card, CardBody, CardHeader},
-        input_bar::{InputBar, Person},
+        connection::{ConnectionIndicator, ConnectionState},
+        groups::describe_message_ttl,
+        input_bar::InputBar,
+        link_preview::LinkPreviewCard,
+        polls::{CreatePollDialog, PollCard},
+        profile::ProfilePopover,
+        scheduled::ScheduledMessagesDialog,
     },
     contexts::account_context::AccountContext,
 };
 
 style_sheet!(chat_styles, "src/components/chat/chat.module.scss", "chat");
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct ReadersAndWriters {
-    readers: HashMap<String, Person>,
-    writers: HashMap<String, Person>,
+
+/// Messages sent over the websocket without an answer in time are sent over HTTP.
+const ACK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// The live state of a mounted [`Chat`], subscribed to by [`ChatSubscriptions`].
+#[derive(Clone)]
+struct ChatSubscription {
+    group_id: String,
+    receive: Callback<SentChatMessage>,
+    poll_updated: Callback<PollResults>,
+    /// Ids of messages that were deleted, e.g. because they expired.
+    deleted: Callback<String>,
+    /// Previews of links in messages, by message id.
+    link_preview: Callback<(String, LinkPreview)>,
+    /// Answers to the commands sent on `commands`, by message id.
+    ack: Callback<(String, Result<(), ChatError>)>,
+    /// The command channel of the current connection.
+    commands: StoredValue<Option<ChannelSignal<ChatCommand>>>,
+    presence: RwSignal<GroupPresence>,
+    writing: RwSignal<bool>,
+}
+
+/// Connects a [`Chat`] to the websocket of its page, which renders
+/// [`ChatSubscriptions`] inside its [`LiveConnection`](crate::components::connection::LiveConnection).
+#[derive(Clone, Copy, Default)]
+pub struct LiveChat {
+    pub connection: RwSignal<ConnectionState>,
+    subscription: RwSignal<Option<ChatSubscription>>,
 }
 
 #[component]
-pub fn Chat(group_id: String) -> impl IntoView {
+pub fn Chat(
+    group_id: String,
+    /// Shown above the messages, set with `/topic`.
+    #[prop(into, optional)]
+    topic: Signal<Option<String>>,
+    /// Seconds after which messages of the group disappear, if they do.
+    #[prop(into, optional)]
+    message_ttl_secs: Signal<Option<i64>>,
+) -> impl IntoView {
     let chat_ref = NodeRef::<leptos::html::Div>::new();
     let page_size = 40;
     let offset = RwSignal::new(0);
-    let messages = RwSignal::new(VecDeque::new());
+    let messages = RwSignal::new(VecDeque::<ChatMessage>::new());
+
+    // Messages of blocked users are filtered by the server, reload when that changes
+    let blocks_version = RwSignal::new(0);
+    let blocked = Resource::new(
+        move || blocks_version.get(),
+        |_| async {
+            list_blocked_users()
+                .await
+                .map(|blocked| {
+                    blocked
+                        .into_iter()
+                        .map(|user| user.user_id)
+                        .collect::<HashSet<_>>()
+                })
+                .unwrap_or_default()
+        },
+    );
 
     // Resource for the initial fetch
     let initial_messages = Resource::new(
         {
             let group_id = group_id.clone();
-            move || group_id.clone()
+            move || (group_id.clone(), blocks_version.get())
         },
-        move |group_id| {
+        move |(group_id, _)| {
             let group_id = group_id.clone();
             async move { fetch_messages(group_id, 0, page_size).await.ok() }
         },
@@ -44,7 +107,15 @@ pub fn Chat(group_id: String) -> impl IntoView {
     Effect::new(move |_| {
         if let Some(Some(fetched)) = initial_messages.get() {
             let count = fetched.len();
-            messages.set(fetched.into_iter().rev().collect());
+            messages.update(|msgs| {
+                // Keep what this client is still sending
+                let unsent = msgs
+                    .drain(..)
+                    .filter(|msg| msg.delivery != Delivery::Delivered)
+                    .collect::<Vec<_>>();
+                *msgs = fetched.into_iter().rev().collect();
+                msgs.extend(unsent);
+            });
             offset.set(count as i64);
         }
     });
@@ -90,100 +161,345 @@ pub fn Chat(group_id: String) -> impl IntoView {
         }
     };
     let account = use_context::<AccountContext>().expect("AccountContext not found");
-    let username = move || {
+    let user_id = move || {
         account
             .user_untracked()
-            .and_then(|v| v.username().map(|v| v.to_string()))
+            .and_then(|v| v.id().map(|v| v.to_string()))
     };
-    let new_messages =
-        leptos_ws::ChannelSignal::<ChatChannelMessages>::new(&group_id).and_then(|signal| {
-            signal.on_client(move |msg| match msg {
-                ChatChannelMessages::NewMessage(msg) => {
-                    messages.update(|msgs| {
-                        msgs.push_back(ChatMessage {
-                            text: msg.text.clone(),
-                            time: msg.time.clone(),
-                            sender: if let Some(username) = username()
-                                && username == msg.username
-                            {
-                                ChatSender::Sent
-                            } else {
-                                ChatSender::Received(msg.username.clone())
-                            },
-                        });
+    // Marks a message of this client as delivered, which adds it to the server's messages
+    let deliver = move |id: &str, time: Option<DateTime<Utc>>| {
+        let mut delivered = false;
+        messages.update(|msgs| {
+            if let Some(msg) = msgs
+                .iter_mut()
+                .find(|msg| msg.id == id && msg.delivery != Delivery::Delivered)
+            {
+                msg.delivery = Delivery::Delivered;
+                if let Some(time) = time {
+                    msg.time = time;
+                }
+                delivered = true;
+            }
+        });
+        if delivered {
+            offset.update(|o| *o += 1);
+        }
+    };
+    let receive = Callback::new(move |msg: SentChatMessage| {
+        if blocked
+            .get_untracked()
+            .is_some_and(|blocked| msg.is_from_any(&blocked))
+        {
+            return;
+        }
+        // Messages may arrive both live and through the catch up after a reconnect
+        if messages.with_untracked(|msgs| msgs.iter().any(|known| known.id == msg.id)) {
+            // The echo of a message sent by this client
+            deliver(&msg.id, Some(msg.time));
+            return;
+        }
+        messages.update(|msgs| {
+            msgs.push_back(ChatMessage {
+                id: msg.id.clone(),
+                text: msg.text.clone(),
+                time: msg.time,
+                sender: if !msg.is_webhook
+                    && user_id().is_some_and(|user_id| user_id == msg.user_id)
+                {
+                    ChatSender::Sent
+                } else {
+                    ChatSender::Received(msg.username.clone())
+                },
+                delivery: Delivery::Delivered,
+                poll: msg.poll.clone(),
+                link_preview: None,
+            });
+        });
+        offset.update(|o| *o += 1)
+    });
+    // Votes of this user come back from the server fn, everyone's through the channel
+    let update_poll = Callback::new(move |poll: ChatPoll| {
+        messages.update(|msgs| {
+            if let Some(msg) = msgs
+                .iter_mut()
+                .find(|msg| msg.id == poll.results.poll_id)
+            {
+                msg.poll = Some(poll);
+            }
+        });
+    });
+    let poll_updated = Callback::new(move |results: PollResults| {
+        messages.update(|msgs| {
+            if let Some(poll) = msgs
+                .iter_mut()
+                .find(|msg| msg.id == results.poll_id)
+                .and_then(|msg| msg.poll.as_mut())
+            {
+                poll.results = results;
+            }
+        });
+    });
+    let deleted = Callback::new(move |id: String| {
+        let mut removed = false;
+        messages.update(|msgs| {
+            if let Some(index) = msgs
+                .iter()
+                .position(|msg| msg.id == id && msg.delivery == Delivery::Delivered)
+            {
+                msgs.remove(index);
+                removed = true;
+            }
+        });
+        // Older pages move up by one
+        if removed {
+            offset.update(|o| *o -= 1);
+        }
+    });
+    let link_preview = Callback::new(move |(id, preview): (String, LinkPreview)| {
+        messages.update(|msgs| {
+            if let Some(msg) = msgs.iter_mut().find(|msg| msg.id == id) {
+                msg.link_preview = Some(preview);
+            }
+        });
+    });
+    let send_error = RwSignal::new(None::<ChatError>);
+    // Until when the server refuses new messages, shown by the input bar
+    let cooldown_until = RwSignal::new(None::<DateTime<Utc>>);
+    let fail = move |id: &str, error: ChatError| {
+        messages.update(|msgs| {
+            if let Some(msg) = msgs
+                .iter_mut()
+                .find(|msg| msg.id == id && msg.delivery == Delivery::Pending)
+            {
+                msg.delivery = Delivery::Failed;
+            }
+        });
+        if let ChatError::SlowDown { retry_after_ms } = error {
+            let retry_after = chrono::Duration::milliseconds(
+                i64::try_from(retry_after_ms).unwrap_or(i64::MAX),
+            );
+            cooldown_until.set(Some(Utc::now() + retry_after));
+            return;
+        }
+        send_error.set(Some(error));
+    };
+    let ack = Callback::new(move |(id, result): (String, Result<(), ChatError>)| {
+        match result {
+            Ok(()) => deliver(&id, None),
+            Err(error) => fail(&id, error),
+        }
+    });
+    let commands = StoredValue::new(None::<ChannelSignal<ChatCommand>>);
+    let live = expect_context::<LiveChat>();
+    let connection = live.connection;
+    // The id is chosen here, so retrying a message or falling back to HTTP can't send it twice
+    let send_http = Callback::new({
+        let group_id = group_id.clone();
+        move |(id, text): (String, String)| {
+            let group_id = group_id.clone();
+            spawn_local(async move {
+                let result = publish_message(group_id, text, Some(id.clone())).await;
+                match result {
+                    Ok(()) => deliver(&id, None),
+                    Err(error) => fail(&id, error),
+                }
+            });
+        }
+    });
+    let send = Callback::new({
+        let group_id = group_id.clone();
+        move |(id, text): (String, String)| {
+            let command = ChatCommand::Publish {
+                group_id: group_id.clone(),
+                text: text.clone(),
+                message_id: id.clone(),
+            };
+            let sent = connection.get_untracked() == ConnectionState::Connected
+                && commands.with_value(|commands| {
+                    commands
+                        .as_ref()
+                        .is_some_and(|commands| commands.send_message(command).is_ok())
+                });
+            if !sent {
+                send_http.run((id, text));
+                return;
+            }
+            set_timeout(
+                move || {
+                    let pending = messages.try_with_untracked(|msgs| {
+                        msgs.iter()
+                            .any(|msg| msg.id == id && msg.delivery == Delivery::Pending)
                     });
-                    offset.update(|o| *o += 1)
+                    if pending == Some(true) {
+                        log::warn!("No answer over the websocket, sending over HTTP");
+                        send_http.run((id, text));
+                    }
+                },
+                ACK_TIMEOUT,
+            );
+        }
+    });
+    // What the last slash command answered, only this user sees it
+    let command_result = RwSignal::new(None::<Result<String, CommandError>>);
+    let run = Callback::new({
+        let group_id = group_id.clone();
+        move |text: String| {
+            let group_id = group_id.clone();
+            send_error.set(None);
+            command_result.set(None);
+            spawn_local(async move {
+                match run_command(group_id, text).await {
+                    Ok(reply) => command_result.set(reply.map(Ok)),
+                    Err(error) => command_result.set(Some(Err(error))),
                 }
+            });
+        }
+    });
+    let available_commands = OnceResource::new(async {
+        list_commands().await.unwrap_or_default()
+    });
+    let submit = Callback::new(move |content: MessageContent| {
+        let text = String::from(content);
+        if parse_command(&text).is_some() {
+            run.run(text);
+            return;
+        }
+        let text = unescape_command(&text).to_string();
+        let id = uuid::Uuid::new_v4().to_string();
+        messages.update(|msgs| {
+            msgs.push_back(ChatMessage {
+                id: id.clone(),
+                text: text.clone(),
+                time: Utc::now(),
+                sender: ChatSender::Sent,
+                delivery: Delivery::Pending,
+                poll: None,
+                link_preview: None,
             })
         });
+        send_error.set(None);
+        command_result.set(None);
+        send.run((id, text));
+    });
+    let retry = move |id: String| {
+        let mut text = None;
+        messages.update(|msgs| {
+            if let Some(msg) = msgs
+                .iter_mut()
+                .find(|msg| msg.id == id && msg.delivery == Delivery::Failed)
+            {
+                msg.delivery = Delivery::Pending;
+                text = Some(msg.text.clone());
+            }
+        });
+        if let Some(text) = text {
+            send_error.set(None);
+            send.run((id, text));
+        }
+    };
+    let profile_username = RwSignal::new(None::<String>);
+    let creating_poll = RwSignal::new(false);
+    let scheduling = RwSignal::new(false);
     let writing = RwSignal::new(false);
-    Effect::new(move || {
-        writing.track();
-        log::info!("Writing effect triggered");
-    });
-    let writers_readers = leptos_ws::BiDirectionalSignal::<ReadersAndWriters>::new(
-        &format!("{}-activity", group_id),
-        ReadersAndWriters::default(),
-    )
-    .unwrap();
-    let writers_readers2 = writers_readers.clone();
-    let writers_readers3 = writers_readers.clone();
-
-    let writers = Memo::new(move |_| {
-        let writers = writers_readers2.read();
-        let current_time = Utc::now();
-        writers
-            .writers
-            .values()
-            .filter(|v| v.last_activity > current_time - Duration::seconds(20))
-            .into_iter()
-            .cloned()
-            .collect::<Vec<_>>()
-    });
-    let readers = Memo::new(move |_| {
-        let readers = writers_readers3.read();
-        let current_time = Utc::now();
-        readers
-            .readers
-            .values()
-            .filter(|v| v.last_activity > current_time - Duration::seconds(20))
-            .into_iter()
-            .cloned()
-            .collect::<Vec<_>>()
+    let presence = RwSignal::new(GroupPresence::default());
+    let writers = Memo::new(move |_| presence.read().writers.clone());
+    let readers = Memo::new(move |_| presence.read().readers.clone());
+
+    live.subscription.set(Some(ChatSubscription {
+        group_id: group_id.clone(),
+        receive,
+        poll_updated,
+        deleted,
+        link_preview,
+        ack,
+        commands,
+        presence,
+        writing,
+    }));
+    on_cleanup({
+        let group_id = group_id.clone();
+        move || {
+            // The chat of the next group may have registered already
+            live.subscription.try_update(|subscription| {
+                if subscription
+                    .as_ref()
+                    .is_some_and(|subscription| subscription.group_id == group_id)
+                {
+                    *subscription = None;
+                }
+            });
+        }
     });
-    Effect::new(move |_| {
-        let writers_readers = writers_readers.clone();
-        set_interval(
-            move || {
-                writers_readers.update(move |data| {
-                    let Some(username) = username() else { return };
-
-                    if writing.get_untracked() {
-                        data.readers.remove(&username);
-                        data.writers
-                            .entry(username.clone())
-                            .or_insert(Person {
-                                name: username.clone(),
-                                last_activity: Utc::now(),
-                            })
-                            .last_activity = Utc::now();
-                    } else {
-                        data.writers.remove(&username);
-                        data.readers
-                            .entry(username.clone())
-                            .or_insert(Person {
-                                name: username.clone(),
-                                last_activity: Utc::now(),
-                            })
-                            .last_activity = Utc::now();
-                    }
+
+    // Fetch what was missed while the websocket was down
+    Effect::new({
+        let group_id = group_id.clone();
+        move |previous: Option<ConnectionState>| {
+            let current = connection.get();
+            if current == ConnectionState::Connected
+                && previous == Some(ConnectionState::Reconnecting)
+            {
+                let last_id = messages.with_untracked(|msgs| {
+                    msgs.iter()
+                        .rev()
+                        .find(|msg| msg.delivery == Delivery::Delivered)
+                        .map(|msg| msg.id.clone())
                 });
-            },
-            std::time::Duration::from_secs(3),
-        );
+                match last_id {
+                    Some(last_id) => {
+                        let group_id = group_id.clone();
+                        spawn_local(async move {
+                            let mut after = last_id;
+                            // A full page means more were missed, continue after its newest message
+                            loop {
+                                match fetch_messages_after(group_id.clone(), after).await {
+                                    Ok(fetched) => {
+                                        let next = fetched
+                                            .last()
+                                            .filter(|_| fetched.len() == CATCH_UP_LIMIT as usize)
+                                            .map(|msg| msg.id.clone());
+                                        let mut added = 0;
+                                        messages.update(|msgs| {
+                                            for msg in fetched {
+                                                if !msgs.iter().any(|known| known.id == msg.id) {
+                                                    msgs.push_back(msg);
+                                                    added += 1;
+                                                }
+                                            }
+                                            msgs.make_contiguous().sort_by_key(|msg| msg.time);
+                                        });
+                                        offset.update(|o| *o += added);
+                                        match next {
+                                            Some(next) => after = next,
+                                            None => break,
+                                        }
+                                    }
+                                    Err(error) => {
+                                        log::error!("Failed to fetch missed messages: {error}");
+                                        initial_messages.refetch();
+                                        break;
+                                    }
+                                }
+                            }
+                        });
+                    }
+                    None => initial_messages.refetch(),
+                }
+            }
+            current
+        }
     });
     view! {
         <div class=chat_styles::CHAT_CONTAINER>
+            <ConnectionIndicator state=connection/>
+            {move || topic.get().map(|topic| view! {
+                <p class=chat_styles::TOPIC>{topic}</p>
+            })}
+            {move || message_ttl_secs.get().map(|ttl_secs| view! {
+                <p class=chat_styles::RETENTION>
+                    {format!("Messages disappear {} after they are sent", describe_message_ttl(ttl_secs))}
+                </p>
+            })}
             <div
                 class=chat_styles::CHAT
                 node_ref=chat_ref
@@ -196,36 +512,209 @@ pub fn Chat(group_id: String) -> impl IntoView {
                     }
                     key=|msg| msg.clone()
                     children=move |msg| {
-                        let class = match &msg.sender {
-                            ChatSender::Sent => format!("{} {}", chat_styles::MSG, chat_styles::SENT),
-                            ChatSender::Received(_) => format!("{} {}", chat_styles::MSG, chat_styles::RCVD),
+                        let class = match (&msg.sender, msg.delivery) {
+                            (ChatSender::Sent, Delivery::Delivered) => format!("{} {}", chat_styles::MSG, chat_styles::SENT),
+                            (ChatSender::Sent, Delivery::Pending) => format!("{} {} {}", chat_styles::MSG, chat_styles::SENT, chat_styles::PENDING),
+                            (ChatSender::Sent, Delivery::Failed) => format!("{} {} {}", chat_styles::MSG, chat_styles::SENT, chat_styles::FAILED),
+                            (ChatSender::Received(_), _) => format!("{} {}", chat_styles::MSG, chat_styles::RCVD),
                         };
                         let converted: DateTime<Local> = DateTime::from(msg.time);
-                        let time = match &msg.sender {
-                            ChatSender::Sent => converted.format("%H:%M").to_string(),
-                            ChatSender::Received(name) => format!("{} {}", name, converted.format("%H:%M").to_string()),
+                        let time = converted.format("%H:%M").to_string();
+                        let sender = match &msg.sender {
+                            ChatSender::Sent => None,
+                            ChatSender::Received(name) => Some(name.clone()),
                         };
                         view! {
-                            <div
-                                data-time=time
-                                class=class
-                            >
-                                {msg.text.clone()}
+                            <div class=class>
+                                <span class=chat_styles::META>
+                                    {sender.map(|name| {
+                                        let username = name.clone();
+                                        view! {
+                                            <button class=chat_styles::SENDER on:click=move |_| profile_username.set(Some(username.clone()))>
+                                                {name}
+                                            </button>
+                                            " "
+                                        }
+                                    })}
+                                    {time}
+                                    {match msg.delivery {
+                                        Delivery::Delivered => None,
+                                        Delivery::Pending => Some(Either::Left(" · Sending…")),
+                                        Delivery::Failed => {
+                                            let id = msg.id.clone();
+                                            Some(Either::Right(view! {
+                                                " · Not sent "
+                                                <button class=chat_styles::RETRY on:click=move |_| retry(id.clone())>
+                                                    "Retry"
+                                                </button>
+                                            }))
+                                        }
+                                    }}
+                                </span>
+                                {match msg.poll.clone() {
+                                    Some(poll) => Either::Left(view! {
+                                        <PollCard
+                                            question=msg.text.clone()
+                                            poll
+                                            own=msg.sender == ChatSender::Sent
+                                            on_update=update_poll
+                                        />
+                                    }),
+                                    None => Either::Right(msg.text.clone()),
+                                }}
+                                {msg.link_preview.clone().map(|preview| view! { <LinkPreviewCard preview/> })}
                             </div>
                         }
                     }
                 />
             </div>
-            <InputBar writers readers writing=writing.write_only() on_submit=Callback::new(move |message| spawn_local( {
-                let group_id = group_id.clone();
-                async move {
-                publish_message(group_id, message).await;
-            }}))/>
+            <CreatePollDialog group_id=group_id.clone() open=creating_poll/>
+            <ScheduledMessagesDialog group_id=group_id.clone() open=scheduling/>
+            <ProfilePopover
+                username=profile_username
+                on_close=Callback::new(move |_| profile_username.set(None))
+                on_block_change=Callback::new(move |_| blocks_version.update(|version| *version += 1))
+            />
+            {move || send_error.get().map(|error| view! {
+                <p class=chat_styles::SEND_ERROR>{match error {
+                    ChatError::Unauthorized => "Your session has expired, please log in again".to_string(),
+                    error => error.to_string(),
+                }}</p>
+            })}
+            {move || command_result.get().map(|result| match result {
+                Ok(reply) => view! { <p class=chat_styles::COMMAND_REPLY>{reply}</p> },
+                Err(error) => view! {
+                    <p class=chat_styles::SEND_ERROR>{match error {
+                        CommandError::Unauthorized => "Your session has expired, please log in again".to_string(),
+                        error => error.to_string(),
+                    }}</p>
+                },
+            })}
+            <InputBar
+                writers
+                readers
+                writing=writing.write_only()
+                on_submit=submit
+                cooldown_until
+                commands=Signal::derive(move || available_commands.get().unwrap_or_default())
+                on_create_poll=Callback::new(move |_| creating_poll.set(true))
+                on_schedule=Callback::new(move |_| scheduling.set(true))
+            />
         </div>
 
     }
 }
 
+/// The websocket subscriptions of the mounted [`Chat`], recreated with every new connection.
+#[component]
+pub fn ChatSubscriptions() -> impl IntoView {
+    let live = expect_context::<LiveChat>();
+    move || {
+        live.subscription
+            .get()
+            .map(|subscription| view! { <GroupSubscriptions subscription/> })
+    }
+}
+
+#[component]
+fn GroupSubscriptions(subscription: ChatSubscription) -> impl IntoView {
+    let ChatSubscription {
+        group_id,
+        receive,
+        poll_updated,
+        deleted,
+        link_preview,
+        ack,
+        commands,
+        presence,
+        writing,
+    } = subscription;
+    match ChannelSignal::<ChatCommand>::new(COMMAND_CHANNEL).and_then(|signal| {
+        signal.on_client(move |command| {
+            if let ChatCommand::Ack { message_id, result } = command {
+                ack.run((message_id.clone(), result.clone()));
+            }
+        })?;
+        Ok(signal)
+    }) {
+        Ok(signal) => {
+            commands.try_set_value(Some(signal));
+        }
+        Err(error) => log::error!("Failed to open the command channel: {error:?}"),
+    }
+    if let Err(error) =
+        leptos_ws::ChannelSignal::<ChatChannelMessages>::new(&group_id).and_then(|signal| {
+            signal.on_client(move |msg| match msg {
+                ChatChannelMessages::NewMessage(msg) => receive.run(msg.clone()),
+                ChatChannelMessages::PollUpdated(results) => poll_updated.run(results.clone()),
+                ChatChannelMessages::Deleted { message_id } => deleted.run(message_id.clone()),
+                ChatChannelMessages::LinkPreview {
+                    message_id,
+                    preview,
+                } => link_preview.run((message_id.clone(), preview.clone())),
+            })
+        })
+    {
+        log::error!("Failed to subscribe to messages: {error:?}");
+    }
+
+    // Presence is owned by the server, the client only reports its own activity
+    match leptos_ws::ReadOnlySignal::<GroupPresence>::new(
+        &activity_signal_name(&group_id),
+        GroupPresence::default(),
+    ) {
+        Ok(group_presence) => {
+            Effect::new(move |_| {
+                presence.try_set(group_presence.get());
+            });
+        }
+        Err(error) => log::error!("Failed to subscribe to presence: {error:?}"),
+    }
+    let presence_events = StoredValue::new(
+        leptos_ws::ChannelSignal::<PresenceEvent>::new(&presence_channel_name(&group_id)).ok(),
+    );
+    let report = move |event: PresenceEvent| {
+        // Also called on cleanup, when the stored value may already be disposed
+        presence_events.try_with_value(|presence_events| {
+            if let Some(presence_events) = presence_events {
+                let _ = presence_events.send_message(event);
+            }
+        });
+    };
+    let typing = Memo::new(move |_| writing.get());
+    Effect::new(move |previous: Option<bool>| {
+        let typing = typing.get();
+        if previous.is_some_and(|previous| previous != typing) {
+            report(if typing {
+                PresenceEvent::Typing
+            } else {
+                PresenceEvent::StoppedTyping
+            });
+        }
+        typing
+    });
+    Effect::new(move |_| {
+        // Heartbeat, typing is refreshed as long as the user keeps typing
+        let heartbeat = set_interval_with_handle(
+            move || {
+                report(if writing.get_untracked() {
+                    PresenceEvent::Typing
+                } else {
+                    PresenceEvent::Viewing
+                })
+            },
+            std::time::Duration::from_secs(3),
+        )
+        .ok();
+        on_cleanup(move || {
+            if let Some(heartbeat) = heartbeat {
+                heartbeat.clear();
+            }
+            report(PresenceEvent::Left);
+        });
+    });
+}
+
 #[component]
 pub fn SelectGroup() -> impl IntoView {
     view! {
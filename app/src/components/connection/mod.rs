@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use api::ws::{HEARTBEAT_INTERVAL, HEARTBEAT_SIGNAL};
+use chrono::{DateTime, Utc};
+use leptos::prelude::*;
+
+leptos_styling::style_sheet!(
+    connection_styles,
+    "src/components/connection/connection.module.scss",
+    "connection"
+);
+
+/// Connections without a heartbeat for this long are considered dropped.
+const STALE_AFTER: Duration = Duration::from_secs(HEARTBEAT_INTERVAL.as_secs() * 3);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectionState {
+    #[default]
+    Connecting,
+    Connected,
+    /// The connection dropped, a new one is being established.
+    Reconnecting,
+}
+
+/// Renders `children` with their own websocket, replacing it with a new one
+/// (and rendering `children` again) when the server's heartbeat stops.
+#[component]
+pub fn LiveConnection(state: RwSignal<ConnectionState>, children: ChildrenFn) -> impl IntoView {
+    let epoch = RwSignal::new(0_u32);
+    let last_beat = StoredValue::new(Utc::now());
+    let attempt_started = StoredValue::new(Utc::now());
+    let retry_after = StoredValue::new(STALE_AFTER);
+
+    let elapsed = |since: DateTime<Utc>| (Utc::now() - since).to_std().unwrap_or_default();
+    Effect::new(move |_| {
+        let watchdog = set_interval_with_handle(
+            move || match state.get_untracked() {
+                ConnectionState::Connected => {
+                    if elapsed(last_beat.get_value()) > STALE_AFTER {
+                        log::warn!("Websocket heartbeat stopped, reconnecting");
+                        state.set(ConnectionState::Reconnecting);
+                        attempt_started.set_value(Utc::now());
+                        retry_after.set_value(STALE_AFTER);
+                        epoch.update(|epoch| *epoch += 1);
+                    }
+                }
+                ConnectionState::Connecting | ConnectionState::Reconnecting => {
+                    if elapsed(attempt_started.get_value()) > retry_after.get_value() {
+                        state.set(ConnectionState::Reconnecting);
+                        attempt_started.set_value(Utc::now());
+                        retry_after.update_value(|retry_after| {
+                            *retry_after = (*retry_after * 2).min(MAX_RETRY_AFTER)
+                        });
+                        epoch.update(|epoch| *epoch += 1);
+                    }
+                }
+            },
+            Duration::from_secs(1),
+        )
+        .ok();
+        on_cleanup(move || {
+            if let Some(watchdog) = watchdog {
+                watchdog.clear();
+            }
+        });
+    });
+
+    move || {
+        epoch.track();
+        // Children of this scope use the new websocket
+        leptos_ws::provide_websocket();
+        let view = children();
+        // Established after the children's subscriptions, so a heartbeat means they are active too
+        let heartbeat = leptos_ws::ReadOnlySignal::<u64>::new(HEARTBEAT_SIGNAL, 0).ok();
+        Effect::new(move |_| {
+            if let Some(heartbeat) = &heartbeat {
+                heartbeat.track();
+                last_beat.set_value(Utc::now());
+                if heartbeat.get_untracked() > 0 {
+                    state.set(ConnectionState::Connected);
+                }
+            }
+        });
+        view
+    }
+}
+
+#[component]
+pub fn ConnectionIndicator(#[prop(into)] state: Signal<ConnectionState>) -> impl IntoView {
+    let label = move || match state.get() {
+        ConnectionState::Connecting => "Connecting…",
+        ConnectionState::Connected => "Live",
+        ConnectionState::Reconnecting => "Connection lost, reconnecting…",
+    };
+    view! {
+        <div
+            class=connection_styles::INDICATOR
+            class=(connection_styles::CONNECTED, move || state.get() == ConnectionState::Connected)
+            class=(connection_styles::RECONNECTING, move || state.get() == ConnectionState::Reconnecting)
+        >
+            <span class=connection_styles::DOT></span>
+            {label}
+        </div>
+    }
+}
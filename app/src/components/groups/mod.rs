@@ -1,4 +1,12 @@
-use leptos::{either::EitherOf3, prelude::*, task::spawn_local};
+use api::{
+    server_fn::groups::{GroupError, LeaveGroup, RenameGroup, SetMessageTtl},
+    user_events::{UserEvent, user_channel_name},
+};
+use leptos::{
+    either::{Either, EitherOf3},
+    prelude::*,
+    task::spawn_local,
+};
 use leptos_icons::Icon;
 use leptos_router::components::A;
 
@@ -8,6 +16,7 @@ use crate::components::{
     input::InputField,
     multi_step::{MultiStep, Step},
     spinner::Spinner,
+    webhooks::{GroupWebhooks, OutgoingWebhooks},
 };
 
 leptos_styling::style_sheet!(
@@ -34,12 +43,14 @@ where
     let group_picture = RwSignal::new(String::new());
     let join_code = RwSignal::new(String::new());
     let finished_api_request = RwSignal::new(false);
+    let error = RwSignal::new(None::<GroupError>);
     Effect::new(move |_| {
         if open_add.get() {
             name.set(String::new());
             group_picture.set(String::new());
             join_code.set(String::new());
             finished_api_request.set(false);
+            error.set(None);
             reset.notify();
         }
     });
@@ -116,6 +127,7 @@ where
                                                     ).await;
                                                     if let Err(err) = result {
                                                         log::error!("Failed to create Group: {err:?}");
+                                                        error.set(Some(err));
                                                         return;
                                                     }
                                                     finished_api_request.set(true);
@@ -153,7 +165,8 @@ where
                                                         join_code
                                                     ).await;
                                                     if let Err(err) = result {
-                                                        log::error!("Failed to create Group: {err:?}");
+                                                        log::error!("Failed to join Group: {err:?}");
+                                                        error.set(Some(err));
                                                         return;
                                                     }
                                                     finished_api_request.set(true);
@@ -177,8 +190,32 @@ where
                             });
                             view!{
                                 <div>
-                                    <h3>"Joining Classroom"</h3>
-                                    <Spinner size=crate::components::spinner::SpinnerSize::Large/>
+                                    {move || match error.get() {
+                                        Some(error) => Either::Left(view! {
+                                            <h3>{match error {
+                                                GroupError::NameTaken | GroupError::InvalidName => "Couldn't create the group",
+                                                GroupError::NotFound | GroupError::AlreadyMember => "Couldn't join the group",
+                                                _ => "An Error Ocurred.",
+                                            }}</h3>
+                                            <p class=groups_styles::ERROR>{match error {
+                                                // Only joining looks groups up, by their join code
+                                                GroupError::NotFound => "No group with this join code".to_string(),
+                                                error => error.to_string(),
+                                            }}</p>
+                                            <Button
+                                                variant=crate::components::button::ButtonVariant::Secondary
+                                                center=true
+                                                {..}
+                                                on:click=move |_| open_add.set(false)
+                                            >
+                                                "Close"
+                                            </Button>
+                                        }),
+                                        None => Either::Right(view! {
+                                            <h3>"Joining Classroom"</h3>
+                                            <Spinner size=crate::components::spinner::SpinnerSize::Large/>
+                                        }),
+                                    }}
                                 </div>
                             }
                         }}/>
@@ -196,6 +233,32 @@ where
     }
 }
 
+/// How long messages of a group can be kept, `None` keeps them forever.
+const MESSAGE_TTL_CHOICES: [(Option<i64>, &str); 6] = [
+    (None, "Off"),
+    (Some(60 * 60), "1 hour"),
+    (Some(24 * 60 * 60), "1 day"),
+    (Some(7 * 24 * 60 * 60), "1 week"),
+    (Some(30 * 24 * 60 * 60), "30 days"),
+    (Some(365 * 24 * 60 * 60), "1 year"),
+];
+
+/// Describes how long messages are kept, e.g. "1 day".
+pub fn describe_message_ttl(ttl_secs: i64) -> String {
+    if let Some((_, label)) = MESSAGE_TTL_CHOICES
+        .iter()
+        .find(|(choice, _)| *choice == Some(ttl_secs))
+    {
+        return label.to_string();
+    }
+    // Set through the api to something other than the choices
+    let (amount, unit) = [(24 * 60 * 60, "days"), (60 * 60, "hours"), (60, "minutes")]
+        .into_iter()
+        .find(|(unit_secs, _)| ttl_secs % unit_secs == 0)
+        .map_or((ttl_secs, "seconds"), |(unit_secs, unit)| (ttl_secs / unit_secs, unit));
+    format!("{amount} {unit}")
+}
+
 #[component]
 pub fn Group(
     id: String,
@@ -203,8 +266,56 @@ pub fn Group(
     last_message: String,
     picture: String,
     join_code: String,
+    /// Seconds after which messages of the group disappear, if they do.
+    message_ttl_secs: Option<i64>,
+    #[prop(into, optional)] on_leave: Option<Callback<()>>,
 ) -> impl IntoView {
     let open = RwSignal::new(false);
+    let leave = ServerAction::<LeaveGroup>::new();
+    Effect::new(move |_| {
+        if let Some(Ok(())) = leave.value().get() {
+            open.set(false);
+            if let Some(on_leave) = on_leave {
+                on_leave.run(());
+            }
+        }
+    });
+    let leave_error = move || {
+        leave
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|error| error.to_string())
+    };
+    // The new name arrives through the group events of the page
+    let rename = ServerAction::<RenameGroup>::new();
+    let new_name = RwSignal::new(name.clone());
+    let rename_error = move || {
+        rename
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|error| error.to_string())
+    };
+    // The new setting arrives through the group events of the page as well
+    let set_ttl = ServerAction::<SetMessageTtl>::new();
+    let ttl_error = move || {
+        set_ttl
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|error| error.to_string())
+    };
+    let custom_ttl = message_ttl_secs.filter(|ttl_secs| {
+        !MESSAGE_TTL_CHOICES
+            .iter()
+            .any(|(choice, _)| *choice == Some(*ttl_secs))
+    });
+    let rename_id = id.clone();
+    let ttl_id = id.clone();
+    let export_id = id.clone();
+    let webhooks_id = id.clone();
+    let outgoing_webhooks_id = id.clone();
     view! {
         <A href=format!("?group={id}") {..} class=groups_styles::GROUP>
             <img src={picture} alt={name.clone()} />
@@ -224,7 +335,73 @@ pub fn Group(
             </DialogHeader>
             <DialogBody>
                 <h1 class=groups_styles::JOIN_CODE>{join_code}</h1>
+                <InputField
+                    name="group_name"
+                    id="group_name"
+                    maxlength=64
+                    label="Name"
+                    prop:value=new_name
+                    on:input=move |e| {
+                        new_name.set(event_target_value(&e));
+                    }
+                />
+                {move || rename_error().map(|error| view! { <p class=groups_styles::ERROR>{error}</p> })}
+                <Button variant=crate::components::button::ButtonVariant::Secondary center=true {..} on:click=move |_| {
+                    rename.dispatch(RenameGroup { group_id: rename_id.clone(), name: new_name.get_untracked() });
+                }>
+                    "Rename group"
+                </Button>
+                <label class=groups_styles::LABEL for="message_ttl">"Disappearing messages"</label>
+                <select id="message_ttl" class=groups_styles::SELECT on:change=move |ev| {
+                    set_ttl.dispatch(SetMessageTtl {
+                        group_id: ttl_id.clone(),
+                        ttl_secs: event_target_value(&ev).parse().ok(),
+                    });
+                }>
+                    {MESSAGE_TTL_CHOICES.map(|(ttl_secs, label)| view! {
+                        <option
+                            value=ttl_secs.map_or("off".to_string(), |ttl_secs| ttl_secs.to_string())
+                            selected=ttl_secs == message_ttl_secs
+                        >
+                            {label}
+                        </option>
+                    })}
+                    {custom_ttl.map(|ttl_secs| view! {
+                        <option value=ttl_secs.to_string() selected>{describe_message_ttl(ttl_secs)}</option>
+                    })}
+                </select>
+                {move || ttl_error().map(|error| view! { <p class=groups_styles::ERROR>{error}</p> })}
+                <span class=groups_styles::LABEL>"Export history"</span>
+                <div class=groups_styles::EXPORTS>
+                    {[("json", "JSON"), ("html", "HTML"), ("markdown", "Markdown")].map(|(format, label)| view! {
+                        // A download, not a page of the app
+                        <a href=format!("/groups/{export_id}/export?format={format}") rel="external" download>
+                            {label}
+                        </a>
+                    })}
+                </div>
+                // Only loaded while the dialog is open
+                <Show when=move || open.get()>
+                    <GroupWebhooks group_id=webhooks_id.clone()/>
+                    <OutgoingWebhooks group_id=outgoing_webhooks_id.clone()/>
+                </Show>
+                {move || leave_error().map(|error| view! { <p class=groups_styles::ERROR>{error}</p> })}
+                <Button variant=crate::components::button::ButtonVariant::Danger center=true {..} on:click=move |_| {
+                    leave.dispatch(LeaveGroup { group_id: id.clone() });
+                }>
+                    "Leave group"
+                </Button>
             </DialogBody>
         </Dialog>
     }
 }
+
+/// Subscribes to the live events of the user's group list.
+#[component]
+pub fn GroupEvents(user_id: String, on_event: Callback<UserEvent>) -> impl IntoView {
+    if let Err(error) = leptos_ws::ChannelSignal::<UserEvent>::new(&user_channel_name(&user_id))
+        .and_then(|signal| signal.on_client(move |event| on_event.run(event.clone())))
+    {
+        log::error!("Failed to subscribe to group events: {error:?}");
+    }
+}
@@ -19,6 +19,7 @@ leptos_styling::style_sheet!(
 pub enum Page {
     Home,
     Chat,
+    Settings,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
@@ -77,8 +78,8 @@ pub fn Header<T: IntoView>(children: TypedChildren<T>) -> impl IntoView {
                     }>
                         {move || account
                             .user()
-                            .and_then(|v| v.username().map(|v| v.to_string())).map(|username| view!{
-                            <div class=header_styles::AVATAR style=move || format!("background-image: url('https://robohash.org/{username}');")></div>
+                            .and_then(|v| v.avatar_url().map(|v| v.to_string())).map(|avatar_url| view!{
+                            <div class=header_styles::AVATAR style=move || format!("background-image: url('{avatar_url}');")></div>
                         })}
                     </Show>
                 </Suspense>
@@ -94,6 +95,9 @@ pub fn Header<T: IntoView>(children: TypedChildren<T>) -> impl IntoView {
                     </Show>
 
                     <Show when=move || account.logged_in()>
+                        <A href="settings" {..} class=dropdown_styles::DROPDOWN_ITEM>
+                            "Settings"
+                        </A>
                         <button on:click=move |_| {logout.dispatch(Logout {});} class=dropdown_styles::DROPDOWN_ITEM>
                             "Logout"
                         </button>
@@ -0,0 +1,80 @@
+use api::server_fn::oidc::{
+    LinkedIdentity, UnlinkIdentity, list_linked_identities, list_oidc_providers,
+};
+use chrono::{DateTime, Local};
+use leptos::prelude::*;
+
+use crate::components::button::{Button, ButtonVariant, Sizing};
+
+leptos_styling::style_sheet!(
+    identities_styles,
+    "src/components/identities/identities.module.scss",
+    "identities"
+);
+
+#[component]
+pub fn Identities() -> impl IntoView {
+    let unlink = ServerAction::<UnlinkIdentity>::new();
+    let identities = Resource::new(move || unlink.version().get(), |_| list_linked_identities());
+    let providers = Resource::new(|| (), |_| list_oidc_providers());
+    let error = move || {
+        unlink
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|error| error.to_string())
+    };
+
+    view! {
+        <div class=identities_styles::IDENTITIES>
+            <Suspense>
+                {move || {
+                    identities.and_then(|identities| {
+                        let identities = identities.to_owned();
+                        view! {
+                            <For each=move || identities.clone() key=|identity| identity.id.clone() let:identity>
+                                <IdentityItem identity unlink/>
+                            </For>
+                        }
+                    })
+                }}
+                {move || providers.and_then(|providers| {
+                    let providers = providers.to_owned();
+                    view! {
+                        <div class=identities_styles::PROVIDERS>
+                            {providers.into_iter().map(|provider| view! {
+                                <a rel="external" href=provider.login_url("/settings")>
+                                    {format!("Link {}", provider.name)}
+                                </a>
+                            }).collect_view()}
+                        </div>
+                    }
+                })}
+            </Suspense>
+            {move || error().map(|error| view! { <p class=identities_styles::ERROR>{error}</p> })}
+        </div>
+    }
+}
+
+#[component]
+fn IdentityItem(identity: LinkedIdentity, unlink: ServerAction<UnlinkIdentity>) -> impl IntoView {
+    let linked: DateTime<Local> = DateTime::from(identity.created_at);
+    let id = identity.id.clone();
+    view! {
+        <div class=identities_styles::IDENTITY>
+            <div class=identities_styles::DETAILS>
+                <h3>{identity.provider.clone()}</h3>
+                <p>{format!(
+                    "{} · linked {}",
+                    identity.email.clone().unwrap_or_else(|| "No email".to_string()),
+                    linked.format("%d.%m.%Y")
+                )}</p>
+            </div>
+            <Button variant=ButtonVariant::Secondary sizing={Sizing::Small} {..} on:click=move |_| {
+                unlink.dispatch(UnlinkIdentity { id: id.clone() });
+            }>
+                "Unlink"
+            </Button>
+        </div>
+    }
+}
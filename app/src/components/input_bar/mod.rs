@@ -4,31 +4,94 @@ use crate::{
     components::{button::Button, input::InputField},
     contexts::account_context::AccountContext,
 };
+use api::{
+    presence::PresentUser,
+    server_fn::{
+        chat::{MAX_MESSAGE_LENGTH, MessageContent},
+        commands::CommandInfo,
+    },
+};
 use chrono::{DateTime, Utc};
 use leptos::{ev::KeyboardEvent, prelude::*};
 use leptos_icons::Icon;
 use leptos_styling::style_sheet;
-use serde::{Deserialize, Serialize};
 style_sheet!(
     input_bar_styles,
     "src/components/input_bar/input_bar.module.scss",
     "input_bar"
 );
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, PartialOrd, Ord, Eq)]
-pub struct Person {
-    pub name: String,
-    pub last_activity: DateTime<Utc>,
-}
-
 #[component]
 pub fn InputBar(
-    #[prop(into)] readers: Signal<Vec<Person>>,
-    #[prop(into)] writers: Signal<Vec<Person>>,
-    #[prop(into, optional)] on_submit: Option<Callback<String>>,
+    #[prop(into)] readers: Signal<Vec<PresentUser>>,
+    #[prop(into)] writers: Signal<Vec<PresentUser>>,
+    #[prop(into, optional)] on_submit: Option<Callback<MessageContent>>,
     #[prop(into)] writing: WriteSignal<bool>,
+    /// Sending is blocked until then, after the server asked to slow down.
+    #[prop(into, optional)]
+    cooldown_until: Signal<Option<DateTime<Utc>>>,
+    /// Slash commands suggested while typing `/`.
+    #[prop(into, optional)]
+    commands: Signal<Vec<CommandInfo>>,
+    /// Shows a button opening the poll dialog.
+    #[prop(into, optional)]
+    on_create_poll: Option<Callback<()>>,
+    /// Shows a button opening the scheduled messages dialog.
+    #[prop(into, optional)]
+    on_schedule: Option<Callback<()>>,
 ) -> impl IntoView {
     let message = RwSignal::new(String::new());
+    let now = RwSignal::new(Utc::now());
+    Effect::new(move |_| {
+        set_interval(
+            move || now.set(Utc::now()),
+            std::time::Duration::from_millis(250),
+        )
+    });
+    let cooldown_secs = Memo::new(move |_| {
+        let until = cooldown_until.get()?;
+        let remaining = (until - now.get()).num_milliseconds();
+        (remaining > 0).then(|| (remaining + 999) / 1000)
+    });
+    let submit = move || {
+        if cooldown_secs.get_untracked().is_some() {
+            return;
+        }
+        // Empty and too long messages stay in the input, the counter shows why
+        let Ok(content) = message.get_untracked().parse::<MessageContent>() else {
+            return;
+        };
+        if let Some(on_submit) = on_submit {
+            on_submit.run(content);
+            message.set(String::new());
+        }
+    };
+    // Commands starting with the typed name, or the typed command once its arguments follow
+    let suggestions = Memo::new(move |_| {
+        message.with(|message| {
+            let Some(typed) = message.strip_prefix('/') else {
+                return Vec::new();
+            };
+            if typed.starts_with('/') {
+                return Vec::new();
+            }
+            commands.with(|commands| match typed.split_once(char::is_whitespace) {
+                Some((name, _)) => commands
+                    .iter()
+                    .filter(|command| command.name == name)
+                    .cloned()
+                    .collect(),
+                None => commands
+                    .iter()
+                    .filter(|command| command.name.starts_with(typed))
+                    .cloned()
+                    .collect(),
+            })
+        })
+    });
+    let complete = move |name: &str| message.set(format!("/{name} "));
+    let length = Memo::new(move |_| message.with(|message| MessageContent::length(message)));
+    let too_long = move || length.get() > MAX_MESSAGE_LENGTH;
     // Timer handle for writing detection
     let writing_timeout: RwSignal<Option<TimeoutHandle>> = RwSignal::new(None);
     let on_user_input = {
@@ -56,14 +119,40 @@ pub fn InputBar(
     view! {
         <div class=input_bar_styles::INPUT_BAR>
             <StatusBar readers writers/>
+            {move || cooldown_secs.get().map(|secs| view! {
+                <p class=input_bar_styles::COOLDOWN>
+                    {format!("Slow down, you can send again in {secs}s")}
+                </p>
+            })}
 
 
+            {move || {
+                let suggestions = suggestions.get();
+                (!suggestions.is_empty()).then(|| view! {
+                    <ul class=input_bar_styles::COMMANDS>
+                        {suggestions.into_iter().map(|command| {
+                            let name = command.name.clone();
+                            view! {
+                                <li>
+                                    <button type="button" on:click=move |_| complete(&name)>
+                                        <span class=input_bar_styles::COMMAND_NAME>
+                                            {format!("/{} {}", command.name, command.args)}
+                                        </span>
+                                        {command.description}
+                                    </button>
+                                </li>
+                            }
+                        }).collect_view()}
+                    </ul>
+                })
+            }}
+
             <div class=input_bar_styles::INPUT_ROW>
             <Suspense>
                 {move || account
                     .user()
-                    .and_then(|v| v.username().map(|v| v.to_string())).map(|username| view!{
-                    <div class=input_bar_styles::AVATAR style=move || format!("background-image: url('https://robohash.org/{username}');")></div>
+                    .and_then(|v| v.avatar_url().map(|v| v.to_string())).map(|avatar_url| view!{
+                    <div class=input_bar_styles::AVATAR style=move || format!("background-image: url('{avatar_url}');")></div>
                 })}
             </Suspense>
             <div class=input_bar_styles::INPUT_FIELD>
@@ -72,19 +161,42 @@ pub fn InputBar(
                     placeholder="Message..." no_bottom_margin=true  {..} on:keydown=move |ev: KeyboardEvent| {
                         on_user_input();
 
-                        if ev.key() == "Enter" && let Some(on_submit) = on_submit{
-                            on_submit.run(message.get());
-                            message.set(String::new());
-
+                        if ev.key() == "Enter" {
+                            submit();
+                        }
+                        // Completes the first suggested command while its name is typed
+                        if ev.key() == "Tab"
+                            && !message.with_untracked(|message| message.contains(char::is_whitespace))
+                            && let Some(command) = suggestions.with_untracked(|suggestions| suggestions.first().cloned())
+                        {
+                            ev.prevent_default();
+                            complete(&command.name);
                         }
                     } />
+                {move || (length.get() > 0).then(|| view! {
+                    <span class=input_bar_styles::COUNTER class=(input_bar_styles::OVER, too_long)>
+                        {move || format!("{}/{MAX_MESSAGE_LENGTH}", length.get())}
+                    </span>
+                })}
             </div>
-            <Button variant=crate::components::button::ButtonVariant::Primary center=true {..} on:click=move |_| {
-                if let Some(on_submit) = on_submit {
-                    on_submit.run(message.get());
-                    message.set(String::new());
-                }
-            }>
+            {on_create_poll.map(|on_create_poll| view! {
+                <Button variant=crate::components::button::ButtonVariant::Secondary center=true {..}
+                    title="New poll"
+                    disabled=move || cooldown_secs.get().is_some()
+                    on:click=move |_| on_create_poll.run(())>
+                    <Icon icon=icondata::LuChartBar/>
+                </Button>
+            })}
+            {on_schedule.map(|on_schedule| view! {
+                <Button variant=crate::components::button::ButtonVariant::Secondary center=true {..}
+                    title="Scheduled messages"
+                    on:click=move |_| on_schedule.run(())>
+                    <Icon icon=icondata::LuClock/>
+                </Button>
+            })}
+            <Button variant=crate::components::button::ButtonVariant::Primary center=true {..}
+                disabled=move || cooldown_secs.get().is_some() || too_long()
+                on:click=move |_| submit()>
                 <Icon icon=icondata::IoSend/>
             </Button>
             </div>
@@ -95,8 +207,8 @@ pub fn InputBar(
 
 #[component]
 pub fn StatusBar(
-    #[prop(into)] readers: Signal<Vec<Person>>,
-    #[prop(into)] writers: Signal<Vec<Person>>,
+    #[prop(into)] readers: Signal<Vec<PresentUser>>,
+    #[prop(into)] writers: Signal<Vec<PresentUser>>,
 ) -> impl IntoView {
     let dot_frames = [".", "..", "..."];
     let dot_index = RwSignal::new(0);
@@ -111,7 +223,7 @@ pub fn StatusBar(
         let readers = readers.read();
 
         let (names, verb) = if !writers.is_empty() {
-            let names = writers.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+            let names = writers.iter().map(|p| p.username.clone()).collect::<Vec<_>>();
             let animation_index = dot_index.read();
             let dot_frame = dot_frames[*animation_index];
             let verb = if names.len() == 1 {
@@ -121,7 +233,7 @@ pub fn StatusBar(
             };
             (names, verb)
         } else {
-            let names = readers.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+            let names = readers.iter().map(|p| p.username.clone()).collect::<Vec<_>>();
             let verb = if names.len() == 1 {
                 "is looking"
             } else {
@@ -142,7 +254,7 @@ pub fn StatusBar(
         let mut seen = std::collections::HashSet::new();
         let mut combined = Vec::new();
         for p in readers.read().iter().chain(writers.read().iter()) {
-            if seen.insert(format!("https://robohash.org/{}", p.name)) {
+            if seen.insert(p.avatar_url.clone()) {
                 combined.push(p.clone());
             }
         }
@@ -157,7 +269,7 @@ pub fn StatusBar(
                 let count = people.len();
                 view! {
                     { people.iter().take(2).map(|person| view! {
-                        <div class=format!("{} {}",input_bar_styles::AVATAR, input_bar_styles::SMALL) style=format!("background-image: url('https://robohash.org/{}');",person.name)></div>
+                        <div class=format!("{} {}",input_bar_styles::AVATAR, input_bar_styles::SMALL) style=format!("background-image: url('{}');",person.avatar_url)></div>
                     }).collect_view()}
                     {
                         if count > 2 {
@@ -0,0 +1,46 @@
+use api::server_fn::chat::LinkPreview;
+use leptos::prelude::*;
+
+leptos_styling::style_sheet!(
+    link_preview_styles,
+    "src/components/link_preview/link_preview.module.scss",
+    "link_preview"
+);
+
+/// Card below a message showing what the linked page is about.
+#[component]
+pub fn LinkPreviewCard(preview: LinkPreview) -> impl IntoView {
+    // Pages without a site name are named by their host
+    let site = preview.site_name.clone().or_else(|| {
+        preview
+            .url
+            .split_once("://")
+            .and_then(|(_, rest)| rest.split(['/', '?', '#']).next())
+            .map(str::to_string)
+    });
+    view! {
+        <a
+            class=link_preview_styles::PREVIEW
+            href=preview.url.clone()
+            target="_blank"
+            rel="noopener noreferrer nofollow"
+        >
+            <span class=link_preview_styles::TEXT>
+                {site.map(|site| view! { <span class=link_preview_styles::SITE>{site}</span> })}
+                {preview.title.map(|title| view! { <span class=link_preview_styles::TITLE>{title}</span> })}
+                {preview.description.map(|description| view! {
+                    <p class=link_preview_styles::DESCRIPTION>{description}</p>
+                })}
+            </span>
+            {preview.image_url.map(|image_url| view! {
+                <img
+                    class=link_preview_styles::IMAGE
+                    src=image_url
+                    alt=""
+                    loading="lazy"
+                    referrerpolicy="no-referrer"
+                />
+            })}
+        </a>
+    }
+}
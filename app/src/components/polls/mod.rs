@@ -0,0 +1,247 @@
+use api::server_fn::polls::{
+    ChatPoll, CreatePoll, MAX_OPTION_LENGTH, MAX_POLL_OPTIONS, PollError, close_poll, vote_poll,
+};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use leptos::{prelude::*, task::spawn_local};
+
+use crate::components::{
+    button::{Button, ButtonVariant, Sizing},
+    checkbox::Checkbox,
+    dialog::{Dialog, DialogBody, DialogHeader},
+    input::InputField,
+};
+
+leptos_styling::style_sheet!(
+    polls_styles,
+    "src/components/polls/polls.module.scss",
+    "polls"
+);
+
+/// A poll message asking `question`, votes are reported through `on_update`.
+#[component]
+pub fn PollCard(
+    question: String,
+    poll: ChatPoll,
+    /// Whether the viewer created the poll and may close it.
+    #[prop(optional)]
+    own: bool,
+    on_update: Callback<ChatPoll>,
+) -> impl IntoView {
+    let poll_id = poll.results.poll_id.clone();
+    let closed = RwSignal::new(poll.is_closed(Utc::now()));
+    let closes_at = poll.results.closes_at;
+    Effect::new(move |_| {
+        if let Some(closes_at) = closes_at
+            && let Ok(remaining) = (closes_at - Utc::now()).to_std()
+        {
+            set_timeout(
+                move || {
+                    let _ = closed.try_set(true);
+                },
+                remaining,
+            );
+        }
+    });
+    let error = RwSignal::new(None::<PollError>);
+
+    let vote = {
+        let poll_id = poll_id.clone();
+        let own_votes = poll.own_votes.clone();
+        let multiple_choice = poll.multiple_choice;
+        move |option_id: String| {
+            let chosen = own_votes.contains(&option_id);
+            let option_ids = match (multiple_choice, chosen) {
+                (true, true) => own_votes
+                    .iter()
+                    .filter(|id| **id != option_id)
+                    .cloned()
+                    .collect(),
+                (true, false) => own_votes.iter().cloned().chain([option_id]).collect(),
+                (false, true) => Vec::new(),
+                (false, false) => vec![option_id],
+            };
+            let poll_id = poll_id.clone();
+            error.set(None);
+            spawn_local(async move {
+                match vote_poll(poll_id, option_ids).await {
+                    Ok(poll) => on_update.run(poll),
+                    Err(err) => error.set(Some(err)),
+                }
+            });
+        }
+    };
+    let close = move |_| {
+        let poll_id = poll_id.clone();
+        error.set(None);
+        spawn_local(async move {
+            if let Err(err) = close_poll(poll_id).await {
+                error.set(Some(err));
+            }
+        });
+    };
+
+    let kind = if poll.multiple_choice {
+        "Multiple choice"
+    } else {
+        "Single choice"
+    };
+    let visibility = if poll.anonymous {
+        "anonymous"
+    } else {
+        "named votes"
+    };
+    let closing = move || match (closed.get(), closes_at) {
+        (true, _) => " · closed".to_string(),
+        (false, Some(closes_at)) => {
+            let closes_at: DateTime<Local> = DateTime::from(closes_at);
+            format!(" · closes {}", closes_at.format("%d.%m.%Y %H:%M"))
+        }
+        (false, None) => String::new(),
+    };
+    let voters = poll.results.voters;
+    let options = poll
+        .results
+        .options
+        .into_iter()
+        .map(|option| {
+            let chosen = poll.own_votes.contains(&option.id);
+            let percent = (option.votes * 100).checked_div(voters).unwrap_or(0);
+            let vote = vote.clone();
+            let id = option.id.clone();
+            view! {
+                <li>
+                    <button
+                        class=polls_styles::OPTION
+                        class=(polls_styles::CHOSEN, chosen)
+                        disabled=move || closed.get()
+                        on:click=move |_| vote(id.clone())
+                    >
+                        <span class=polls_styles::BAR style=format!("width: {percent}%;")></span>
+                        <span class=polls_styles::TEXT>{option.text}</span>
+                        <span class=polls_styles::COUNT>{option.votes}</span>
+                    </button>
+                    {(!option.voters.is_empty()).then(|| view! {
+                        <p class=polls_styles::VOTERS>{option.voters.join(", ")}</p>
+                    })}
+                </li>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <div class=polls_styles::POLL>
+            <p class=polls_styles::QUESTION>{question}</p>
+            <p class=polls_styles::DETAILS>{format!("{kind}, {visibility}")}{closing}</p>
+            <ul class=polls_styles::OPTIONS>{options}</ul>
+            <div class=polls_styles::FOOTER>
+                <span>{if voters == 1 { "1 voter".to_string() } else { format!("{voters} voters") }}</span>
+                {move || (own && !closed.get()).then(|| view! {
+                    <button class=polls_styles::CLOSE on:click=close.clone()>"Close poll"</button>
+                })}
+            </div>
+            {move || error.get().map(|error| view! {
+                <p class=polls_styles::ERROR>{error.to_string()}</p>
+            })}
+        </div>
+    }
+}
+
+/// Parses the value of a `datetime-local` input, which is in local time.
+pub(crate) fn parse_local_time(value: &str) -> Option<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M").ok()?;
+    Local
+        .from_local_datetime(&naive)
+        .single()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Dialog composing a new poll in a group.
+#[component]
+pub fn CreatePollDialog(group_id: String, open: RwSignal<bool>) -> impl IntoView {
+    let create = ServerAction::<CreatePoll>::new();
+    let question = RwSignal::new(String::new());
+    let options = RwSignal::new(String::new());
+    let multiple_choice = RwSignal::new(false);
+    let anonymous = RwSignal::new(false);
+    let closes_at = RwSignal::new(String::new());
+    Effect::new(move |_| {
+        if open.get() {
+            question.set(String::new());
+            options.set(String::new());
+            multiple_choice.set(false);
+            anonymous.set(false);
+            closes_at.set(String::new());
+        }
+    });
+    // The poll shows up in the chat through the group's channel
+    Effect::new(move |_| {
+        if let Some(Ok(())) = create.value().get() {
+            open.set(false);
+        }
+    });
+    let error = move || {
+        create
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|error| error.to_string())
+    };
+
+    view! {
+        <Dialog open on_outside_click=Callback::new(move |_| open.set(false))>
+            <DialogHeader>
+                <h2>"New poll"</h2>
+            </DialogHeader>
+            <DialogBody>
+                <div class=polls_styles::FORM>
+                    <InputField name="poll_question" label="Question" placeholder="Where do we go for lunch?" value=question/>
+                    <label class=polls_styles::LABEL for="poll_options">
+                        {format!("Options, one per line (up to {MAX_POLL_OPTIONS})")}
+                    </label>
+                    <textarea
+                        id="poll_options"
+                        class=polls_styles::TEXTAREA
+                        rows=5
+                        maxlength=(MAX_POLL_OPTIONS * (MAX_OPTION_LENGTH + 1)).to_string()
+                        prop:value=move || options.get()
+                        on:input=move |ev| options.set(event_target_value(&ev))
+                    ></textarea>
+                    <Checkbox
+                        id="poll-multiple-choice"
+                        label="Allow multiple choices"
+                        checked=Signal::from(multiple_choice)
+                        on_change=Callback::new(move |checked| multiple_choice.set(checked))
+                    />
+                    <Checkbox
+                        id="poll-anonymous"
+                        label="Anonymous votes"
+                        checked=Signal::from(anonymous)
+                        on_change=Callback::new(move |checked| anonymous.set(checked))
+                    />
+                    <InputField name="poll_closes_at" label="Closes at (optional)" input_type="datetime-local" value=closes_at/>
+                    {move || error().map(|error| view! { <p class=polls_styles::ERROR>{error}</p> })}
+                    <Button variant=ButtonVariant::Primary sizing=Sizing::Small center=true {..}
+                        disabled=move || create.pending().get()
+                        on:click={
+                            let group_id = group_id.clone();
+                            move |_| {
+                                create.dispatch(CreatePoll {
+                                    group_id: group_id.clone(),
+                                    question: question.get_untracked(),
+                                    options: options.with_untracked(|options| {
+                                        options.lines().map(str::to_string).collect()
+                                    }),
+                                    multiple_choice: multiple_choice.get_untracked(),
+                                    anonymous: anonymous.get_untracked(),
+                                    closes_at: parse_local_time(&closes_at.get_untracked()),
+                                });
+                            }
+                        }
+                    >
+                        "Post poll"
+                    </Button>
+                </div>
+            </DialogBody>
+        </Dialog>
+    }
+}
@@ -0,0 +1,222 @@
+use api::server_fn::blocks::{BlockUser, UnblockUser};
+use api::server_fn::profile::{
+    MAX_BIO_LENGTH, MAX_DISPLAY_NAME_LENGTH, ProfileError, RemoveAvatar, UpdateProfile,
+    UserProfile, get_profile, upload_avatar,
+};
+use leptos::{
+    either::Either,
+    prelude::*,
+    wasm_bindgen::JsCast,
+    web_sys::{FormData, HtmlFormElement, SubmitEvent},
+};
+
+use crate::{
+    components::{
+        button::{Button, ButtonVariant, Sizing},
+        dialog::{Dialog, DialogBody, DialogHeader},
+        input::InputField,
+    },
+    contexts::account_context::AccountContext,
+};
+
+leptos_styling::style_sheet!(
+    profile_styles,
+    "src/components/profile/profile.module.scss",
+    "profile"
+);
+
+/// Shows the profile of `username` while it is set.
+#[component]
+pub fn ProfilePopover(
+    #[prop(into)] username: Signal<Option<String>>,
+    #[prop(into)] on_close: Callback<()>,
+    /// Called after the user was blocked or unblocked from the popover.
+    #[prop(into, optional)]
+    on_block_change: Option<Callback<()>>,
+) -> impl IntoView {
+    let block = ServerAction::<BlockUser>::new();
+    let unblock = ServerAction::<UnblockUser>::new();
+    let profile = Resource::new(
+        move || (username.get(), block.version().get(), unblock.version().get()),
+        |(username, _, _)| async move {
+            match username {
+                Some(username) => Some(get_profile(username).await),
+                None => None,
+            }
+        },
+    );
+    Effect::new(move |previous: Option<(usize, usize)>| {
+        let versions = (block.version().get(), unblock.version().get());
+        if previous.is_some_and(|previous| previous != versions)
+            && let Some(on_block_change) = on_block_change
+        {
+            on_block_change.run(());
+        }
+        versions
+    });
+    let error = move || {
+        block
+            .value()
+            .get()
+            .or_else(|| unblock.value().get())
+            .and_then(|result| result.err())
+            .map(|error| error.to_string())
+    };
+    view! {
+        <Dialog open=Signal::derive(move || username.read().is_some()) on_outside_click=on_close>
+            <Suspense>
+                {move || profile.get().flatten().map(|profile| match profile {
+                    Ok(profile) => Either::Left(view! { <ProfileCard profile block unblock/> }),
+                    Err(error) => Either::Right(view! {
+                        <p class=profile_styles::ERROR>{error.to_string()}</p>
+                    }),
+                })}
+            </Suspense>
+            {move || error().map(|error| view! { <p class=profile_styles::ERROR>{error}</p> })}
+        </Dialog>
+    }
+}
+
+#[component]
+fn ProfileCard(
+    profile: UserProfile,
+    block: ServerAction<BlockUser>,
+    unblock: ServerAction<UnblockUser>,
+) -> impl IntoView {
+    let name = profile.name().to_string();
+    let username = profile.username.clone();
+    view! {
+        <DialogHeader>
+            <div class=profile_styles::IDENTITY>
+                <img class=profile_styles::AVATAR src=profile.avatar_url alt=name.clone()/>
+                <div>
+                    <h3>{name}</h3>
+                    <p>{format!("@{}", profile.username)}</p>
+                </div>
+            </div>
+        </DialogHeader>
+        <DialogBody>
+            <p class=profile_styles::BIO>
+                {profile.bio.unwrap_or_else(|| "No bio yet.".to_string())}
+            </p>
+        </DialogBody>
+        <div class=profile_styles::POPOVER_ACTIONS>
+            {if profile.blocked {
+                Either::Left(view! {
+                    <Button variant=ButtonVariant::Secondary sizing={Sizing::Small} {..} on:click=move |_| {
+                        unblock.dispatch(UnblockUser { username: username.clone() });
+                    }>
+                        "Unblock"
+                    </Button>
+                })
+            } else {
+                Either::Right(view! {
+                    <Button variant=ButtonVariant::Danger sizing={Sizing::Small} {..} on:click=move |_| {
+                        block.dispatch(BlockUser { username: username.clone() });
+                    }>
+                        "Block"
+                    </Button>
+                })
+            }}
+        </div>
+    }
+}
+
+/// Edit form of the own profile.
+#[component]
+pub fn ProfileSettings() -> impl IntoView {
+    let account = use_context::<AccountContext>().expect("AccountContext not found");
+    let update = ServerAction::<UpdateProfile>::new();
+    let remove = ServerAction::<RemoveAvatar>::new();
+    let upload = Action::new_local(|data: &FormData| upload_avatar(data.clone().into()));
+
+    let profile = Resource::new(
+        move || account.user().and_then(|user| user.username().map(str::to_string)),
+        |username| async move {
+            match username {
+                Some(username) => get_profile(username).await.ok(),
+                None => None,
+            }
+        },
+    );
+    let display_name = RwSignal::new(String::new());
+    let bio = RwSignal::new(String::new());
+    let avatar_url = RwSignal::new(None::<String>);
+    Effect::new(move |_| {
+        if let Some(Some(profile)) = profile.get() {
+            display_name.set(profile.display_name.unwrap_or_default());
+            bio.set(profile.bio.unwrap_or_default());
+            avatar_url.set(Some(profile.avatar_url));
+        }
+    });
+
+    // Every action returns the updated profile, the header shows the new avatar and name too
+    let error = RwSignal::new(None::<ProfileError>);
+    let on_result = move |result: Option<Result<UserProfile, ProfileError>>| match result {
+        Some(Ok(profile)) => {
+            error.set(None);
+            avatar_url.set(Some(profile.avatar_url));
+            account.refresh();
+        }
+        Some(Err(err)) => error.set(Some(err)),
+        None => {}
+    };
+    Effect::new(move |_| on_result(update.value().get()));
+    Effect::new(move |_| on_result(remove.value().get()));
+    Effect::new(move |_| on_result(upload.value().get()));
+
+    let on_upload = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let Some(form) = ev
+            .target()
+            .and_then(|target| target.dyn_into::<HtmlFormElement>().ok())
+        else {
+            return;
+        };
+        if let Ok(data) = FormData::new_with_form(&form) {
+            upload.dispatch_local(data);
+        }
+    };
+
+    view! {
+        <div class=profile_styles::PROFILE_SETTINGS>
+            <div class=profile_styles::AVATAR_ROW>
+                {move || avatar_url.get().map(|src| view! {
+                    <img class=profile_styles::AVATAR src=src alt="Avatar"/>
+                })}
+                <form class=profile_styles::UPLOAD on:submit=on_upload>
+                    <input type="file" name="avatar" accept="image/png,image/jpeg,image/webp,image/gif" required/>
+                    <div class=profile_styles::ACTIONS>
+                        <Button variant=ButtonVariant::Primary sizing={Sizing::Small} {..} type="submit" disabled=move || upload.pending().get()>
+                            "Upload avatar"
+                        </Button>
+                        <Button variant=ButtonVariant::Secondary sizing={Sizing::Small} {..} type="button" on:click=move |_| {
+                            remove.dispatch(RemoveAvatar {});
+                        }>
+                            "Remove avatar"
+                        </Button>
+                    </div>
+                </form>
+            </div>
+            <InputField name="display_name" label="Display name" maxlength=MAX_DISPLAY_NAME_LENGTH value=display_name/>
+            <label class=profile_styles::LABEL for="bio">"Bio"</label>
+            <textarea
+                id="bio"
+                name="bio"
+                rows=4
+                maxlength=MAX_BIO_LENGTH
+                prop:value=move || bio.get()
+                on:input=move |ev| bio.set(event_target_value(&ev))
+            />
+            <Button variant=ButtonVariant::Primary sizing={Sizing::Small} {..} on:click=move |_| {
+                update.dispatch(UpdateProfile {
+                    display_name: display_name.get_untracked(),
+                    bio: bio.get_untracked(),
+                });
+            }>
+                "Save profile"
+            </Button>
+            {move || error.get().map(|error| view! { <p class=profile_styles::ERROR>{error.to_string()}</p> })}
+        </div>
+    }
+}
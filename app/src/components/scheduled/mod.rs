@@ -0,0 +1,193 @@
+use api::server_fn::{
+    chat::MAX_MESSAGE_LENGTH,
+    scheduled::{
+        CancelScheduledMessage, ScheduleMessage, ScheduledMessageInfo, UpdateScheduledMessage,
+        list_scheduled_messages,
+    },
+};
+use chrono::{DateTime, Local};
+use leptos::prelude::*;
+
+use crate::components::{
+    button::{Button, ButtonVariant, Sizing},
+    dialog::{Dialog, DialogBody, DialogHeader},
+    input::InputField,
+    polls::parse_local_time,
+};
+
+leptos_styling::style_sheet!(
+    scheduled_styles,
+    "src/components/scheduled/scheduled.module.scss",
+    "scheduled"
+);
+
+/// Dialog scheduling messages in a group and managing the pending ones.
+#[component]
+pub fn ScheduledMessagesDialog(group_id: String, open: RwSignal<bool>) -> impl IntoView {
+    let schedule = ServerAction::<ScheduleMessage>::new();
+    let update = ServerAction::<UpdateScheduledMessage>::new();
+    let cancel = ServerAction::<CancelScheduledMessage>::new();
+    let pending = Resource::new(
+        {
+            let group_id = group_id.clone();
+            move || {
+                (
+                    group_id.clone(),
+                    open.get(),
+                    schedule.version().get(),
+                    update.version().get(),
+                    cancel.version().get(),
+                )
+            }
+        },
+        |(group_id, open, ..)| async move {
+            if open {
+                list_scheduled_messages(group_id).await
+            } else {
+                Ok(Vec::new())
+            }
+        },
+    );
+
+    let text = RwSignal::new(String::new());
+    let send_at = RwSignal::new(String::new());
+    // The pending message being edited, if any
+    let editing = RwSignal::new(None::<String>);
+    let reset = move || {
+        text.set(String::new());
+        send_at.set(String::new());
+        editing.set(None);
+    };
+    Effect::new(move |_| {
+        if open.get() {
+            reset();
+        }
+    });
+    Effect::new(move |_| {
+        if let Some(Ok(_)) = schedule.value().get() {
+            reset();
+        }
+    });
+    Effect::new(move |_| {
+        if let Some(Ok(())) = update.value().get() {
+            reset();
+        }
+    });
+    let edit = Callback::new(move |message: ScheduledMessageInfo| {
+        let local: DateTime<Local> = DateTime::from(message.send_at);
+        text.set(message.text);
+        send_at.set(local.format("%Y-%m-%dT%H:%M").to_string());
+        editing.set(Some(message.id));
+    });
+    let submit = move |_| {
+        // An unparsable time is rejected by the server as being in the past
+        let time = parse_local_time(&send_at.get_untracked()).unwrap_or_default();
+        match editing.get_untracked() {
+            Some(id) => {
+                update.dispatch(UpdateScheduledMessage {
+                    id,
+                    text: text.get_untracked(),
+                    send_at: time,
+                });
+            }
+            None => {
+                schedule.dispatch(ScheduleMessage {
+                    group_id: group_id.clone(),
+                    text: text.get_untracked(),
+                    send_at: time,
+                });
+            }
+        }
+    };
+    let error = move || {
+        schedule
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .or_else(|| update.value().get().and_then(|result| result.err()))
+            .or_else(|| cancel.value().get().and_then(|result| result.err()))
+            .map(|error| error.to_string())
+    };
+
+    view! {
+        <Dialog open on_outside_click=Callback::new(move |_| open.set(false))>
+            <DialogHeader>
+                <h2>"Scheduled messages"</h2>
+            </DialogHeader>
+            <DialogBody>
+                <div class=scheduled_styles::FORM>
+                    <label class=scheduled_styles::LABEL for="scheduled_text">"Message"</label>
+                    <textarea
+                        id="scheduled_text"
+                        class=scheduled_styles::TEXTAREA
+                        rows=3
+                        maxlength=MAX_MESSAGE_LENGTH.to_string()
+                        prop:value=move || text.get()
+                        on:input=move |ev| text.set(event_target_value(&ev))
+                    ></textarea>
+                    <InputField name="scheduled_send_at" label="Send at" input_type="datetime-local" value=send_at/>
+                    {move || error().map(|error| view! { <p class=scheduled_styles::ERROR>{error}</p> })}
+                    <div class=scheduled_styles::ACTIONS>
+                        {move || editing.get().is_some().then(|| view! {
+                            <Button variant=ButtonVariant::Secondary sizing={Sizing::Small} {..} on:click=move |_| reset()>
+                                "Discard changes"
+                            </Button>
+                        })}
+                        <Button variant=ButtonVariant::Primary sizing=Sizing::Small center=true {..}
+                            disabled=move || schedule.pending().get() || update.pending().get()
+                            on:click=submit
+                        >
+                            {move || if editing.get().is_some() { "Save" } else { "Schedule" }}
+                        </Button>
+                    </div>
+                </div>
+                <Suspense>
+                    {move || pending.and_then(move |messages| {
+                        let messages = messages.to_owned();
+                        view! {
+                            <ul class=scheduled_styles::LIST>
+                                <For each=move || messages.clone() key=|message| (message.id.clone(), message.text.clone(), message.send_at) let:message>
+                                    <ScheduledMessageItem message editing edit cancel/>
+                                </For>
+                            </ul>
+                        }
+                    })}
+                </Suspense>
+            </DialogBody>
+        </Dialog>
+    }
+}
+
+#[component]
+fn ScheduledMessageItem(
+    message: ScheduledMessageInfo,
+    editing: RwSignal<Option<String>>,
+    edit: Callback<ScheduledMessageInfo>,
+    cancel: ServerAction<CancelScheduledMessage>,
+) -> impl IntoView {
+    let send_at: DateTime<Local> = DateTime::from(message.send_at);
+    let id = message.id.clone();
+    let is_editing = {
+        let id = id.clone();
+        move || editing.read().as_ref() == Some(&id)
+    };
+    view! {
+        <li class=scheduled_styles::ITEM class=(scheduled_styles::EDITING, is_editing)>
+            <div class=scheduled_styles::DETAILS>
+                <span class=scheduled_styles::TIME>{send_at.format("%d.%m.%Y %H:%M").to_string()}</span>
+                <p>{message.text.clone()}</p>
+            </div>
+            <Button variant=ButtonVariant::Secondary sizing={Sizing::Small} {..} on:click={
+                let message = message.clone();
+                move |_| edit.run(message.clone())
+            }>
+                "Edit"
+            </Button>
+            <Button variant=ButtonVariant::Danger sizing={Sizing::Small} {..} on:click=move |_| {
+                cancel.dispatch(CancelScheduledMessage { id: id.clone() });
+            }>
+                "Cancel"
+            </Button>
+        </li>
+    }
+}
@@ -0,0 +1,89 @@
+use api::server_fn::sessions::{
+    RevokeAllOtherSessions, RevokeSession, SessionInfo, list_sessions,
+};
+use chrono::{DateTime, Local};
+use leptos::prelude::*;
+
+use crate::components::button::{Button, ButtonVariant, Sizing};
+
+leptos_styling::style_sheet!(
+    sessions_styles,
+    "src/components/sessions/sessions.module.scss",
+    "sessions"
+);
+
+#[component]
+pub fn Sessions() -> impl IntoView {
+    let revoke = ServerAction::<RevokeSession>::new();
+    let revoke_others = ServerAction::<RevokeAllOtherSessions>::new();
+    let sessions = Resource::new(
+        move || (revoke.version().get(), revoke_others.version().get()),
+        |_| list_sessions(),
+    );
+    let error = move || {
+        revoke
+            .value()
+            .get()
+            .or_else(|| revoke_others.value().get())
+            .and_then(|result| result.err())
+            .map(|error| error.to_string())
+    };
+
+    view! {
+        <div class=sessions_styles::SESSIONS>
+            <Suspense>
+                {move || {
+                    sessions.and_then(|sessions| {
+                        let sessions = sessions.to_owned();
+                        view! {
+                            <For each=move || sessions.clone() key=|session| session.id.clone() let:session>
+                                <SessionItem session revoke/>
+                            </For>
+                        }
+                    })
+                }}
+            </Suspense>
+            {move || error().map(|error| view! { <p class=sessions_styles::ERROR>{error}</p> })}
+            <Button variant=ButtonVariant::Danger sizing={Sizing::Small} {..} on:click=move |_| {
+                revoke_others.dispatch(RevokeAllOtherSessions {});
+            }>
+                "Sign out all other sessions"
+            </Button>
+        </div>
+    }
+}
+
+#[component]
+fn SessionItem(session: SessionInfo, revoke: ServerAction<RevokeSession>) -> impl IntoView {
+    let last_seen: DateTime<Local> = DateTime::from(session.last_seen_at);
+    let signed_in: DateTime<Local> = DateTime::from(session.created_at);
+    let ip_address = session
+        .ip_address
+        .clone()
+        .unwrap_or_else(|| "Unknown address".to_string());
+    let id = session.id.clone();
+    view! {
+        <div class=sessions_styles::SESSION>
+            <div class=sessions_styles::DETAILS>
+                <h3>
+                    {session.device.clone()}
+                    <Show when=move || session.current>
+                        <span class=sessions_styles::BADGE>"This device"</span>
+                    </Show>
+                </h3>
+                <p>{format!("{ip_address} · last active {}", last_seen.format("%d.%m.%Y %H:%M"))}</p>
+                <p>{format!("Signed in {}", signed_in.format("%d.%m.%Y %H:%M"))}</p>
+            </div>
+            <Show when=move || !session.current>
+                <Button variant=ButtonVariant::Secondary sizing={Sizing::Small} {..} on:click={
+                    let id = id.clone();
+                    move |_| {
+                        revoke.dispatch(RevokeSession { id: id.clone() });
+                    }
+                }>
+                    "Revoke"
+                </Button>
+            </Show>
+        </div>
+    }
+}
@@ -0,0 +1,221 @@
+use api::server_fn::webhooks::{
+    CreateOutgoingWebhook, CreateWebhook, DeleteOutgoingWebhook, DeleteWebhook,
+    OutgoingWebhookInfo, WebhookEvent, WebhookInfo, list_outgoing_webhooks, list_webhooks,
+};
+use chrono::{DateTime, Local};
+use leptos::prelude::*;
+
+use crate::components::{
+    button::{Button, ButtonVariant, Sizing},
+    checkbox::Checkbox,
+    input::InputField,
+};
+
+leptos_styling::style_sheet!(
+    webhooks_styles,
+    "src/components/webhooks/webhooks.module.scss",
+    "webhooks"
+);
+
+/// Incoming webhooks of a group, which post messages without a user account.
+#[component]
+pub fn GroupWebhooks(group_id: String) -> impl IntoView {
+    let create = ServerAction::<CreateWebhook>::new();
+    let delete = ServerAction::<DeleteWebhook>::new();
+    let webhooks = Resource::new(
+        {
+            let group_id = group_id.clone();
+            move || (group_id.clone(), create.version().get(), delete.version().get())
+        },
+        |(group_id, _, _)| list_webhooks(group_id),
+    );
+
+    let name = RwSignal::new(String::new());
+    let created = move || create.value().get().and_then(|result| result.ok());
+    let error = move || {
+        create
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .or_else(|| delete.value().get().and_then(|result| result.err()))
+            .map(|error| error.to_string())
+    };
+    let create_group_id = group_id.clone();
+
+    view! {
+        <div class=webhooks_styles::WEBHOOKS>
+            <span class=webhooks_styles::LABEL>"Webhooks"</span>
+            <div class=webhooks_styles::FORM>
+                <InputField name="webhook_name" placeholder="CI" maxlength=64 value=name no_bottom_margin=true/>
+                <Button variant=ButtonVariant::Secondary sizing={Sizing::Small} {..} on:click=move |_| {
+                    create.dispatch(CreateWebhook {
+                        group_id: create_group_id.clone(),
+                        name: name.get_untracked(),
+                    });
+                }>
+                    "Add webhook"
+                </Button>
+            </div>
+            {move || created().map(|created| view! {
+                <div class=webhooks_styles::SECRET>
+                    <code>{created.url}</code>
+                    <p>"Copy this url now, it won't be shown again. POST JSON like "<code>"{\"text\": \"Build passed\"}"</code>" to it."</p>
+                </div>
+            })}
+            {move || error().map(|error| view! { <p class=webhooks_styles::ERROR>{error}</p> })}
+            <Suspense>
+                {move || {
+                    let group_id = group_id.clone();
+                    webhooks.and_then(move |webhooks| {
+                        let webhooks = webhooks.to_owned();
+                        let group_id = group_id.clone();
+                        view! {
+                            <For each=move || webhooks.clone() key=|webhook| webhook.id.clone() let:webhook>
+                                <WebhookItem group_id=group_id.clone() webhook delete/>
+                            </For>
+                        }
+                    })
+                }}
+            </Suspense>
+        </div>
+    }
+}
+
+#[component]
+fn WebhookItem(
+    group_id: String,
+    webhook: WebhookInfo,
+    delete: ServerAction<DeleteWebhook>,
+) -> impl IntoView {
+    let last_used = webhook.last_used_at.map_or("never used".to_string(), |last_used_at| {
+        let last_used_at: DateTime<Local> = DateTime::from(last_used_at);
+        format!("last used {}", last_used_at.format("%d.%m.%Y %H:%M"))
+    });
+    let id = webhook.id.clone();
+    view! {
+        <div class=webhooks_styles::WEBHOOK>
+            <div class=webhooks_styles::DETAILS>
+                <h3>{webhook.name.clone()}</h3>
+                <p>{format!("{}… · {last_used}", webhook.prefix)}</p>
+            </div>
+            <Button variant=ButtonVariant::Danger sizing={Sizing::Small} {..} on:click=move |_| {
+                delete.dispatch(DeleteWebhook { group_id: group_id.clone(), id: id.clone() });
+            }>
+                "Delete"
+            </Button>
+        </div>
+    }
+}
+
+fn event_checkbox_id(event: WebhookEvent) -> &'static str {
+    match event {
+        WebhookEvent::MessageCreated => "webhook-event-message-created",
+        WebhookEvent::MemberJoined => "webhook-event-member-joined",
+        WebhookEvent::MemberLeft => "webhook-event-member-left",
+    }
+}
+
+/// Urls the events of a group are sent to as signed JSON.
+#[component]
+pub fn OutgoingWebhooks(group_id: String) -> impl IntoView {
+    let create = ServerAction::<CreateOutgoingWebhook>::new();
+    let delete = ServerAction::<DeleteOutgoingWebhook>::new();
+    let webhooks = Resource::new(
+        {
+            let group_id = group_id.clone();
+            move || (group_id.clone(), create.version().get(), delete.version().get())
+        },
+        |(group_id, _, _)| list_outgoing_webhooks(group_id),
+    );
+
+    let url = RwSignal::new(String::new());
+    let events = RwSignal::new(vec![WebhookEvent::MessageCreated]);
+    let created = move || create.value().get().and_then(|result| result.ok());
+    let error = move || {
+        create
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .or_else(|| delete.value().get().and_then(|result| result.err()))
+            .map(|error| error.to_string())
+    };
+    let create_group_id = group_id.clone();
+
+    view! {
+        <div class=webhooks_styles::WEBHOOKS>
+            <span class=webhooks_styles::LABEL>"Outgoing webhooks"</span>
+            <InputField name="outgoing_webhook_url" placeholder="https://example.com/hook" value=url no_bottom_margin=true/>
+            {WebhookEvent::ALL.map(|event| view! {
+                <Checkbox
+                    id=event_checkbox_id(event)
+                    label=event.description()
+                    checked=Signal::derive(move || events.read().contains(&event))
+                    on_change=Callback::new(move |checked| events.update(|events| {
+                        events.retain(|e| *e != event);
+                        if checked {
+                            events.push(event);
+                        }
+                    }))
+                />
+            })}
+            <Button variant=ButtonVariant::Secondary sizing={Sizing::Small} {..} on:click=move |_| {
+                create.dispatch(CreateOutgoingWebhook {
+                    group_id: create_group_id.clone(),
+                    url: url.get_untracked(),
+                    events: events.get_untracked(),
+                });
+            }>
+                "Add outgoing webhook"
+            </Button>
+            {move || created().map(|created| view! {
+                <div class=webhooks_styles::SECRET>
+                    <code>{created.secret}</code>
+                    <p>"Copy this signing secret now, it won't be shown again. Requests are signed with HMAC-SHA256 of "<code>"{timestamp}.{body}"</code>"."</p>
+                </div>
+            })}
+            {move || error().map(|error| view! { <p class=webhooks_styles::ERROR>{error}</p> })}
+            <Suspense>
+                {move || {
+                    let group_id = group_id.clone();
+                    webhooks.and_then(move |webhooks| {
+                        let webhooks = webhooks.to_owned();
+                        let group_id = group_id.clone();
+                        view! {
+                            <For each=move || webhooks.clone() key=|webhook| webhook.id.clone() let:webhook>
+                                <OutgoingWebhookItem group_id=group_id.clone() webhook delete/>
+                            </For>
+                        }
+                    })
+                }}
+            </Suspense>
+        </div>
+    }
+}
+
+#[component]
+fn OutgoingWebhookItem(
+    group_id: String,
+    webhook: OutgoingWebhookInfo,
+    delete: ServerAction<DeleteOutgoingWebhook>,
+) -> impl IntoView {
+    let events = webhook
+        .events
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let id = webhook.id.clone();
+    view! {
+        <div class=webhooks_styles::WEBHOOK>
+            <div class=webhooks_styles::DETAILS>
+                <h3>{webhook.url.clone()}</h3>
+                <p>{events}</p>
+            </div>
+            <Button variant=ButtonVariant::Danger sizing={Sizing::Small} {..} on:click=move |_| {
+                delete.dispatch(DeleteOutgoingWebhook { group_id: group_id.clone(), id: id.clone() });
+            }>
+                "Delete"
+            </Button>
+        </div>
+    }
+}
@@ -1,3 +1,4 @@
+#![recursion_limit = "256"]
 use components::header::Header;
 use leptos::prelude::*;
 use leptos_meta::{MetaTags, Stylesheet, Title, provide_meta_context};
@@ -16,7 +17,7 @@ use crate::{
         account_context::{AccountContext, AccountProvider},
         theme_context::ThemeContextProvider,
     },
-    pages::{login::LoginPage, signup::SignupPage},
+    pages::{login::LoginPage, settings::SettingsPage, signup::SignupPage},
 };
 
 mod components;
@@ -46,7 +47,6 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
 #[component]
 pub fn App() -> impl IntoView {
     provide_meta_context();
-    leptos_ws::provide_websocket();
     view! {
         <Stylesheet id="leptos" href="/pkg/leptos-ws-demo.css"/>
 
@@ -66,6 +66,10 @@ pub fn App() -> impl IntoView {
                                         <pages::chat::ChatPage/>
                                     </main>
                                 }/>
+                                <ProtectedRoute condition=move || {
+                                    let account_context = expect_context::<AccountContext>();
+                                    account_context.user().map(|v| v.is_logged_in())
+                                } path=path!("settings") redirect_path=move || "/login?next=/settings" view=SettingsPage/>
 
                                 <Route path=path!("login") view=LoginPage />
                                 <Route path=path!("signup") view=SignupPage />
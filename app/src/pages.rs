@@ -1,3 +1,4 @@
 pub mod chat;
 pub mod login;
+pub mod settings;
 pub mod signup;
@@ -1,12 +1,19 @@
-use std::collections::VecDeque;
+use api::{server_fn::groups::Group as GroupInfo, user_events::UserEvent};
 
-use crate::components::{
-    chat::{Chat, SelectGroup},
-    groups::{Group, Groups},
-    header::HeaderContext,
+use crate::{
+    components::{
+        chat::{Chat, ChatSubscriptions, LiveChat, SelectGroup},
+        connection::LiveConnection,
+        groups::{Group, GroupEvents, Groups},
+        header::HeaderContext,
+    },
+    contexts::account_context::AccountContext,
 };
 use leptos::{either::Either, prelude::*};
-use leptos_router::{hooks::use_query, params::Params};
+use leptos_router::{
+    hooks::{use_navigate, use_query},
+    params::Params,
+};
 
 #[derive(Params, PartialEq)]
 struct HomeQuery {
@@ -40,6 +47,36 @@ pub fn ChatPage() -> impl IntoView {
         }
     });
 
+    // The chat of a group that was left can't be shown anymore
+    let navigate = use_navigate();
+    let on_leave = Callback::new({
+        let navigate = navigate.clone();
+        move |_| {
+            reload_groups.notify();
+            navigate("/chat", Default::default());
+        }
+    });
+
+    let live = LiveChat::default();
+    provide_context(live);
+    let account = expect_context::<AccountContext>();
+    let on_group_event = Callback::new(move |event: UserEvent| {
+        if let UserEvent::RemovedFromGroup { group_id: removed } = &event
+            && query.with_untracked(|query| {
+                query
+                    .as_ref()
+                    .is_ok_and(|query| query.group.as_ref() == Some(removed))
+            })
+        {
+            navigate("/chat", Default::default());
+        }
+        groups.update(|groups| {
+            if let Some(Ok(groups)) = groups {
+                apply_group_event(groups, event);
+            }
+        });
+    });
+
     view! {
         <Groups reload_groups>
             <Suspense>
@@ -47,7 +84,7 @@ pub fn ChatPage() -> impl IntoView {
                     groups.and_then(|v| {
                         let groups = v.to_owned();
                         view!{
-                            <For each=move || groups.clone() key=move |group| group.id.clone()
+                            <For each=move || groups.clone() key=move |group| (group.id.clone(), group.name.clone(), group.last_message.clone(), group.message_ttl_secs)
                             let:group>
                                 <Group
                                     id=group.id.clone()
@@ -55,6 +92,8 @@ pub fn ChatPage() -> impl IntoView {
                                     last_message=group.last_message.clone()
                                     picture=group.avatar_url.clone()
                                     join_code=group.join_code.clone()
+                                    message_ttl_secs=group.message_ttl_secs
+                                    on_leave=on_leave
                                 />
                             </For>
                         }
@@ -64,10 +103,83 @@ pub fn ChatPage() -> impl IntoView {
             </Suspense>
         </Groups>
         {move || match group_id() {
-            Some(id) => Either::Left(view!{
-            <Chat group_id=id/>
+            Some(id) => Either::Left({
+                let topic = Signal::derive({
+                    let id = id.clone();
+                    move || {
+                        groups.with(|groups| {
+                            groups
+                                .as_ref()
+                                .and_then(|groups| groups.as_ref().ok())
+                                .and_then(|groups| groups.iter().find(|group| group.id == id))
+                                .and_then(|group| group.topic.clone())
+                        })
+                    }
+                });
+                let message_ttl_secs = Signal::derive({
+                    let id = id.clone();
+                    move || {
+                        groups.with(|groups| {
+                            groups
+                                .as_ref()
+                                .and_then(|groups| groups.as_ref().ok())
+                                .and_then(|groups| groups.iter().find(|group| group.id == id))
+                                .and_then(|group| group.message_ttl_secs)
+                        })
+                    }
+                });
+                view! {
+                    <Chat group_id=id topic message_ttl_secs/>
+                }
             }),
             None => Either::Right(SelectGroup)
         }}
+        // After the chat, which registers the subscriptions of its group
+        <LiveConnection state=live.connection>
+            {move || {
+                account
+                    .user()
+                    .and_then(|account| account.id().map(str::to_string))
+                    .map(|user_id| view! { <GroupEvents user_id on_event=on_group_event/> })
+            }}
+            <ChatSubscriptions/>
+        </LiveConnection>
+    }
+}
+
+/// Applies a live event to the group list, keeping the most recently active group first.
+fn apply_group_event(groups: &mut Vec<GroupInfo>, event: UserEvent) {
+    match event {
+        UserEvent::MessagePreview {
+            group_id,
+            text,
+            time,
+        } => {
+            if let Some(group) = groups.iter_mut().find(|group| group.id == group_id) {
+                group.last_message = text;
+                group.last_activity = time;
+            }
+        }
+        UserEvent::AddedToGroup(added) => {
+            groups.retain(|group| group.id != added.id);
+            groups.push(added);
+        }
+        UserEvent::RemovedFromGroup { group_id } => groups.retain(|group| group.id != group_id),
+        UserEvent::GroupRenamed { group_id, name } => {
+            if let Some(group) = groups.iter_mut().find(|group| group.id == group_id) {
+                group.name = name;
+            }
+        }
+        UserEvent::TopicChanged { group_id, topic } => {
+            if let Some(group) = groups.iter_mut().find(|group| group.id == group_id) {
+                group.topic = topic;
+            }
+        }
+        UserEvent::MessageTtlChanged { group_id, ttl_secs } => {
+            if let Some(group) = groups.iter_mut().find(|group| group.id == group_id) {
+                group.message_ttl_secs = ttl_secs;
+            }
+        }
     }
+    groups.sort_by_key(|group| std::cmp::Reverse(group.last_activity));
 }
@@ -1,5 +1,6 @@
 use api::get_pow;
 use api::server_fn::login::Login;
+use api::server_fn::oidc::list_oidc_providers;
 use leptos::ev::SubmitEvent;
 use leptos::prelude::*;
 use leptos_captcha::Captcha;
@@ -53,6 +54,8 @@ pub fn LoginPage() -> impl IntoView {
                 .unwrap_or("/".to_string())
         })
     });
+    let error = Signal::derive(move || query.with(|q| q.get("error")));
+    let providers = Resource::new(|| (), |_| list_oidc_providers());
 
     view! {
         <main class="auth-area">
@@ -89,6 +92,30 @@ pub fn LoginPage() -> impl IntoView {
                                 </Button>
                             </div>
                         </form>
+                        {move || error.get().map(|error| view! {
+                            <p class=login_styles::ERROR>{error}</p>
+                        })}
+                        <Suspense>
+                            {move || providers.and_then(|providers| {
+                                (!providers.is_empty()).then(|| {
+                                    let providers = providers.to_owned();
+                                    view! {
+                                        <div class=login_styles::PROVIDERS>
+                                            <span>"or continue with"</span>
+                                            {providers.into_iter().map(|provider| view! {
+                                                <a
+                                                    rel="external"
+                                                    class=login_styles::PROVIDER
+                                                    href=move || provider.login_url(&next.get())
+                                                >
+                                                    {provider.name.clone()}
+                                                </a>
+                                            }).collect_view()}
+                                        </div>
+                                    }
+                                })
+                            })}
+                        </Suspense>
                     </CardBody>
                 </Card>
             </div>
@@ -0,0 +1,82 @@
+use crate::components::{
+    api_tokens::ApiTokens,
+    blocked_users::BlockedUsers,
+    card::{Card, CardBody, CardHeader},
+    header::{HeaderContext, Page},
+    identities::Identities,
+    profile::ProfileSettings,
+    sessions::Sessions,
+};
+use leptos::prelude::*;
+
+#[component]
+pub fn SettingsPage() -> impl IntoView {
+    let header = expect_context::<HeaderContext>();
+    Effect::new(move |_| {
+        header.switch_page(Page::Settings);
+    });
+    view! {
+        <main class="settings-page">
+            <Card>
+                <CardHeader>
+                    <h2>"Profile"</h2>
+                </CardHeader>
+                <CardBody>
+                    <p>"Other members see your display name, bio and avatar when they click your name in a chat."</p>
+                    <ProfileSettings/>
+                </CardBody>
+            </Card>
+            <Card>
+                <CardHeader>
+                    <h2>"Blocked users"</h2>
+                </CardHeader>
+                <CardBody>
+                    <p>"You don't see messages from blocked users. They aren't notified."</p>
+                    <BlockedUsers/>
+                </CardBody>
+            </Card>
+            <Card>
+                <CardHeader>
+                    <h2>"Active sessions"</h2>
+                </CardHeader>
+                <CardBody>
+                    <p>"These devices are currently signed in to your account. Revoke any session you don't recognize."</p>
+                    <Sessions/>
+                </CardBody>
+            </Card>
+            <Card>
+                <CardHeader>
+                    <h2>"Single sign-on"</h2>
+                </CardHeader>
+                <CardBody>
+                    <p>"Sign in with your identity provider instead of your password."</p>
+                    <Identities/>
+                </CardBody>
+            </Card>
+            <Card>
+                <CardHeader>
+                    <h2>"Personal access tokens"</h2>
+                </CardHeader>
+                <CardBody>
+                    <p>"Tokens let bots and scripts call the API as you, limited to the selected scopes."</p>
+                    <ApiTokens/>
+                </CardBody>
+            </Card>
+        </main>
+
+        <style>
+            {r#"
+                .settings-page {
+                    flex-grow: 1;
+                    display: flex;
+                    flex-direction: column;
+                    gap: 1.5rem;
+                    padding: 2rem;
+                    max-width: 800px;
+                    margin: 0 auto;
+                    width: 100%;
+                }
+            "#}
+        </style>
+    }
+}
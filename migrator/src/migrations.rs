@@ -1,6 +1,18 @@
 pub mod m0000_setup;
 pub mod m0001_chat;
 pub mod m0002_join_code;
+pub mod m0003_user_sessions;
+pub mod m0004_api_tokens;
+pub mod m0005_user_identities;
+pub mod m0006_user_profiles;
+pub mod m0007_user_blocks;
+pub mod m0008_group_webhooks;
+pub mod m0009_outgoing_webhooks;
+pub mod m0010_group_topics;
+pub mod m0011_polls;
+pub mod m0012_scheduled_messages;
+pub mod m0013_message_retention;
+pub mod m0014_link_previews;
 
 use sqlx_migrator::{Migration, vec_box};
 
@@ -9,5 +21,17 @@ pub fn migrations() -> Vec<Box<dyn Migration<sqlx::Sqlite>>> {
         m0000_setup::SetupMigration,
         m0001_chat::ChatMigration,
         m0002_join_code::JoinCodeMigration,
+        m0003_user_sessions::UserSessionsMigration,
+        m0004_api_tokens::ApiTokensMigration,
+        m0005_user_identities::UserIdentitiesMigration,
+        m0006_user_profiles::UserProfilesMigration,
+        m0007_user_blocks::UserBlocksMigration,
+        m0008_group_webhooks::GroupWebhooksMigration,
+        m0009_outgoing_webhooks::OutgoingWebhooksMigration,
+        m0010_group_topics::GroupTopicsMigration,
+        m0011_polls::PollsMigration,
+        m0012_scheduled_messages::ScheduledMessagesMigration,
+        m0013_message_retention::MessageRetentionMigration,
+        m0014_link_previews::LinkPreviewsMigration,
     ]
 }
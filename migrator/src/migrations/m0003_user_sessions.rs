@@ -0,0 +1,59 @@
+use sqlx_migrator::error::Error;
+use sqlx_migrator::operation::Operation;
+use sqlx_migrator::vec_box;
+
+use crate::migrations::m0002_join_code::JoinCodeMigration;
+
+pub(crate) struct UserSessionsOperation;
+pub(crate) struct UserSessionsMigration;
+
+#[async_trait::async_trait]
+impl Operation<sqlx::Sqlite> for UserSessionsOperation {
+    // Up migration: track device metadata for every authenticated session
+    async fn up(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        // `session_id` is the axum_session cookie value and never leaves the server,
+        // `id` is the public handle used by the account settings UI.
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS user_sessions (
+                id           BLOB NOT NULL PRIMARY KEY,
+                session_id   VARCHAR(128) NOT NULL UNIQUE,
+                user_id      BLOB NOT NULL,
+                user_agent   TEXT,
+                ip_address   VARCHAR(64),
+                created_at   DATETIME NOT NULL,
+                last_seen_at DATETIME NOT NULL,
+                revoked_at   DATETIME,
+                FOREIGN KEY (user_id) REFERENCES users(id)
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_user_sessions_user_id ON user_sessions(user_id);",
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        Ok(())
+    }
+
+    // Down migration: drop session metadata
+    async fn down(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("DROP INDEX IF EXISTS idx_user_sessions_user_id")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("DROP TABLE IF EXISTS user_sessions")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+}
+
+sqlx_migrator::sqlite_migration!(
+    UserSessionsMigration,
+    "main",
+    "user_sessions",
+    vec_box![JoinCodeMigration],
+    vec_box![UserSessionsOperation]
+);
@@ -0,0 +1,58 @@
+use sqlx_migrator::error::Error;
+use sqlx_migrator::operation::Operation;
+use sqlx_migrator::vec_box;
+
+use crate::migrations::m0003_user_sessions::UserSessionsMigration;
+
+pub(crate) struct ApiTokensOperation;
+pub(crate) struct ApiTokensMigration;
+
+#[async_trait::async_trait]
+impl Operation<sqlx::Sqlite> for ApiTokensOperation {
+    // Up migration: personal access tokens for bots and scripts
+    async fn up(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        // Only the SHA-256 hash of a token is stored, `prefix` lets users recognize it.
+        // `scopes` is a comma separated list of scope names.
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS api_tokens (
+                id           BLOB NOT NULL PRIMARY KEY,
+                user_id      BLOB NOT NULL,
+                name         VARCHAR(64) NOT NULL,
+                token_hash   VARCHAR(64) NOT NULL UNIQUE,
+                prefix       VARCHAR(16) NOT NULL,
+                scopes       TEXT NOT NULL,
+                created_at   DATETIME NOT NULL,
+                expires_at   DATETIME,
+                last_used_at DATETIME,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_api_tokens_user_id ON api_tokens(user_id);")
+            .execute(&mut *connection)
+            .await?;
+
+        Ok(())
+    }
+
+    // Down migration: drop personal access tokens
+    async fn down(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("DROP INDEX IF EXISTS idx_api_tokens_user_id")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("DROP TABLE IF EXISTS api_tokens")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+}
+
+sqlx_migrator::sqlite_migration!(
+    ApiTokensMigration,
+    "main",
+    "api_tokens",
+    vec_box![UserSessionsMigration],
+    vec_box![ApiTokensOperation]
+);
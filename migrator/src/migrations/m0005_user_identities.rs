@@ -0,0 +1,59 @@
+use sqlx_migrator::error::Error;
+use sqlx_migrator::operation::Operation;
+use sqlx_migrator::vec_box;
+
+use crate::migrations::m0004_api_tokens::ApiTokensMigration;
+
+pub(crate) struct UserIdentitiesOperation;
+pub(crate) struct UserIdentitiesMigration;
+
+#[async_trait::async_trait]
+impl Operation<sqlx::Sqlite> for UserIdentitiesOperation {
+    // Up migration: link users to accounts of external OpenID Connect providers
+    async fn up(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        // `created_account` marks identities that signed up the user,
+        // those users have no usable password.
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS user_identities (
+                id              BLOB NOT NULL PRIMARY KEY,
+                user_id         BLOB NOT NULL,
+                provider        VARCHAR(64) NOT NULL,
+                subject         VARCHAR(255) NOT NULL,
+                email           VARCHAR(255),
+                created_account BOOLEAN NOT NULL DEFAULT FALSE,
+                created_at      DATETIME NOT NULL,
+                UNIQUE (provider, subject),
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_user_identities_user_id ON user_identities(user_id);",
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        Ok(())
+    }
+
+    // Down migration: drop identity links
+    async fn down(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("DROP INDEX IF EXISTS idx_user_identities_user_id")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("DROP TABLE IF EXISTS user_identities")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+}
+
+sqlx_migrator::sqlite_migration!(
+    UserIdentitiesMigration,
+    "main",
+    "user_identities",
+    vec_box![ApiTokensMigration],
+    vec_box![UserIdentitiesOperation]
+);
@@ -0,0 +1,65 @@
+use sqlx_migrator::error::Error;
+use sqlx_migrator::operation::Operation;
+use sqlx_migrator::vec_box;
+
+use crate::migrations::m0005_user_identities::UserIdentitiesMigration;
+
+pub(crate) struct UserProfilesOperation;
+pub(crate) struct UserProfilesMigration;
+
+#[async_trait::async_trait]
+impl Operation<sqlx::Sqlite> for UserProfilesOperation {
+    // Up migration: profile fields and uploaded avatars
+    async fn up(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("ALTER TABLE users ADD COLUMN display_name VARCHAR(64);")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("ALTER TABLE users ADD COLUMN bio TEXT;")
+            .execute(&mut *connection)
+            .await?;
+        // Doubles as cache buster of the avatar url
+        sqlx::query("ALTER TABLE users ADD COLUMN avatar_updated_at DATETIME;")
+            .execute(&mut *connection)
+            .await?;
+
+        // Avatars are small after resizing, so they live in the database
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS user_avatars (
+                user_id      BLOB NOT NULL PRIMARY KEY,
+                content_type VARCHAR(32) NOT NULL,
+                data         BLOB NOT NULL,
+                updated_at   DATETIME NOT NULL,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        Ok(())
+    }
+
+    // Down migration: drop profile fields and avatars
+    async fn down(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("DROP TABLE IF EXISTS user_avatars")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("ALTER TABLE users DROP COLUMN avatar_updated_at")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("ALTER TABLE users DROP COLUMN bio")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("ALTER TABLE users DROP COLUMN display_name")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+}
+
+sqlx_migrator::sqlite_migration!(
+    UserProfilesMigration,
+    "main",
+    "user_profiles",
+    vec_box![UserIdentitiesMigration],
+    vec_box![UserProfilesOperation]
+);
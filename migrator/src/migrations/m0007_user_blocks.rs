@@ -0,0 +1,45 @@
+use sqlx_migrator::error::Error;
+use sqlx_migrator::operation::Operation;
+use sqlx_migrator::vec_box;
+
+use crate::migrations::m0006_user_profiles::UserProfilesMigration;
+
+pub(crate) struct UserBlocksOperation;
+pub(crate) struct UserBlocksMigration;
+
+#[async_trait::async_trait]
+impl Operation<sqlx::Sqlite> for UserBlocksOperation {
+    // Up migration: users hiding other users
+    async fn up(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS user_blocks (
+                blocker_id BLOB NOT NULL,
+                blocked_id BLOB NOT NULL,
+                created_at DATETIME NOT NULL,
+                PRIMARY KEY (blocker_id, blocked_id),
+                FOREIGN KEY (blocker_id) REFERENCES users(id) ON DELETE CASCADE,
+                FOREIGN KEY (blocked_id) REFERENCES users(id) ON DELETE CASCADE
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        Ok(())
+    }
+
+    // Down migration: drop blocks
+    async fn down(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("DROP TABLE IF EXISTS user_blocks")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+}
+
+sqlx_migrator::sqlite_migration!(
+    UserBlocksMigration,
+    "main",
+    "user_blocks",
+    vec_box![UserProfilesMigration],
+    vec_box![UserBlocksOperation]
+);
@@ -0,0 +1,59 @@
+use sqlx_migrator::error::Error;
+use sqlx_migrator::operation::Operation;
+use sqlx_migrator::vec_box;
+
+use crate::migrations::m0007_user_blocks::UserBlocksMigration;
+
+pub(crate) struct GroupWebhooksOperation;
+pub(crate) struct GroupWebhooksMigration;
+
+#[async_trait::async_trait]
+impl Operation<sqlx::Sqlite> for GroupWebhooksOperation {
+    // Up migration: incoming webhooks posting into groups
+    async fn up(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        // Every webhook posts as its own user, which nobody can sign in as
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS group_webhooks (
+                id           BLOB NOT NULL PRIMARY KEY,
+                group_id     BLOB NOT NULL,
+                user_id      BLOB NOT NULL,
+                name         VARCHAR(64) NOT NULL,
+                token_hash   VARCHAR(64) NOT NULL UNIQUE,
+                prefix       VARCHAR(16) NOT NULL,
+                created_by   BLOB NOT NULL,
+                created_at   DATETIME NOT NULL,
+                last_used_at DATETIME,
+                FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+                FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE CASCADE
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+        // Name shown instead of the username of the sender, set by webhooks
+        sqlx::query("ALTER TABLE messages ADD COLUMN sender_name VARCHAR(64);")
+            .execute(&mut *connection)
+            .await?;
+
+        Ok(())
+    }
+
+    // Down migration: drop webhooks
+    async fn down(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("ALTER TABLE messages DROP COLUMN sender_name")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("DROP TABLE IF EXISTS group_webhooks")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+}
+
+sqlx_migrator::sqlite_migration!(
+    GroupWebhooksMigration,
+    "main",
+    "group_webhooks",
+    vec_box![UserBlocksMigration],
+    vec_box![GroupWebhooksOperation]
+);
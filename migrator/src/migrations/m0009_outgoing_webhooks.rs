@@ -0,0 +1,77 @@
+use sqlx_migrator::error::Error;
+use sqlx_migrator::operation::Operation;
+use sqlx_migrator::vec_box;
+
+use crate::migrations::m0008_group_webhooks::GroupWebhooksMigration;
+
+pub(crate) struct OutgoingWebhooksOperation;
+pub(crate) struct OutgoingWebhooksMigration;
+
+#[async_trait::async_trait]
+impl Operation<sqlx::Sqlite> for OutgoingWebhooksOperation {
+    // Up migration: webhooks called on group events and their delivery queue
+    async fn up(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        // The secret signs the payloads, so unlike tokens it is kept as is
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS outgoing_webhooks (
+                id         BLOB NOT NULL PRIMARY KEY,
+                group_id   BLOB NOT NULL,
+                url        TEXT NOT NULL,
+                secret     VARCHAR(64) NOT NULL,
+                events     TEXT NOT NULL,
+                created_by BLOB NOT NULL,
+                created_at DATETIME NOT NULL,
+                FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+                FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE CASCADE
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                id              BLOB NOT NULL PRIMARY KEY,
+                webhook_id      BLOB NOT NULL,
+                event           VARCHAR(32) NOT NULL,
+                payload         TEXT NOT NULL,
+                attempts        INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at DATETIME,
+                last_error      TEXT,
+                created_at      DATETIME NOT NULL,
+                delivered_at    DATETIME,
+                FOREIGN KEY (webhook_id) REFERENCES outgoing_webhooks(id) ON DELETE CASCADE
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+        // Finished deliveries have no next attempt
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS webhook_deliveries_due
+                ON webhook_deliveries (next_attempt_at)
+                WHERE next_attempt_at IS NOT NULL;",
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        Ok(())
+    }
+
+    // Down migration: drop outgoing webhooks
+    async fn down(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("DROP TABLE IF EXISTS webhook_deliveries")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("DROP TABLE IF EXISTS outgoing_webhooks")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+}
+
+sqlx_migrator::sqlite_migration!(
+    OutgoingWebhooksMigration,
+    "main",
+    "outgoing_webhooks",
+    vec_box![GroupWebhooksMigration],
+    vec_box![OutgoingWebhooksOperation]
+);
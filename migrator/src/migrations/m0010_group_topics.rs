@@ -0,0 +1,35 @@
+use sqlx_migrator::error::Error;
+use sqlx_migrator::operation::Operation;
+use sqlx_migrator::vec_box;
+
+use crate::migrations::m0009_outgoing_webhooks::OutgoingWebhooksMigration;
+
+pub(crate) struct GroupTopicsOperation;
+pub(crate) struct GroupTopicsMigration;
+
+#[async_trait::async_trait]
+impl Operation<sqlx::Sqlite> for GroupTopicsOperation {
+    // Up migration: topic shown above the chat of a group
+    async fn up(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("ALTER TABLE groups ADD COLUMN topic VARCHAR(256);")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+
+    // Down migration: drop topics
+    async fn down(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("ALTER TABLE groups DROP COLUMN topic")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+}
+
+sqlx_migrator::sqlite_migration!(
+    GroupTopicsMigration,
+    "main",
+    "group_topics",
+    vec_box![OutgoingWebhooksMigration],
+    vec_box![GroupTopicsOperation]
+);
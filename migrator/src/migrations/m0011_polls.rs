@@ -0,0 +1,81 @@
+use sqlx_migrator::error::Error;
+use sqlx_migrator::operation::Operation;
+use sqlx_migrator::vec_box;
+
+use crate::migrations::m0010_group_topics::GroupTopicsMigration;
+
+pub(crate) struct PollsOperation;
+pub(crate) struct PollsMigration;
+
+#[async_trait::async_trait]
+impl Operation<sqlx::Sqlite> for PollsOperation {
+    // Up migration: polls attached to messages, their options and votes
+    async fn up(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        // The message holds the question, so polls show up like any other message
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS polls (
+                message_id      BLOB NOT NULL PRIMARY KEY,
+                multiple_choice BOOLEAN NOT NULL DEFAULT FALSE,
+                anonymous       BOOLEAN NOT NULL DEFAULT FALSE,
+                closes_at       DATETIME,
+                FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS poll_options (
+                id       BLOB NOT NULL PRIMARY KEY,
+                poll_id  BLOB NOT NULL,
+                position INTEGER NOT NULL,
+                text     VARCHAR(256) NOT NULL,
+                FOREIGN KEY (poll_id) REFERENCES polls(message_id) ON DELETE CASCADE
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS poll_votes (
+                poll_id    BLOB NOT NULL,
+                option_id  BLOB NOT NULL,
+                user_id    BLOB NOT NULL,
+                created_at DATETIME NOT NULL,
+                PRIMARY KEY (option_id, user_id),
+                FOREIGN KEY (poll_id) REFERENCES polls(message_id) ON DELETE CASCADE,
+                FOREIGN KEY (option_id) REFERENCES poll_options(id) ON DELETE CASCADE,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS poll_votes_poll ON poll_votes (poll_id, user_id);")
+            .execute(&mut *connection)
+            .await?;
+
+        Ok(())
+    }
+
+    // Down migration: drop polls
+    async fn down(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("DROP TABLE IF EXISTS poll_votes")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("DROP TABLE IF EXISTS poll_options")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("DROP TABLE IF EXISTS polls")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+}
+
+sqlx_migrator::sqlite_migration!(
+    PollsMigration,
+    "main",
+    "polls",
+    vec_box![GroupTopicsMigration],
+    vec_box![PollsOperation]
+);
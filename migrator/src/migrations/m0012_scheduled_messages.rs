@@ -0,0 +1,54 @@
+use sqlx_migrator::error::Error;
+use sqlx_migrator::operation::Operation;
+use sqlx_migrator::vec_box;
+
+use crate::migrations::m0011_polls::PollsMigration;
+
+pub(crate) struct ScheduledMessagesOperation;
+pub(crate) struct ScheduledMessagesMigration;
+
+#[async_trait::async_trait]
+impl Operation<sqlx::Sqlite> for ScheduledMessagesOperation {
+    // Up migration: messages waiting to be sent at a later time
+    async fn up(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        // The id becomes the id of the sent message, so sending twice has no effect
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS scheduled_messages (
+                id            BLOB NOT NULL PRIMARY KEY,
+                group_id      BLOB NOT NULL,
+                user_id       BLOB NOT NULL,
+                content       TEXT NOT NULL,
+                send_at       DATETIME NOT NULL,
+                claimed_until DATETIME,
+                created_at    DATETIME NOT NULL,
+                FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS scheduled_messages_due ON scheduled_messages (send_at);",
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        Ok(())
+    }
+
+    // Down migration: drop scheduled messages
+    async fn down(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("DROP TABLE IF EXISTS scheduled_messages")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+}
+
+sqlx_migrator::sqlite_migration!(
+    ScheduledMessagesMigration,
+    "main",
+    "scheduled_messages",
+    vec_box![PollsMigration],
+    vec_box![ScheduledMessagesOperation]
+);
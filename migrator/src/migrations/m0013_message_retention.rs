@@ -0,0 +1,44 @@
+use sqlx_migrator::error::Error;
+use sqlx_migrator::operation::Operation;
+use sqlx_migrator::vec_box;
+
+use crate::migrations::m0012_scheduled_messages::ScheduledMessagesMigration;
+
+pub(crate) struct MessageRetentionOperation;
+pub(crate) struct MessageRetentionMigration;
+
+#[async_trait::async_trait]
+impl Operation<sqlx::Sqlite> for MessageRetentionOperation {
+    // Up migration: groups whose messages disappear after a while
+    async fn up(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("ALTER TABLE groups ADD COLUMN message_ttl_secs INTEGER;")
+            .execute(&mut *connection)
+            .await?;
+        // Expired messages are looked up by the time they were sent
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS messages_group_created_at ON messages (group_id, created_at);",
+        )
+        .execute(&mut *connection)
+        .await?;
+        Ok(())
+    }
+
+    // Down migration: keep messages forever
+    async fn down(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("DROP INDEX IF EXISTS messages_group_created_at")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("ALTER TABLE groups DROP COLUMN message_ttl_secs")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+}
+
+sqlx_migrator::sqlite_migration!(
+    MessageRetentionMigration,
+    "main",
+    "message_retention",
+    vec_box![ScheduledMessagesMigration],
+    vec_box![MessageRetentionOperation]
+);
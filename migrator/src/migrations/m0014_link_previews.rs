@@ -0,0 +1,45 @@
+use sqlx_migrator::error::Error;
+use sqlx_migrator::operation::Operation;
+use sqlx_migrator::vec_box;
+
+use crate::migrations::m0013_message_retention::MessageRetentionMigration;
+
+pub(crate) struct LinkPreviewsOperation;
+pub(crate) struct LinkPreviewsMigration;
+
+#[async_trait::async_trait]
+impl Operation<sqlx::Sqlite> for LinkPreviewsOperation {
+    // Up migration: metadata of linked pages, shared by all messages linking them
+    async fn up(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        // Pages without metadata are kept too, so they aren't fetched again for every message
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS link_previews (
+                url         TEXT NOT NULL PRIMARY KEY,
+                title       TEXT,
+                description TEXT,
+                image_url   TEXT,
+                site_name   TEXT,
+                fetched_at  DATETIME NOT NULL
+            );",
+        )
+        .execute(&mut *connection)
+        .await?;
+        Ok(())
+    }
+
+    // Down migration: drop link previews
+    async fn down(&self, connection: &mut sqlx::SqliteConnection) -> Result<(), Error> {
+        sqlx::query("DROP TABLE IF EXISTS link_previews")
+            .execute(&mut *connection)
+            .await?;
+        Ok(())
+    }
+}
+
+sqlx_migrator::sqlite_migration!(
+    LinkPreviewsMigration,
+    "main",
+    "link_previews",
+    vec_box![MessageRetentionMigration],
+    vec_box![LinkPreviewsOperation]
+);
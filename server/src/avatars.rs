@@ -0,0 +1,29 @@
+use api::AppState;
+use axum::{
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use uuid::Uuid;
+
+/// Serves uploaded avatars, urls carry a version so they can be cached forever.
+pub async fn avatar(State(state): State<AppState>, Path(user_id): Path<Uuid>) -> Response {
+    match state.profile_repository.get_avatar(user_id).await {
+        Ok(Some(avatar)) => (
+            [
+                (header::CONTENT_TYPE, avatar.content_type),
+                (
+                    header::CACHE_CONTROL,
+                    "public, max-age=31536000, immutable".to_string(),
+                ),
+            ],
+            avatar.data,
+        )
+            .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(error) => {
+            log::error!("Failed to load avatar of {user_id}: {error}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use api::{AppState, TokenAuth};
+use axum::{
+    extract::{FromRequestParts, OptionalFromRequestParts, Request},
+    http::{StatusCode, header::AUTHORIZATION, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Authenticates `Authorization: Bearer <token>` requests with a personal access token.
+pub struct BearerToken(pub TokenAuth);
+
+impl FromRequestParts<AppState> for BearerToken {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        <Self as OptionalFromRequestParts<AppState>>::from_request_parts(parts, state)
+            .await?
+            .ok_or((StatusCode::UNAUTHORIZED, "Missing bearer token"))
+    }
+}
+
+impl OptionalFromRequestParts<AppState> for BearerToken {
+    type Rejection = (StatusCode, &'static str);
+
+    /// Requests without an `Authorization` header are not rejected,
+    /// but a header with an invalid token is.
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let Some(header) = parts.headers.get(AUTHORIZATION) else {
+            return Ok(None);
+        };
+        if !state.config.features.api_tokens {
+            return Err((StatusCode::FORBIDDEN, "Personal access tokens are disabled"));
+        }
+        let Some(secret) = header
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+        else {
+            return Err((StatusCode::UNAUTHORIZED, "Malformed authorization header"));
+        };
+        match TokenAuth::authenticate(state, secret).await {
+            Ok(Some(token_auth)) => Ok(Some(BearerToken(token_auth))),
+            Ok(None) => Err((StatusCode::UNAUTHORIZED, "Invalid or expired token")),
+            Err(error) => {
+                log::error!("Failed to authenticate token: {error}");
+                Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to authenticate token"))
+            }
+        }
+    }
+}
+
+/// Makes the token of bearer requests available to server functions.
+pub async fn bearer_auth(
+    token: Result<Option<BearerToken>, (StatusCode, &'static str)>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match token {
+        Ok(Some(BearerToken(token_auth))) => {
+            request.extensions_mut().insert(token_auth);
+            next.run(request).await
+        }
+        Ok(None) => next.run(request).await,
+        Err(rejection) => rejection.into_response(),
+    }
+}
@@ -0,0 +1,73 @@
+use api::{
+    AppState, AuthSession, TokenAuth,
+    export::{ExportError, ExportFormat, export},
+    server_fn::tokens::TokenScope,
+};
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+/// Downloads the full history of a group, for members only.
+///
+/// Accepts the session of the browser, or a personal access token with the
+/// `messages:read` scope.
+pub async fn group_history(
+    State(state): State<AppState>,
+    auth: AuthSession,
+    token: Option<Extension<TokenAuth>>,
+    Path(group_id): Path<Uuid>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let user_id = match token {
+        Some(Extension(token_auth)) if !token_auth.token.has_scope(TokenScope::MessagesRead) => {
+            let error = format!("Token is missing the {} scope", TokenScope::MessagesRead);
+            return (StatusCode::FORBIDDEN, Json(json!({ "error": error }))).into_response();
+        }
+        Some(Extension(token_auth)) => Some(token_auth.user.id),
+        None => auth.current_user.map(|user| user.id),
+    };
+    let result = match user_id {
+        Some(user_id) => export(&state, user_id, group_id, query.format).await,
+        None => Err(ExportError::Unauthorized),
+    };
+    match result {
+        Ok((file_name, chunks)) => (
+            [
+                (
+                    header::CONTENT_TYPE,
+                    query.format.content_type().to_string(),
+                ),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{file_name}\""),
+                ),
+            ],
+            Body::from_stream(chunks),
+        )
+            .into_response(),
+        Err(error) => {
+            let status = match &error {
+                ExportError::Unauthorized => StatusCode::UNAUTHORIZED,
+                ExportError::NotAMember => StatusCode::NOT_FOUND,
+                ExportError::Database(error) => {
+                    log::error!("Failed to export group {group_id}: {error}");
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            (status, Json(json!({ "error": error.to_string() }))).into_response()
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! `server import <slack|discord> <file>...`, imports chat history into the
+//! configured database instead of starting the server.
+use api::import::{ImportError, ImportSource, ImportedChannel, discord, slack};
+use migrator::migrate;
+
+const USAGE: &str = "Usage: server import slack <export.zip>...\n       server import discord <channel.json>...";
+
+/// Runs the import and returns the exit code of the process.
+pub async fn run(config: &api::config::Config, args: &[String]) -> i32 {
+    let source = match args.first().map(String::as_str) {
+        Some("slack") => ImportSource::Slack,
+        Some("discord") => ImportSource::Discord,
+        _ => {
+            eprintln!("{USAGE}");
+            return 2;
+        }
+    };
+    let files = &args[1..];
+    if files.is_empty() {
+        eprintln!("{USAGE}");
+        return 2;
+    }
+
+    let mut channels = Vec::new();
+    for file in files {
+        match read(source, file).await {
+            Ok(read) => channels.extend(read),
+            Err(error) => {
+                log::error!("{file}: {error}");
+                return 1;
+            }
+        }
+    }
+
+    let mut pool = match api::connect(&config.database).await {
+        Ok(pool) => pool,
+        Err(error) => {
+            log::error!("{error}");
+            return 1;
+        }
+    };
+    if let Err(error) = migrate(&mut pool).await {
+        log::error!("Database migration failed: {error}");
+        return 1;
+    }
+    match api::import::import(&pool, source, channels).await {
+        Ok(report) => {
+            log::info!(
+                "Imported {} messages ({} skipped) into {} new and {} existing groups, \
+                 matched {} users and created {} placeholders",
+                report.messages_imported,
+                report.messages_skipped,
+                report.groups_created,
+                report.groups_reused,
+                report.users_matched,
+                report.placeholders_created,
+            );
+            0
+        }
+        Err(error) => {
+            log::error!("Import failed, nothing was written: {error}");
+            1
+        }
+    }
+}
+
+async fn read(source: ImportSource, file: &str) -> Result<Vec<ImportedChannel>, ImportError> {
+    let data = tokio::fs::read(file).await?;
+    match source {
+        ImportSource::Slack => slack::parse(data),
+        ImportSource::Discord => Ok(vec![discord::parse(file, &data)?]),
+    }
+}
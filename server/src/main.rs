@@ -1,7 +1,12 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
 use api::AppState;
+use api::ws::AuthorizedWebsocket;
 use app::*;
-use axum::extract::Path;
+use axum::extract::{DefaultBodyLimit, Path};
 use axum::http::HeaderMap;
+use axum::middleware;
 use axum::response::{IntoResponse, Response as AxumResponse};
 use axum::routing::{get, post};
 use axum::{
@@ -10,12 +15,22 @@ use axum::{
 };
 use leptos::logging::log;
 use leptos::prelude::*;
+use leptos::server_fn::ServerFn;
 use leptos_axum::{
     LeptosRoutes, generate_route_list, generate_route_list_with_exclusions_and_ssg_and_context,
     handle_server_fns_with_context,
 };
+use leptos_ws::LeptosWsWebsocket;
 use migrator::migrate;
 
+mod avatars;
+mod bearer;
+mod export;
+mod import;
+mod oidc;
+mod sessions;
+mod webhooks;
+
 async fn leptos_routes_handler(state: State<AppState>, req: Request) -> AxumResponse {
     let state1 = state.0.clone();
     let options2 = state.clone().0.options.clone();
@@ -47,21 +62,59 @@ async fn server_fn_handler(
     )
     .await
 }
+/// `leptos_ws` clients always connect to the websocket endpoint of the library,
+/// serve them with the authenticated endpoint of the api instead.
+async fn websocket_handler(State(state): State<AppState>, mut request: Request) -> impl IntoResponse {
+    *request.uri_mut() = AuthorizedWebsocket::PATH
+        .parse()
+        .expect("websocket path is a valid uri");
+    handle_server_fns_with_context(
+        move || {
+            provide_context(state.clone());
+            provide_context(state.options.clone());
+            provide_context(state.server_signals.clone());
+        },
+        request,
+    )
+    .await
+}
 #[tokio::main]
 async fn main() {
     // Set up logging
     simple_logger::init_with_level(log::Level::Info).unwrap();
 
     dotenvy::dotenv().ok();
-    let uri = std::env::var("DATABASE_URL").unwrap();
+    let config = match api::config::Config::load() {
+        Ok(config) => Arc::new(config),
+        Err(error) => {
+            log::error!("{error}");
+            std::process::exit(1);
+        }
+    };
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().is_some_and(|command| command == "import") {
+        std::process::exit(import::run(&config, &args[1..]).await);
+    }
     let conf = get_configuration(None).unwrap();
     let addr = conf.leptos_options.site_addr;
 
-    let mut state = AppState::new(&uri, conf.leptos_options.clone(), None).await;
+    let mut state = match AppState::new(config.clone(), conf.leptos_options.clone(), None).await {
+        Ok(state) => state,
+        Err(error) => {
+            log::error!("{error}");
+            std::process::exit(1);
+        }
+    };
+    api::ws::spawn_heartbeat(state.server_signals.clone());
     match migrate(&mut state.pool.clone()).await {
         Ok(_) => log::info!("Database migration completed successfully."),
         Err(e) => log::error!("Database migration failed: {:?}", e),
     }
+    // Started after the migration, which creates the tables they use
+    api::scheduler::spawn(state.clone());
+    api::janitor::spawn(state.clone());
+    api::backplane::spawn_relay(state.clone());
+    api::outgoing_webhooks::spawn_worker(state.clone());
 
     let leptos_options = conf.leptos_options;
     leptos_captcha::spow::pow::Pow::init_random().unwrap();
@@ -76,14 +129,35 @@ async fn main() {
     state.routes = Some(routes.clone());
     let state3 = state.clone();
 
-    let session_store = api::get_session_store(state.pool.clone()).await;
+    let session_store = match api::get_session_store(state.pool.clone(), &config.session).await {
+        Ok(session_store) => session_store,
+        Err(error) => {
+            log::error!("{error}");
+            std::process::exit(1);
+        }
+    };
     let pool = state.pool.clone();
     leptos_styling::generate_style_sheets(leptos_options.clone());
-    let layer = api::get_auth_session(pool.clone()).await;
+    let layer = api::get_auth_session(pool.clone(), &config.session).await;
     let app = Router::new()
         .route("/api/{*fn_name}", post(server_fn_handler))
         .route("/api/{*fn_name}", get(server_fn_handler))
         .leptos_routes_with_handler(routes, get(leptos_routes_handler))
+        // Revoked sessions must not keep receiving group traffic or downloading its history
+        .route(LeptosWsWebsocket::PATH, get(websocket_handler))
+        .route("/groups/{group_id}/export", get(export::group_history))
+        // Only pages, server functions and the websocket count as activity, not assets or avatars
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            sessions::track_sessions,
+        ))
+        .route("/avatars/{user_id}", get(avatars::avatar))
+        .route(
+            api::server_fn::webhooks::WEBHOOK_ROUTE,
+            post(webhooks::receive),
+        )
+        .route("/auth/oidc/{provider}/login", get(oidc::login))
+        .route("/auth/oidc/{provider}/callback", get(oidc::callback))
         .fallback(leptos_axum::file_and_error_handler_with_context::<
             AppState,
             _,
@@ -94,6 +168,11 @@ async fn main() {
             },
             shell,
         ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            bearer::bearer_auth,
+        ))
+        .layer(DefaultBodyLimit::max(config.uploads.max_request_bytes))
         .with_state(state)
         .layer(layer)
         .layer(axum_session::SessionLayer::new(session_store));
@@ -102,7 +181,10 @@ async fn main() {
     // `axum::Server` is a re-export of `hyper::Server`
     log::info!("listening on http://{}", &addr);
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app.into_make_service())
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
         .await
         .unwrap();
 }
@@ -0,0 +1,120 @@
+use api::{
+    AppState, AuthSession,
+    oidc::{OIDC_FLOW_KEY, OidcError, OidcFlow, login_error_path, sign_in},
+};
+use axum::{
+    extract::{Path, Query, State},
+    response::Redirect,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct LoginQuery {
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+/// Only redirect to paths of this site after signing in. Browsers read a
+/// backslash like a slash, so `/\evil.com` would leave the site as well.
+fn local_path(next: Option<String>) -> String {
+    next.filter(|next| next.starts_with('/') && !next.starts_with("//") && !next.contains('\\'))
+        .unwrap_or_else(|| "/".to_string())
+}
+
+fn login_error(message: &str) -> Redirect {
+    Redirect::to(&login_error_path(message))
+}
+
+/// Redirects to the provider, signed in users link the identity to their account instead.
+pub async fn login(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    Query(query): Query<LoginQuery>,
+    auth: AuthSession,
+) -> Redirect {
+    let link_user_id = auth.current_user.as_ref().map(|user| user.id);
+    match state
+        .oidc
+        .authorize(&provider, local_path(query.next), link_user_id)
+        .await
+    {
+        Ok((url, flow)) => {
+            auth.session.set(OIDC_FLOW_KEY, flow);
+            Redirect::to(&url)
+        }
+        Err(error) => {
+            log::error!("Failed to start sign in with {provider}: {error}");
+            login_error(&error.to_string())
+        }
+    }
+}
+
+pub async fn callback(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    Query(query): Query<CallbackQuery>,
+    auth: AuthSession,
+) -> Redirect {
+    let Some(flow) = auth.session.get_remove::<OidcFlow>(OIDC_FLOW_KEY) else {
+        return login_error(&OidcError::MissingFlow.to_string());
+    };
+    if let Some(error) = query.error {
+        return login_error(&error);
+    }
+    let (Some(code), Some(csrf_state)) = (query.code, query.state) else {
+        return login_error("Invalid callback");
+    };
+    let result = async {
+        let identity = state
+            .oidc
+            .exchange(&provider, &flow, code, &csrf_state)
+            .await?;
+        sign_in(&state, identity, flow.link_user_id).await
+    }
+    .await;
+    match result {
+        Ok(user_id) => {
+            auth.login_user(user_id);
+            auth.remember_user(true);
+            Redirect::to(&flow.next)
+        }
+        Err(error) => {
+            log::error!("Sign in with {provider} failed: {error}");
+            login_error(&error.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::local_path;
+
+    #[test]
+    fn keeps_paths_of_this_site() {
+        assert_eq!(
+            local_path(Some("/groups/1?tab=chat".into())),
+            "/groups/1?tab=chat"
+        );
+        assert_eq!(local_path(None), "/");
+    }
+
+    #[test]
+    fn rejects_redirects_to_other_sites() {
+        for next in [
+            "https://evil.com",
+            "//evil.com",
+            "/\\evil.com",
+            "/\\/evil.com",
+            "\\\\evil.com",
+            "evil.com",
+        ] {
+            assert_eq!(local_path(Some(next.into())), "/", "{next}");
+        }
+    }
+}
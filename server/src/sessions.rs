@@ -0,0 +1,92 @@
+use std::net::{IpAddr, SocketAddr};
+
+use api::{AppState, AuthSession};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, header::USER_AGENT},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{Duration, Utc};
+
+/// How often the last seen timestamp of a session is written back.
+const TOUCH_INTERVAL: Duration = Duration::minutes(1);
+
+/// Records device metadata for authenticated sessions and logs out revoked sessions.
+pub async fn track_sessions(
+    State(state): State<AppState>,
+    auth: AuthSession,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(user_id) = auth.current_user.as_ref().map(|user| user.id) else {
+        return next.run(request).await;
+    };
+    let session_id = auth.session.get_session_id();
+    let user_agent = request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let ip_address = Some(client_ip(
+        request.headers(),
+        addr,
+        &state.config.trusted_proxies,
+    ));
+
+    match state
+        .session_repository
+        .get_by_session_id(&session_id)
+        .await
+    {
+        Ok(Some(session)) if session.is_revoked() || session.user_id != user_id => {
+            log::info!("Rejecting revoked session of user {user_id}");
+            auth.logout_user();
+            auth.session.destroy();
+            // Handlers further down must not see the user of the revoked session
+            if let Some(auth) = request.extensions_mut().get_mut::<AuthSession>() {
+                auth.current_user = None;
+            }
+        }
+        Ok(Some(session)) => {
+            let now = Utc::now();
+            if (now - session.last_seen_at > TOUCH_INTERVAL
+                || session.ip_address != ip_address
+                || session.user_agent != user_agent)
+                && let Err(error) = state
+                    .session_repository
+                    .touch(session.id, user_agent, ip_address, now)
+                    .await
+            {
+                log::error!("Failed to update session: {error}");
+            }
+        }
+        Ok(None) => {
+            let session = api::UserSession::new(session_id, user_id, user_agent, ip_address);
+            if let Err(error) = state.session_repository.create(session).await {
+                log::error!("Failed to record session: {error}");
+            }
+        }
+        Err(error) => log::error!("Failed to load session: {error}"),
+    }
+    next.run(request).await
+}
+
+/// The address shown to the user. Behind trusted proxies this is the last
+/// forwarded address that isn't one of them, anything before it could be made up.
+fn client_ip(headers: &HeaderMap, addr: SocketAddr, trusted_proxies: &[IpAddr]) -> String {
+    if !trusted_proxies.contains(&addr.ip()) {
+        return addr.ip().to_string();
+    }
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|value| value.trim().parse::<IpAddr>().ok())
+        .rev()
+        .find(|ip| !trusted_proxies.contains(ip))
+        .unwrap_or(addr.ip())
+        .to_string()
+}
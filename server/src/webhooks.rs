@@ -0,0 +1,45 @@
+use api::{
+    AppState,
+    webhooks::{WebhookError, WebhookPayload, post},
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+
+/// Posts the JSON payload into the group of the webhook with the token.
+pub async fn receive(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Json(payload): Json<WebhookPayload>,
+) -> Response {
+    match post(&state, &token, payload).await {
+        Ok(id) => (StatusCode::CREATED, Json(json!({ "id": id }))).into_response(),
+        Err(error) => {
+            let status = match &error {
+                WebhookError::Disabled | WebhookError::NotFound => StatusCode::NOT_FOUND,
+                WebhookError::InvalidUsername
+                | WebhookError::UsernameTaken
+                | WebhookError::InvalidAttachments
+                | WebhookError::InvalidContent(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                WebhookError::SlowDown { .. } => StatusCode::TOO_MANY_REQUESTS,
+                WebhookError::Database(error) => {
+                    log::error!("Failed to post webhook message: {error}");
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            let mut response =
+                (status, Json(json!({ "error": error.to_string() }))).into_response();
+            if let WebhookError::SlowDown { retry_after } = error {
+                response.headers_mut().insert(
+                    header::RETRY_AFTER,
+                    retry_after.as_secs().max(1).into(),
+                );
+            }
+            response
+        }
+    }
+}